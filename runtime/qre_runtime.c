@@ -0,0 +1,765 @@
+/*
+ * The runtime every QRE program is linked with.
+ *
+ * Objects start with the header the compiler lays out in front of every heap
+ * object: the type ID, then the reference count. Arrays store each element in a
+ * 64 bit slot, the generated code converts values to and from them.
+ *
+ * An object is freed when its reference count drops to 0, after the destructor
+ * the compiler generated for its type released the references it holds.
+ *
+ * Objects that only reference cycles keep alive are found by trial deletion
+ * (Bacon and Rajan, "Concurrent Cycle Collection in Reference Counted Systems").
+ * Every object whose count drops to a value above 0 may be the last reference
+ * into a garbage cycle and is remembered as a possible root. The collector
+ * subtracts the references the objects reachable from the roots hold to each
+ * other, and what is left at 0 is only referenced from garbage. It runs when
+ * `collect_cycles` is called, and whenever QRE_CYCLE_PERIOD possible roots were
+ * remembered, 10000 by default, 0 for never.
+ *
+ * Objects of shared types can be used by every thread and are retained and
+ * released atomically. They only reference other shared objects, so they are
+ * never part of a cycle. Everything else belongs to the thread that made it, and
+ * each thread has its own collector and weak references.
+ */
+
+#include <pthread.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+/* The exit code of a program that panicked. */
+#define QRE_PANIC_EXIT_CODE 101
+
+typedef struct {
+    uint32_t type_id;
+    /* The reference count in the low bits and the state of the object above them. */
+    uint32_t refcount;
+} qre_header;
+
+#define QRE_COUNT_MASK 0x07ffffffu
+/* Retained and released atomically, set when the object is made. */
+#define QRE_SHARED 0x08000000u
+/* The color the cycle collector gave the object. */
+#define QRE_COLOR_MASK 0x30000000u
+/* In use, or freed. */
+#define QRE_BLACK 0x00000000u
+/* Possibly part of a garbage cycle, its references from there are subtracted. */
+#define QRE_GRAY 0x10000000u
+/* Part of a garbage cycle. */
+#define QRE_WHITE 0x20000000u
+/* A possible root, whose count dropped to a value above 0. */
+#define QRE_PURPLE 0x30000000u
+/* The object is in the buffer of possible roots, which frees it once it is gone. */
+#define QRE_BUFFERED 0x40000000u
+/* Weak references point to the object. */
+#define QRE_WEAKLY_REFERENCED 0x80000000u
+
+typedef struct {
+    qre_header header;
+    uint64_t length;
+    char bytes[];
+} qre_string;
+
+typedef struct {
+    qre_header header;
+    uint64_t length;
+    uint64_t capacity;
+    int64_t *buffer;
+    /* Whether the elements are references to objects, which the array owns. */
+    bool holds_objects;
+} qre_array;
+
+typedef struct {
+    qre_header header;
+    void *function;
+    /* Releases the captured values, null for closures without captures. */
+    void (*drop)(void *closure);
+} qre_closure;
+
+typedef struct qre_weak {
+    qre_header header;
+    /* Null once the object was freed. */
+    qre_header *target;
+    /* The next weak reference to the same object. */
+    struct qre_weak *next;
+} qre_weak;
+
+/* The destructor of each type ID, null for types that hold no references. */
+extern void (*const qre_destructors[])(void *object);
+/*
+ * The tracer of each type ID, which calls qre_gc_visit with every reference the
+ * destructor releases. Null for types whose objects are never part of a cycle,
+ * and for closures, which the collector can't look into.
+ */
+extern void (*const qre_tracers[])(void *object);
+extern const uint32_t qre_type_count;
+
+void qre_panic_message(const char *message, int64_t length) {
+    fflush(stdout);
+    fprintf(stderr, "panic: %.*s\n", (int) length, message);
+    exit(QRE_PANIC_EXIT_CODE);
+}
+
+void qre_panic(qre_string *message) {
+    qre_panic_message(message->bytes, (int64_t) message->length);
+}
+
+static void qre_panic_text(const char *message) {
+    qre_panic_message(message, (int64_t) strlen(message));
+}
+
+/* Allocates a zeroed object, the caller stores its type ID and reference count. */
+void *qre_alloc(int64_t size) {
+    void *object = calloc(1, (size_t) size);
+    if (object == NULL) {
+        qre_panic_text("out of memory");
+    }
+    return object;
+}
+
+static void qre_free(qre_header *object);
+static void qre_possible_root(qre_header *object);
+static void qre_collect_if_due(void);
+static void qre_weak_clear(qre_header *object);
+
+static uint32_t qre_count(qre_header *object) {
+    return object->refcount & QRE_COUNT_MASK;
+}
+
+static uint32_t qre_color(qre_header *object) {
+    return object->refcount & QRE_COLOR_MASK;
+}
+
+static void qre_set_color(qre_header *object, uint32_t color) {
+    object->refcount = (object->refcount & ~QRE_COLOR_MASK) | color;
+}
+
+static bool qre_is_traced(qre_header *object) {
+    return object->type_id < qre_type_count && qre_tracers[object->type_id] != NULL;
+}
+
+/* Other threads may change the count of a shared object, but never the flag. */
+static bool qre_is_shared(qre_header *object) {
+    return __atomic_load_n(&object->refcount, __ATOMIC_RELAXED) & QRE_SHARED;
+}
+
+/* While the collector frees garbage, which already gave up its references. */
+static _Thread_local bool qre_freeing_garbage = false;
+/* How many destructors are running, the collector doesn't run while one is. */
+static _Thread_local uint32_t qre_destructor_depth = 0;
+
+uint32_t qre_retain(void *object) {
+    if (object == NULL) {
+        return 0;
+    }
+    qre_header *header = object;
+    if (qre_is_shared(header)) {
+        uint32_t refcount = __atomic_load_n(&header->refcount, __ATOMIC_RELAXED);
+        do {
+            if ((refcount & QRE_COUNT_MASK) == QRE_COUNT_MASK) {
+                qre_panic_text("reference count overflow");
+            }
+        } while (!__atomic_compare_exchange_n(
+            &header->refcount, &refcount, refcount + 1, true, __ATOMIC_RELAXED, __ATOMIC_RELAXED
+        ));
+        return (refcount + 1) & QRE_COUNT_MASK;
+    }
+    /* Counting past the mask would carry into the state bits above it. */
+    if (qre_count(header) == QRE_COUNT_MASK) {
+        qre_panic_text("reference count overflow");
+    }
+    header->refcount += 1;
+    return qre_count(header);
+}
+
+/* Runs the destructor of an object whose reference count dropped to 0. */
+static void qre_destroy(qre_header *object) {
+    qre_set_color(object, QRE_BLACK);
+    if (object->refcount & QRE_WEAKLY_REFERENCED) {
+        qre_weak_clear(object);
+    }
+    if (object->type_id < qre_type_count && qre_destructors[object->type_id] != NULL) {
+        qre_destructor_depth += 1;
+        qre_destructors[object->type_id](object);
+        qre_destructor_depth -= 1;
+    }
+    /* The collector frees objects in its buffer. */
+    if (!(object->refcount & QRE_BUFFERED)) {
+        qre_free(object);
+    }
+}
+
+uint32_t qre_release(void *object) {
+    if (object == NULL) {
+        return 0;
+    }
+    qre_header *header = object;
+    /* Even while garbage is freed, the collector never subtracted references to these. */
+    if (qre_is_shared(header)) {
+        uint32_t refcount =
+            __atomic_sub_fetch(&header->refcount, 1, __ATOMIC_ACQ_REL) & QRE_COUNT_MASK;
+        if (refcount == 0) {
+            qre_destroy(header);
+        }
+        return refcount;
+    }
+    if (qre_freeing_garbage) {
+        return 0;
+    }
+    header->refcount -= 1;
+    uint32_t refcount = qre_count(header);
+    if (refcount == 0) {
+        qre_destroy(header);
+    } else {
+        qre_possible_root(header);
+    }
+    qre_collect_if_due();
+    return refcount;
+}
+
+void qre_closure_drop(qre_closure *closure) {
+    if (closure->drop != NULL) {
+        closure->drop(closure);
+    }
+}
+
+qre_string *qre_string_new(uint32_t type_id, const char *bytes, int64_t length) {
+    qre_string *string = qre_alloc((int64_t) sizeof(qre_string) + length + 1);
+    string->header.type_id = type_id;
+    string->header.refcount = 1 | QRE_SHARED;
+    string->length = (uint64_t) length;
+    memcpy(string->bytes, bytes, (size_t) length);
+    string->bytes[length] = '\0';
+    return string;
+}
+
+bool qre_string_eq(qre_string *lhs, qre_string *rhs) {
+    return lhs->length == rhs->length && memcmp(lhs->bytes, rhs->bytes, lhs->length) == 0;
+}
+
+/* Gives up the reference an element of the array holds, if it is an object. */
+static void qre_array_release(qre_array *array, int64_t element) {
+    if (array->holds_objects) {
+        qre_release((void *) (intptr_t) element);
+    }
+}
+
+static void qre_array_grow(qre_array *array, uint64_t capacity) {
+    if (capacity <= array->capacity) {
+        return;
+    }
+    int64_t *buffer = realloc(array->buffer, capacity * sizeof(int64_t));
+    if (buffer == NULL) {
+        qre_panic_text("out of memory");
+    }
+    array->buffer = buffer;
+    array->capacity = capacity;
+}
+
+/* Makes room for one more element, doubling the capacity of a full array. */
+static void qre_array_make_room(qre_array *array) {
+    if (array->length < array->capacity) {
+        return;
+    }
+    uint64_t capacity = array->capacity * 2;
+    qre_array_grow(array, capacity < 4 ? 4 : capacity);
+}
+
+qre_array *qre_array_new(uint32_t type_id, int64_t capacity, int32_t holds_objects) {
+    qre_array *array = qre_alloc(sizeof(qre_array));
+    array->header.type_id = type_id;
+    array->header.refcount = 1;
+    array->holds_objects = holds_objects != 0;
+    qre_array_grow(array, (uint64_t) capacity);
+    return array;
+}
+
+int64_t qre_array_len(qre_array *array) {
+    return (int64_t) array->length;
+}
+
+int64_t qre_array_capacity(qre_array *array) {
+    return (int64_t) array->capacity;
+}
+
+int64_t qre_array_get(qre_array *array, int64_t index, qre_string *message) {
+    if ((uint64_t) index >= array->length) {
+        qre_panic(message);
+    }
+    return array->buffer[index];
+}
+
+void qre_array_set(qre_array *array, int64_t index, int64_t value, qre_string *message) {
+    if ((uint64_t) index >= array->length) {
+        qre_panic(message);
+    }
+    int64_t previous = array->buffer[index];
+    array->buffer[index] = value;
+    qre_array_release(array, previous);
+}
+
+void qre_array_push(qre_array *array, int64_t value) {
+    qre_array_make_room(array);
+    array->buffer[array->length] = value;
+    array->length += 1;
+}
+
+/* The generated code checks that the array isn't empty. */
+int64_t qre_array_pop(qre_array *array) {
+    array->length -= 1;
+    return array->buffer[array->length];
+}
+
+void qre_array_insert(qre_array *array, int64_t index, int64_t value, qre_string *message) {
+    if ((uint64_t) index > array->length) {
+        qre_panic(message);
+    }
+    qre_array_make_room(array);
+    memmove(
+        &array->buffer[index + 1],
+        &array->buffer[index],
+        (array->length - (uint64_t) index) * sizeof(int64_t)
+    );
+    array->buffer[index] = value;
+    array->length += 1;
+}
+
+int64_t qre_array_remove(qre_array *array, int64_t index, qre_string *message) {
+    if ((uint64_t) index >= array->length) {
+        qre_panic(message);
+    }
+    int64_t value = array->buffer[index];
+    array->length -= 1;
+    memmove(
+        &array->buffer[index],
+        &array->buffer[index + 1],
+        (array->length - (uint64_t) index) * sizeof(int64_t)
+    );
+    return value;
+}
+
+/* Makes room for at least `additional` more elements. */
+void qre_array_reserve(qre_array *array, int64_t additional) {
+    qre_array_grow(array, array->length + (uint64_t) additional);
+}
+
+void qre_array_clear(qre_array *array) {
+    uint64_t length = array->length;
+    array->length = 0;
+    for (uint64_t i = 0; i < length; i++) {
+        qre_array_release(array, array->buffer[i]);
+    }
+}
+
+/* The destructor of arrays, the runtime frees the array object itself. */
+void qre_array_drop(qre_array *array) {
+    qre_array_clear(array);
+    free(array->buffer);
+}
+
+void qre_gc_visit(void *object);
+
+/* The tracer of arrays holding objects. */
+void qre_array_trace(qre_array *array) {
+    for (uint64_t i = 0; i < array->length; i++) {
+        qre_gc_visit((void *) (intptr_t) array->buffer[i]);
+    }
+}
+
+/* The command-line arguments of the process, as the `[string]` `main` takes. */
+qre_array *qre_args(int32_t argc, char **argv, uint32_t array_type_id, uint32_t string_type_id) {
+    qre_array *arguments = qre_array_new(array_type_id, argc, true);
+    for (int32_t i = 0; i < argc; i++) {
+        qre_string *argument = qre_string_new(string_type_id, argv[i], (int64_t) strlen(argv[i]));
+        qre_array_push(arguments, (int64_t) (intptr_t) argument);
+    }
+    return arguments;
+}
+
+/* A growable stack of objects. */
+typedef struct {
+    qre_header **objects;
+    size_t length;
+    size_t capacity;
+} qre_stack;
+
+static void qre_stack_push(qre_stack *stack, qre_header *object) {
+    if (stack->length == stack->capacity) {
+        size_t capacity = stack->capacity < 16 ? 16 : stack->capacity * 2;
+        qre_header **objects = realloc(stack->objects, capacity * sizeof(qre_header *));
+        if (objects == NULL) {
+            qre_panic_text("out of memory");
+        }
+        stack->objects = objects;
+        stack->capacity = capacity;
+    }
+    stack->objects[stack->length++] = object;
+}
+
+static qre_header *qre_stack_pop(qre_stack *stack) {
+    return stack->length == 0 ? NULL : stack->objects[--stack->length];
+}
+
+/*
+ * Weak references to an object are kept in a list, found through a table from
+ * the object to the first of them, so they can be cleared when it is freed.
+ */
+typedef struct {
+    qre_header *target;
+    qre_weak *first;
+} qre_weak_entry;
+
+static _Thread_local qre_weak_entry *qre_weak_entries = NULL;
+static _Thread_local size_t qre_weak_capacity = 0;
+static _Thread_local size_t qre_weak_count = 0;
+
+static size_t qre_weak_slot(qre_header *target) {
+    uintptr_t hash = (uintptr_t) target;
+    hash ^= hash >> 17;
+    hash *= (uintptr_t) 0x9e3779b97f4a7c15ull;
+    return (size_t) (hash ^ (hash >> 29)) & (qre_weak_capacity - 1);
+}
+
+/* The entry of `target`, or the empty one it would go in. */
+static qre_weak_entry *qre_weak_find(qre_header *target) {
+    size_t slot = qre_weak_slot(target);
+    while (qre_weak_entries[slot].target != NULL && qre_weak_entries[slot].target != target) {
+        slot = (slot + 1) & (qre_weak_capacity - 1);
+    }
+    return &qre_weak_entries[slot];
+}
+
+static void qre_weak_grow(void) {
+    qre_weak_entry *entries = qre_weak_entries;
+    size_t capacity = qre_weak_capacity;
+    qre_weak_capacity = capacity == 0 ? 64 : capacity * 2;
+    qre_weak_entries = calloc(qre_weak_capacity, sizeof(qre_weak_entry));
+    if (qre_weak_entries == NULL) {
+        qre_panic_text("out of memory");
+    }
+    for (size_t i = 0; i < capacity; i++) {
+        if (entries[i].target != NULL) {
+            *qre_weak_find(entries[i].target) = entries[i];
+        }
+    }
+    free(entries);
+}
+
+/* Removes the entry of an object, moving later entries of its probe sequence back. */
+static void qre_weak_remove(qre_weak_entry *entry) {
+    size_t hole = (size_t) (entry - qre_weak_entries);
+    size_t slot = hole;
+    for (;;) {
+        slot = (slot + 1) & (qre_weak_capacity - 1);
+        if (qre_weak_entries[slot].target == NULL) {
+            break;
+        }
+        size_t home = qre_weak_slot(qre_weak_entries[slot].target);
+        /* Entries that can't be found from their home slot through the hole move into it. */
+        if (((slot - home) & (qre_weak_capacity - 1)) >= ((slot - hole) & (qre_weak_capacity - 1))) {
+            qre_weak_entries[hole] = qre_weak_entries[slot];
+            hole = slot;
+        }
+    }
+    qre_weak_entries[hole].target = NULL;
+    qre_weak_entries[hole].first = NULL;
+    qre_weak_count -= 1;
+}
+
+qre_weak *qre_weak_new(uint32_t type_id, void *object) {
+    qre_header *target = object;
+    qre_weak *weak = qre_alloc(sizeof(qre_weak));
+    weak->header.type_id = type_id;
+    weak->header.refcount = 1;
+    weak->target = target;
+    if ((qre_weak_count + 1) * 2 > qre_weak_capacity) {
+        qre_weak_grow();
+    }
+    qre_weak_entry *entry = qre_weak_find(target);
+    if (entry->target == NULL) {
+        entry->target = target;
+        qre_weak_count += 1;
+    }
+    weak->next = entry->first;
+    entry->first = weak;
+    target->refcount |= QRE_WEAKLY_REFERENCED;
+    return weak;
+}
+
+/* The object, or a boxed `none` of `none_type_id` once it was freed. */
+void *qre_weak_get(qre_weak *weak, uint32_t none_type_id) {
+    if (weak->target != NULL) {
+        qre_retain(weak->target);
+        return weak->target;
+    }
+    qre_header *none = qre_alloc(sizeof(qre_header));
+    none->type_id = none_type_id;
+    none->refcount = 1 | QRE_SHARED;
+    return none;
+}
+
+/* The destructor of weak references. */
+void qre_weak_drop(qre_weak *weak) {
+    if (weak->target == NULL) {
+        return;
+    }
+    qre_weak_entry *entry = qre_weak_find(weak->target);
+    qre_weak **link = &entry->first;
+    while (*link != weak) {
+        link = &(*link)->next;
+    }
+    *link = weak->next;
+    if (entry->first == NULL) {
+        weak->target->refcount &= ~QRE_WEAKLY_REFERENCED;
+        qre_weak_remove(entry);
+    }
+}
+
+/* Clears the weak references to an object that is being freed. */
+static void qre_weak_clear(qre_header *object) {
+    qre_weak_entry *entry = qre_weak_find(object);
+    for (qre_weak *weak = entry->first; weak != NULL; weak = weak->next) {
+        weak->target = NULL;
+    }
+    object->refcount &= ~QRE_WEAKLY_REFERENCED;
+    qre_weak_remove(entry);
+}
+
+static void qre_free(qre_header *object) {
+    free(object);
+}
+
+/* The possible roots of garbage cycles. */
+static _Thread_local qre_stack qre_roots = {0};
+/* The objects the collector still has to visit. */
+static _Thread_local qre_stack qre_pending = {0};
+/* What qre_gc_visit does with each reference a tracer visits. */
+static _Thread_local void (*qre_visitor)(qre_header *object) = NULL;
+static _Thread_local size_t qre_cycle_period = 0;
+static _Thread_local bool qre_collecting = false;
+
+static void qre_trace(qre_header *object, void (*visitor)(qre_header *object)) {
+    if (qre_is_traced(object)) {
+        qre_visitor = visitor;
+        qre_tracers[object->type_id](object);
+    }
+}
+
+/* Shared objects are never garbage of this thread, the references to them are left alone. */
+void qre_gc_visit(void *object) {
+    if (object != NULL && !qre_is_shared(object)) {
+        qre_visitor(object);
+    }
+}
+
+static void qre_possible_root(qre_header *object) {
+    if (!qre_is_traced(object) || qre_color(object) == QRE_PURPLE) {
+        return;
+    }
+    qre_set_color(object, QRE_PURPLE);
+    if (!(object->refcount & QRE_BUFFERED)) {
+        object->refcount |= QRE_BUFFERED;
+        qre_stack_push(&qre_roots, object);
+    }
+}
+
+/* Subtracts the reference, looking into objects the first time they are reached. */
+static void qre_mark_gray_visitor(qre_header *object) {
+    object->refcount -= 1;
+    if (qre_color(object) != QRE_GRAY) {
+        qre_set_color(object, QRE_GRAY);
+        qre_stack_push(&qre_pending, object);
+    }
+}
+
+/* Subtracts the references the objects reachable from `root` hold to each other. */
+static void qre_mark_gray(qre_header *root) {
+    if (qre_color(root) == QRE_GRAY) {
+        return;
+    }
+    qre_set_color(root, QRE_GRAY);
+    qre_stack_push(&qre_pending, root);
+    qre_header *object;
+    while ((object = qre_stack_pop(&qre_pending)) != NULL) {
+        qre_trace(object, qre_mark_gray_visitor);
+    }
+}
+
+/* Restores the reference, looking into objects the first time they are reached. */
+static void qre_scan_black_visitor(qre_header *object) {
+    object->refcount += 1;
+    if (qre_color(object) != QRE_BLACK) {
+        qre_set_color(object, QRE_BLACK);
+        qre_stack_push(&qre_pending, object);
+    }
+}
+
+/* Restores the references of everything an object that is still in use reaches. */
+static void qre_scan_black(qre_header *root) {
+    qre_stack pending = qre_pending;
+    qre_pending = (qre_stack) {0};
+    qre_set_color(root, QRE_BLACK);
+    qre_stack_push(&qre_pending, root);
+    qre_header *object;
+    while ((object = qre_stack_pop(&qre_pending)) != NULL) {
+        qre_trace(object, qre_scan_black_visitor);
+    }
+    free(qre_pending.objects);
+    qre_pending = pending;
+}
+
+static void qre_push_visitor(qre_header *object) {
+    qre_stack_push(&qre_pending, object);
+}
+
+/*
+ * Colors what is only referenced from the gray objects reachable from `root`
+ * white, and everything it reaches that something else references black again.
+ */
+static void qre_scan(qre_header *root) {
+    qre_stack_push(&qre_pending, root);
+    qre_header *object;
+    while ((object = qre_stack_pop(&qre_pending)) != NULL) {
+        if (qre_color(object) != QRE_GRAY) {
+            continue;
+        }
+        if (qre_count(object) > 0) {
+            qre_scan_black(object);
+        } else {
+            qre_set_color(object, QRE_WHITE);
+            qre_trace(object, qre_push_visitor);
+        }
+    }
+}
+
+/* Moves the white objects reachable from `root` to `garbage`. */
+static void qre_collect_white(qre_header *root, qre_stack *garbage) {
+    qre_stack_push(&qre_pending, root);
+    qre_header *object;
+    while ((object = qre_stack_pop(&qre_pending)) != NULL) {
+        if (qre_color(object) != QRE_WHITE || (object->refcount & QRE_BUFFERED)) {
+            continue;
+        }
+        qre_set_color(object, QRE_BLACK);
+        qre_stack_push(garbage, object);
+        qre_trace(object, qre_push_visitor);
+    }
+}
+
+/* Frees the objects only reference cycles keep alive, returning how many there were. */
+int64_t qre_collect_cycles(void) {
+    if (qre_collecting) {
+        return 0;
+    }
+    qre_collecting = true;
+    qre_stack roots = qre_roots;
+    qre_roots = (qre_stack) {0};
+
+    /* Roots that are gone or were used again since are dropped. */
+    size_t kept = 0;
+    for (size_t i = 0; i < roots.length; i++) {
+        qre_header *root = roots.objects[i];
+        if (qre_color(root) == QRE_PURPLE && qre_count(root) > 0) {
+            roots.objects[kept++] = root;
+            continue;
+        }
+        root->refcount &= ~QRE_BUFFERED;
+        if (qre_color(root) == QRE_BLACK && qre_count(root) == 0) {
+            qre_free(root);
+        }
+    }
+    roots.length = kept;
+    for (size_t i = 0; i < roots.length; i++) {
+        qre_mark_gray(roots.objects[i]);
+    }
+    for (size_t i = 0; i < roots.length; i++) {
+        qre_scan(roots.objects[i]);
+    }
+    qre_stack garbage = {0};
+    for (size_t i = 0; i < roots.length; i++) {
+        roots.objects[i]->refcount &= ~QRE_BUFFERED;
+        qre_collect_white(roots.objects[i], &garbage);
+    }
+    free(roots.objects);
+
+    for (size_t i = 0; i < garbage.length; i++) {
+        if (garbage.objects[i]->refcount & QRE_WEAKLY_REFERENCED) {
+            qre_weak_clear(garbage.objects[i]);
+        }
+    }
+    /*
+     * The references garbage holds to other garbage are already subtracted, and the
+     * objects it references that are still in use never counted them. Only what the
+     * destructors free besides references is still needed, like the buffers of arrays.
+     */
+    qre_freeing_garbage = true;
+    for (size_t i = 0; i < garbage.length; i++) {
+        qre_header *object = garbage.objects[i];
+        if (qre_is_traced(object) && qre_destructors[object->type_id] != NULL) {
+            qre_destructors[object->type_id](object);
+        }
+    }
+    qre_freeing_garbage = false;
+    /* Closures are never looked into, so the references they hold still count. */
+    for (size_t i = 0; i < garbage.length; i++) {
+        qre_header *object = garbage.objects[i];
+        if (!qre_is_traced(object) && object->type_id < qre_type_count
+            && qre_destructors[object->type_id] != NULL) {
+            qre_destructor_depth += 1;
+            qre_destructors[object->type_id](object);
+            qre_destructor_depth -= 1;
+        }
+    }
+    for (size_t i = 0; i < garbage.length; i++) {
+        qre_free(garbage.objects[i]);
+    }
+    free(garbage.objects);
+    qre_collecting = false;
+    return (int64_t) garbage.length;
+}
+
+static void qre_collect_if_due(void) {
+    static _Thread_local bool configured = false;
+    if (!configured) {
+        configured = true;
+        const char *period = getenv("QRE_CYCLE_PERIOD");
+        qre_cycle_period = period == NULL ? 10000 : (size_t) strtoull(period, NULL, 10);
+    }
+    if (qre_cycle_period != 0 && qre_roots.length >= qre_cycle_period
+        && qre_destructor_depth == 0 && !qre_collecting) {
+        qre_collect_cycles();
+    }
+}
+
+/* A thread started by `spawn`, which holds a reference to its task. */
+static void *qre_thread_main(void *task) {
+    qre_closure *closure = task;
+    ((void (*)(void *)) closure->function)(closure);
+    qre_release(closure);
+    /* Nothing else can reach the objects of this thread once it ends. */
+    qre_collect_cycles();
+    free(qre_roots.objects);
+    free(qre_pending.objects);
+    free(qre_weak_entries);
+    return NULL;
+}
+
+int64_t qre_thread_spawn(qre_closure *task) {
+    /* Both threads release the task from now on, it only captures shared objects. */
+    task->header.refcount |= QRE_SHARED;
+    qre_retain(task);
+    pthread_t thread;
+    if (pthread_create(&thread, NULL, qre_thread_main, task) != 0) {
+        qre_panic_text("can not start a thread");
+    }
+    return (int64_t) (uintptr_t) thread;
+}
+
+void qre_thread_join(int64_t thread) {
+    if (pthread_join((pthread_t) (uintptr_t) thread, NULL) != 0) {
+        qre_panic_text("can not join the thread");
+    }
+}
@@ -0,0 +1,32 @@
+use std::path::Path;
+use std::process::Command;
+
+/// The runtime every program is linked with, compiled along with it.
+const RUNTIME: &str = include_str!("../../runtime/qre_runtime.c");
+
+/// Links the object file of a program with the runtime into an executable.
+///
+/// The C compiler named by `CC`, or `cc`, drives the system linker. The runtime
+/// source is written next to `object` to compile it.
+pub fn link(object: &Path, output: &Path) -> Result<(), String> {
+    let runtime = object.with_file_name("qre_runtime.c");
+    std::fs::write(&runtime, RUNTIME)
+        .map_err(|error| format!("can not write {}: {}", runtime.display(), error))?;
+
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(&compiler)
+        .arg(object)
+        .arg(&runtime)
+        .arg("-o")
+        .arg(output)
+        // Float remainders are calls to `fmod`.
+        .arg("-lm")
+        // `spawn` starts POSIX threads.
+        .arg("-pthread")
+        .status()
+        .map_err(|error| format!("can not run `{}`: {}", compiler, error))?;
+    if !status.success() {
+        return Err(format!("`{}` failed to link {}", compiler, output.display()));
+    }
+    Ok(())
+}
@@ -0,0 +1,562 @@
+use crate::backend::llvm::types::{function_type, struct_type};
+use crate::backend::qir::entry::ENTRY_POINT;
+use crate::backend::qir::layout::{object_fields, CLOSURE_FUNCTION_FIELD, TYPE_ID_FIELD};
+use crate::backend::qir::structs::{
+    QIRBasicBlock, QIRExpression, QIRHeader, QIRLocalVariable, QIRTerminator, QIRType,
+};
+use crate::frontend::parser::ast::PathData;
+use crate::frontend::span::Span;
+use inkwell::basic_block::BasicBlock;
+use inkwell::builder::{Builder, BuilderError};
+use inkwell::context::Context;
+use inkwell::module::Module;
+use inkwell::types::{ArrayType, BasicMetadataTypeEnum, StructType};
+use inkwell::values::{
+    BasicMetadataValueEnum, BasicValue, BasicValueEnum, FunctionValue, GlobalValue, IntValue,
+    PointerValue,
+};
+use inkwell::{AddressSpace, IntPredicate};
+use std::collections::HashMap;
+
+/// Generates an LLVM module from the headers of a lowered program.
+pub struct CodeGenerator<'ctx> {
+    pub(crate) context: &'ctx Context,
+    pub(crate) module: Module<'ctx>,
+    pub(crate) builder: Builder<'ctx>,
+    /// The named structures of the program, by their fields.
+    structs: Vec<(Vec<QIRType>, StructType<'ctx>)>,
+    /// The table of every interface, a row of method pointers for each type ID.
+    vtables: HashMap<String, (GlobalValue<'ctx>, ArrayType<'ctx>)>,
+    /// The function being generated.
+    function: Option<FunctionValue<'ctx>>,
+    /// The stack slot of every local of the function being generated.
+    locals: Vec<PointerValue<'ctx>>,
+}
+
+pub(crate) type Generated<'ctx> = Result<Option<BasicValueEnum<'ctx>>, BuilderError>;
+
+impl<'ctx> CodeGenerator<'ctx> {
+    pub fn new(context: &'ctx Context, module_name: &str) -> Self {
+        CodeGenerator {
+            context,
+            module: context.create_module(module_name),
+            builder: context.create_builder(),
+            structs: vec![],
+            vtables: HashMap::new(),
+            function: None,
+            locals: vec![],
+        }
+    }
+
+    /// Generates the module for a whole program, which LLVM verifies.
+    pub fn generate(mut self, program: &[QIRHeader]) -> Result<Module<'ctx>, String> {
+        self.declare(program);
+        for header in program {
+            if let QIRHeader::Function {
+                name,
+                parameters,
+                locals,
+                blocks,
+                ..
+            } = header
+            {
+                self.generate_function(name, parameters, locals, blocks)
+                    .map_err(|error| format!("can not generate `{}`: {}", name.name, error))?;
+            }
+        }
+        self.module.verify().map_err(|error| error.to_string())?;
+        Ok(self.module)
+    }
+
+    /// Declares the structures, globals and functions of the program, so bodies can
+    /// refer to them in any order.
+    fn declare(&mut self, program: &[QIRHeader]) {
+        for header in program {
+            match header {
+                QIRHeader::Struct { name, fields } => {
+                    let ty = self.context.opaque_struct_type(&name.name);
+                    ty.set_body(&struct_type(self.context, fields).get_field_types(), false);
+                    self.structs.push((fields.clone(), ty));
+                }
+                QIRHeader::GlobalVariable { name, ty } => {
+                    let ty = ty
+                        .to_llvm_type(self.context)
+                        .expect("globals are never void");
+                    let global = self.module.add_global(ty, None, &symbol(&name.name));
+                    global.set_initializer(&ty.const_zero());
+                }
+                QIRHeader::Function {
+                    name,
+                    parameters,
+                    returns,
+                    ..
+                } => {
+                    let parameters = parameters
+                        .iter()
+                        .map(|(_, ty)| {
+                            BasicMetadataTypeEnum::from(
+                                ty.to_llvm_type(self.context)
+                                    .expect("parameters are never void"),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    let ty = function_type(self.context, &parameters, returns);
+                    self.module.add_function(&symbol(&name.name), ty, None);
+                }
+                QIRHeader::VTable { .. } | QIRHeader::Destructors { .. } => {}
+            }
+        }
+        // Tables point to the functions they hold, so they come last.
+        for header in program {
+            match header {
+                QIRHeader::VTable { interface, entries } => self.declare_vtable(interface, entries),
+                QIRHeader::Destructors { entries, tracers } => {
+                    self.declare_destructors(entries, tracers)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Declares the tables the runtime looks up the destructor and the tracer of an
+    /// object in by its type ID, `qre_destructors` and `qre_tracers`, and their
+    /// length, `qre_type_count`.
+    fn declare_destructors(&mut self, entries: &[(u32, PathData)], tracers: &[(u32, PathData)]) {
+        let ptr = self.context.ptr_type(AddressSpace::default());
+        let count = entries
+            .iter()
+            .chain(tracers)
+            .map(|(type_id, _)| type_id + 1)
+            .max()
+            .unwrap_or(0);
+        for (name, entries) in [("qre_destructors", entries), ("qre_tracers", tracers)] {
+            let mut table = vec![ptr.const_null(); count as usize];
+            for (type_id, function) in entries {
+                table[*type_id as usize] = self.function_pointer(function);
+            }
+            let global = self.module.add_global(ptr.array_type(count), None, name);
+            global.set_initializer(&ptr.const_array(&table));
+            global.set_constant(true);
+        }
+
+        let i32 = self.context.i32_type();
+        let global = self.module.add_global(i32, None, "qre_type_count");
+        global.set_initializer(&i32.const_int(count as u64, false));
+        global.set_constant(true);
+    }
+
+    /// Declares the table of an interface, holding the implementations of its methods
+    /// in the row of each implementing type's ID. Rows of other types are null.
+    fn declare_vtable(&mut self, interface: &PathData, entries: &[(u32, Vec<PathData>)]) {
+        let ptr = self.context.ptr_type(AddressSpace::default());
+        let methods = entries.first().map_or(0, |(_, functions)| functions.len());
+        let row_type = ptr.array_type(methods as u32);
+        let rows = entries.iter().map(|(type_id, _)| type_id + 1).max().unwrap_or(0);
+
+        let mut table = vec![row_type.const_zero(); rows as usize];
+        for (type_id, functions) in entries {
+            let functions = functions
+                .iter()
+                .map(|function| self.function_pointer(function))
+                .collect::<Vec<_>>();
+            table[*type_id as usize] = ptr.const_array(&functions);
+        }
+        let table_type = row_type.array_type(rows);
+        let global = self
+            .module
+            .add_global(table_type, None, &symbol(&format!("{}::<vtable>", interface.name)));
+        global.set_initializer(&row_type.const_array(&table));
+        global.set_constant(true);
+        self.vtables
+            .insert(interface.name.clone(), (global, table_type));
+    }
+
+    fn generate_function(
+        &mut self,
+        name: &PathData,
+        parameters: &[(QIRLocalVariable, QIRType)],
+        locals: &[QIRType],
+        blocks: &[QIRBasicBlock],
+    ) -> Result<(), BuilderError> {
+        let function = self
+            .module
+            .get_function(&symbol(&name.name))
+            .expect("functions are declared first");
+        self.function = Some(function);
+        let entry = self.context.append_basic_block(function, "entry");
+        self.builder.position_at_end(entry);
+
+        let mut slots = vec![];
+        for (id, ty) in locals.iter().enumerate() {
+            // Locals of void type never hold anything, but keep the IDs after them in place.
+            let ty = ty
+                .to_llvm_type(self.context)
+                .unwrap_or(self.context.i8_type().into());
+            slots.push(self.builder.build_alloca(ty, &format!("local{}", id))?);
+        }
+        self.locals = slots;
+        for (index, (local, _)) in parameters.iter().enumerate() {
+            let value = function
+                .get_nth_param(index as u32)
+                .expect("parameters are declared");
+            self.builder.build_store(self.locals[local.id], value)?;
+        }
+
+        // The stack slots are set up once, before the first block of the function.
+        let labels = (0..blocks.len())
+            .map(|label| self.context.append_basic_block(function, &format!("bb{}", label)))
+            .collect::<Vec<_>>();
+        self.builder.build_unconditional_branch(labels[0])?;
+        for (block, label) in blocks.iter().zip(&labels) {
+            self.builder.position_at_end(*label);
+            for expr in &block.exprs {
+                self.generate_expression(expr)?;
+            }
+            self.generate_terminator(&block.terminator, &labels)?;
+        }
+        Ok(())
+    }
+
+    fn generate_terminator(
+        &mut self,
+        terminator: &QIRTerminator,
+        labels: &[BasicBlock<'ctx>],
+    ) -> Result<(), BuilderError> {
+        match terminator {
+            QIRTerminator::Br { target } => {
+                self.builder.build_unconditional_branch(labels[*target])?;
+            }
+            QIRTerminator::CondBr {
+                condition,
+                if_true,
+                if_false,
+            } => {
+                let condition = self.generate_value(condition)?.into_int_value();
+                self.builder
+                    .build_conditional_branch(condition, labels[*if_true], labels[*if_false])?;
+            }
+            QIRTerminator::Ret { value } => {
+                let value = match value {
+                    Some(value) => self.generate_expression(value)?,
+                    None => None,
+                };
+                self.builder
+                    .build_return(value.as_ref().map(|value| value as &dyn BasicValue))?;
+            }
+            QIRTerminator::Unreachable => {
+                self.builder.build_unreachable()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn generate_expression(&mut self, expr: &QIRExpression) -> Generated<'ctx> {
+        match expr {
+            QIRExpression::Retain { ptr } => {
+                let ptr = self.generate_value(ptr)?;
+                self.call(self.runtime_function("qre_retain"), &[ptr])
+            }
+            QIRExpression::Release { ptr } => {
+                let ptr = self.generate_value(ptr)?;
+                self.call(self.runtime_function("qre_release"), &[ptr])
+            }
+            QIRExpression::Invoke {
+                name,
+                arguments,
+                return_type,
+            } => {
+                let arguments = self.generate_values(arguments)?;
+                if name.name.starts_with("qre::") {
+                    return self.generate_intrinsic(name, &arguments, return_type);
+                }
+                let function = match self.module.get_function(&symbol(&name.name)) {
+                    Some(function) => function,
+                    // Functions the program doesn't define are linked in from elsewhere.
+                    None => {
+                        let parameters = arguments
+                            .iter()
+                            .map(|argument| argument.get_type().into())
+                            .collect::<Vec<_>>();
+                        let ty = function_type(self.context, &parameters, return_type);
+                        self.module.add_function(&name.name, ty, None)
+                    }
+                };
+                self.call(function, &arguments)
+            }
+            QIRExpression::InvokeClosure {
+                closure,
+                arguments,
+                return_type,
+            } => {
+                let closure = self.generate_value(closure)?;
+                let mut values = vec![closure];
+                values.extend(self.generate_values(arguments)?);
+                let function = self
+                    .load_field(
+                        closure.into_pointer_value(),
+                        &object_fields([QIRType::Ptr]),
+                        CLOSURE_FUNCTION_FIELD,
+                    )?
+                    .into_pointer_value();
+                self.call_indirect(function, &values, return_type)
+            }
+            QIRExpression::GetFunctionPtr { function } => {
+                Ok(Some(self.function_pointer(function).into()))
+            }
+            QIRExpression::InvokeDynamic {
+                interface,
+                method,
+                arguments,
+                return_type,
+            } => {
+                let values = self.generate_values(arguments)?;
+                let (table, table_type) = self.vtables[&interface.name];
+                let type_id = self
+                    .load_field(values[0].into_pointer_value(), &object_fields([]), TYPE_ID_FIELD)?
+                    .into_int_value();
+                let i64 = self.context.i64_type();
+                let indices = [
+                    i64.const_zero(),
+                    self.builder.build_int_z_extend(type_id, i64, "type_id")?,
+                    i64.const_int(*method as u64, false),
+                ];
+                // Every object an interface value points to implements the interface,
+                // so the table has a row for its type ID.
+                let slot = unsafe {
+                    self.builder.build_in_bounds_gep(
+                        table_type,
+                        table.as_pointer_value(),
+                        &indices,
+                        "method",
+                    )?
+                };
+                let ptr = self.context.ptr_type(AddressSpace::default());
+                let function = self
+                    .builder
+                    .build_load(ptr, slot, "method")?
+                    .into_pointer_value();
+                self.call_indirect(function, &values, return_type)
+            }
+            QIRExpression::Integer { value, ty } => {
+                let ty = ty
+                    .to_llvm_type(self.context)
+                    .expect("integers have a type")
+                    .into_int_type();
+                Ok(Some(ty.const_int(*value as u64, false).into()))
+            }
+            QIRExpression::Float { value, ty } => {
+                let ty = ty
+                    .to_llvm_type(self.context)
+                    .expect("floats have a type")
+                    .into_float_type();
+                Ok(Some(ty.const_float(*value).into()))
+            }
+            QIRExpression::Bool { value } => Ok(Some(
+                self.context
+                    .bool_type()
+                    .const_int(*value as u64, false)
+                    .into(),
+            )),
+            QIRExpression::String { value, type_id } => {
+                let bytes = self
+                    .builder
+                    .build_global_string_ptr(value, "string")?
+                    .as_pointer_value();
+                let type_id = self.context.i32_type().const_int(*type_id as u64, false);
+                let length = self.context.i64_type().const_int(value.len() as u64, false);
+                self.call(
+                    self.runtime_function("qre_string_new"),
+                    &[type_id.into(), bytes.into(), length.into()],
+                )
+            }
+            QIRExpression::Null => Ok(Some(
+                self.context
+                    .ptr_type(AddressSpace::default())
+                    .const_null()
+                    .into(),
+            )),
+            QIRExpression::InstantiateStructure { ptr_type } => {
+                let size = self
+                    .struct_type(ptr_type)
+                    .size_of()
+                    .expect("objects are sized");
+                self.call(self.runtime_function("qre_alloc"), &[size.into()])
+            }
+            QIRExpression::StoreToPtr {
+                receiver,
+                new_value,
+                ..
+            } => {
+                let receiver = self.generate_value(receiver)?.into_pointer_value();
+                if let Some(value) = self.generate_expression(new_value)? {
+                    self.builder.build_store(receiver, value)?;
+                }
+                Ok(None)
+            }
+            QIRExpression::GetFieldPtr {
+                receiver,
+                ptr_type,
+                field,
+                ..
+            } => {
+                let receiver = self.generate_value(receiver)?.into_pointer_value();
+                let ty = self.struct_type(ptr_type);
+                let field = self
+                    .builder
+                    .build_struct_gep(ty, receiver, *field as u32, "field")?;
+                Ok(Some(field.into()))
+            }
+            QIRExpression::LoadFromPtr { ptr, output_type } => {
+                let ptr = self.generate_value(ptr)?.into_pointer_value();
+                match output_type.to_llvm_type(self.context) {
+                    Some(ty) => Ok(Some(self.builder.build_load(ty, ptr, "load")?)),
+                    None => Ok(None),
+                }
+            }
+            QIRExpression::GetLocalPtr { local } => Ok(Some(self.locals[local.id].into())),
+            QIRExpression::GetGlobalPtr { global } => {
+                let global = self
+                    .module
+                    .get_global(&symbol(&global.name))
+                    .expect("globals are declared first");
+                Ok(Some(global.as_pointer_value().into()))
+            }
+        }
+    }
+
+    fn generate_value(&mut self, expr: &QIRExpression) -> Result<BasicValueEnum<'ctx>, BuilderError> {
+        Ok(self
+            .generate_expression(expr)?
+            .expect("only expressions with a value are used as operands"))
+    }
+
+    fn generate_values(
+        &mut self,
+        exprs: &[QIRExpression],
+    ) -> Result<Vec<BasicValueEnum<'ctx>>, BuilderError> {
+        exprs.iter().map(|expr| self.generate_value(expr)).collect()
+    }
+
+    pub(crate) fn call(
+        &self,
+        function: FunctionValue<'ctx>,
+        arguments: &[BasicValueEnum<'ctx>],
+    ) -> Generated<'ctx> {
+        let arguments = arguments
+            .iter()
+            .map(|&argument| argument.into())
+            .collect::<Vec<BasicMetadataValueEnum>>();
+        let call = self.builder.build_call(function, &arguments, "")?;
+        Ok(call.try_as_basic_value().left())
+    }
+
+    /// Calls a function pointer, typed by the arguments it is called with.
+    fn call_indirect(
+        &self,
+        function: PointerValue<'ctx>,
+        arguments: &[BasicValueEnum<'ctx>],
+        return_type: &QIRType,
+    ) -> Generated<'ctx> {
+        let parameters = arguments
+            .iter()
+            .map(|argument| argument.get_type().into())
+            .collect::<Vec<_>>();
+        let arguments = arguments
+            .iter()
+            .map(|&argument| argument.into())
+            .collect::<Vec<BasicMetadataValueEnum>>();
+        let ty = function_type(self.context, &parameters, return_type);
+        let call = self
+            .builder
+            .build_indirect_call(ty, function, &arguments, "")?;
+        Ok(call.try_as_basic_value().left())
+    }
+
+    fn function_pointer(&self, function: &PathData) -> PointerValue<'ctx> {
+        self.module
+            .get_function(&symbol(&function.name))
+            .expect("functions are declared first")
+            .as_global_value()
+            .as_pointer_value()
+    }
+
+    /// The named structure with the given fields, or a literal one if the program
+    /// declares none.
+    fn struct_type(&self, fields: &[QIRType]) -> StructType<'ctx> {
+        self.structs
+            .iter()
+            .find(|(declared, _)| declared == fields)
+            .map(|(_, ty)| *ty)
+            .unwrap_or_else(|| struct_type(self.context, fields))
+    }
+
+    pub(crate) fn load_field(
+        &self,
+        object: PointerValue<'ctx>,
+        fields: &[QIRType],
+        field: i32,
+    ) -> Result<BasicValueEnum<'ctx>, BuilderError> {
+        let ptr = self
+            .builder
+            .build_struct_gep(self.struct_type(fields), object, field as u32, "field")?;
+        let ty = fields[field as usize]
+            .to_llvm_type(self.context)
+            .expect("fields are never void");
+        self.builder.build_load(ty, ptr, "field")
+    }
+
+    /// Panics with `message` if `failed` is true.
+    pub(crate) fn check(&self, failed: IntValue<'ctx>, message: &str) -> Result<(), BuilderError> {
+        let function = self.current_function();
+        let panic_block = self.context.append_basic_block(function, "panic");
+        let ok_block = self.context.append_basic_block(function, "ok");
+        self.builder
+            .build_conditional_branch(failed, panic_block, ok_block)?;
+
+        self.builder.position_at_end(panic_block);
+        let text = self
+            .builder
+            .build_global_string_ptr(message, "message")?
+            .as_pointer_value();
+        let length = self.context.i64_type().const_int(message.len() as u64, false);
+        self.call(
+            self.runtime_function("qre_panic_message"),
+            &[text.into(), length.into()],
+        )?;
+        self.builder.build_unreachable()?;
+
+        self.builder.position_at_end(ok_block);
+        Ok(())
+    }
+
+    /// Compares `value` to zero.
+    pub(crate) fn compare_to_zero(
+        &self,
+        predicate: IntPredicate,
+        value: IntValue<'ctx>,
+    ) -> Result<IntValue<'ctx>, BuilderError> {
+        let zero = value.get_type().const_zero();
+        self.builder.build_int_compare(predicate, value, zero, "compared")
+    }
+
+    fn current_function(&self) -> FunctionValue<'ctx> {
+        self.function.expect("code is only generated inside functions")
+    }
+
+}
+
+/// The name of the LLVM symbol for a function or global of the program.
+///
+/// Names are prefixed, so they never clash with the C library the program is linked
+/// with. The entry point is the C `main` the process starts in.
+pub(crate) fn symbol(name: &str) -> String {
+    if name == ENTRY_POINT {
+        return "main".to_string();
+    }
+    format!("qre.{}", name)
+}
+
+/// Where a panic happened, as `file:row:column`.
+pub(crate) fn location(span: &Span) -> String {
+    format!("{}:{}:{}", span.file_name, span.row_start, span.column_start)
+}
@@ -0,0 +1,406 @@
+use crate::backend::llvm::codegen::{location, CodeGenerator, Generated};
+use crate::backend::qir::structs::QIRType;
+use crate::frontend::parser::ast::{AstType, PathData};
+use crate::frontend::span::Span;
+use inkwell::builder::BuilderError;
+use inkwell::intrinsics::Intrinsic;
+use inkwell::types::BasicTypeEnum;
+use inkwell::values::{BasicValueEnum, FloatValue, IntValue};
+use inkwell::{FloatPredicate, IntPredicate};
+
+impl<'ctx> CodeGenerator<'ctx> {
+    /// Generates a call to a `qre::` intrinsic, inline where LLVM has instructions for
+    /// it and as a call to the runtime otherwise.
+    ///
+    /// Integer arithmetic, shifts and conversions panic instead of wrapping or
+    /// truncating, at the location of the call.
+    pub(crate) fn generate_intrinsic(
+        &mut self,
+        name: &PathData,
+        arguments: &[BasicValueEnum<'ctx>],
+        return_type: &QIRType,
+    ) -> Generated<'ctx> {
+        let span = &name.token.span;
+        match name.name.as_str() {
+            "qre::panic" => {
+                // The runtime never returns from a panic, and the block that calls it
+                // ends as unreachable.
+                self.call(self.runtime_function("qre_panic"), arguments)?;
+                return Ok(None);
+            }
+            "qre::args" => return self.call(self.runtime_function("qre_args"), arguments),
+            _ => {}
+        }
+
+        let (ty, operation) = name
+            .name
+            .strip_prefix("qre::")
+            .and_then(|intrinsic| intrinsic.split_once("::"))
+            .expect("intrinsics are named `qre::type::operation`");
+        match (ty, operation) {
+            ("array", operation) => self.generate_array_operation(operation, arguments, return_type),
+            ("closure", "drop") => self.call(self.runtime_function("qre_closure_drop"), arguments),
+            ("weak", operation) => {
+                self.call(self.runtime_function(&format!("qre_weak_{}", operation)), arguments)
+            }
+            ("gc", "visit") => self.call(self.runtime_function("qre_gc_visit"), arguments),
+            ("gc", "collect") => self.call(self.runtime_function("qre_collect_cycles"), arguments),
+            ("thread", operation) => {
+                self.call(self.runtime_function(&format!("qre_thread_{}", operation)), arguments)
+            }
+            ("string", "eq" | "ne") => {
+                let equal = self
+                    .call(self.runtime_function("qre_string_eq"), arguments)?
+                    .expect("string comparisons return a Bool")
+                    .into_int_value();
+                if operation == "eq" {
+                    return Ok(Some(equal.into()));
+                }
+                Ok(Some(self.builder.build_not(equal, "not_equal")?.into()))
+            }
+            ("bool", operation) => {
+                let [lhs, rhs] = binary_operands(arguments, &name.name);
+                let (lhs, rhs) = (lhs.into_int_value(), rhs.into_int_value());
+                let value = match operation {
+                    "or" => self.builder.build_or(lhs, rhs, "or")?,
+                    "and" => self.builder.build_and(lhs, rhs, "and")?,
+                    "eq" => self.builder.build_int_compare(IntPredicate::EQ, lhs, rhs, "eq")?,
+                    "ne" => self.builder.build_int_compare(IntPredicate::NE, lhs, rhs, "ne")?,
+                    _ => unreachable!("`{}` is not an intrinsic", name.name),
+                };
+                Ok(Some(value.into()))
+            }
+            (ty, operation) => {
+                let ty = AstType::from_name(ty).expect("numeric intrinsics are named by type");
+                if let Some(target) = operation.strip_prefix("to_") {
+                    let target = AstType::from_name(target).expect("casts are named by type");
+                    return Ok(Some(self.generate_cast(arguments[0], &ty, &target, span)?));
+                }
+                let [lhs, rhs] = binary_operands(arguments, &name.name);
+                if ty.is_float() {
+                    let value = self.generate_float_operation(
+                        operation,
+                        lhs.into_float_value(),
+                        rhs.into_float_value(),
+                    )?;
+                    return Ok(Some(value));
+                }
+                let value = self.generate_integer_operation(
+                    operation,
+                    lhs.into_int_value(),
+                    rhs.into_int_value(),
+                    ty.is_signed(),
+                    span,
+                )?;
+                Ok(Some(value))
+            }
+        }
+    }
+
+    fn generate_float_operation(
+        &self,
+        operation: &str,
+        lhs: FloatValue<'ctx>,
+        rhs: FloatValue<'ctx>,
+    ) -> Result<BasicValueEnum<'ctx>, BuilderError> {
+        let value = match operation {
+            "add" => self.builder.build_float_add(lhs, rhs, "sum")?.into(),
+            "sub" => self.builder.build_float_sub(lhs, rhs, "difference")?.into(),
+            "mul" => self.builder.build_float_mul(lhs, rhs, "product")?.into(),
+            "div" => self.builder.build_float_div(lhs, rhs, "quotient")?.into(),
+            "rem" => self.builder.build_float_rem(lhs, rhs, "remainder")?.into(),
+            comparison => {
+                // Comparisons with NaN are false, except for `!=`.
+                let predicate = match comparison {
+                    "eq" => FloatPredicate::OEQ,
+                    "ne" => FloatPredicate::UNE,
+                    "lt" => FloatPredicate::OLT,
+                    "gt" => FloatPredicate::OGT,
+                    "le" => FloatPredicate::OLE,
+                    "ge" => FloatPredicate::OGE,
+                    _ => unreachable!("`{}` is not a float intrinsic", comparison),
+                };
+                self.builder
+                    .build_float_compare(predicate, lhs, rhs, comparison)?
+                    .into()
+            }
+        };
+        Ok(value)
+    }
+
+    fn generate_integer_operation(
+        &self,
+        operation: &str,
+        lhs: IntValue<'ctx>,
+        rhs: IntValue<'ctx>,
+        signed: bool,
+        span: &Span,
+    ) -> Result<BasicValueEnum<'ctx>, BuilderError> {
+        let ty = lhs.get_type();
+        match operation {
+            "add" | "sub" | "mul" => {
+                let sign = if signed { "s" } else { "u" };
+                let checked = Intrinsic::find(&format!("llvm.{}{}.with.overflow", sign, operation))
+                    .and_then(|intrinsic| intrinsic.get_declaration(&self.module, &[ty.into()]))
+                    .expect("LLVM checks arithmetic on every integer type");
+                let result = self
+                    .call(checked, &[lhs.into(), rhs.into()])?
+                    .expect("overflow intrinsics return the result and whether it overflowed")
+                    .into_struct_value();
+                let value = self.builder.build_extract_value(result, 0, "value")?;
+                let overflowed = self
+                    .builder
+                    .build_extract_value(result, 1, "overflowed")?
+                    .into_int_value();
+                self.check(overflowed, &format!("integer overflow at {}", location(span)))?;
+                Ok(value)
+            }
+            "div" | "rem" => {
+                let by_zero = self.compare_to_zero(IntPredicate::EQ, rhs)?;
+                self.check(by_zero, &format!("division by zero at {}", location(span)))?;
+                if signed {
+                    // The quotient of the smallest value and -1 is one past the largest.
+                    let smallest = ty.const_int(1 << (ty.get_bit_width() - 1), false);
+                    let is_smallest =
+                        self.builder
+                            .build_int_compare(IntPredicate::EQ, lhs, smallest, "is_smallest")?;
+                    let is_minus_one = self.builder.build_int_compare(
+                        IntPredicate::EQ,
+                        rhs,
+                        ty.const_all_ones(),
+                        "is_minus_one",
+                    )?;
+                    let overflowed = self
+                        .builder
+                        .build_and(is_smallest, is_minus_one, "overflowed")?;
+                    self.check(overflowed, &format!("integer overflow at {}", location(span)))?;
+                }
+                let value = match (operation, signed) {
+                    ("div", true) => self.builder.build_int_signed_div(lhs, rhs, "quotient")?,
+                    ("div", false) => self.builder.build_int_unsigned_div(lhs, rhs, "quotient")?,
+                    (_, true) => self.builder.build_int_signed_rem(lhs, rhs, "remainder")?,
+                    (_, false) => self.builder.build_int_unsigned_rem(lhs, rhs, "remainder")?,
+                };
+                Ok(value.into())
+            }
+            "shl" | "shr" => {
+                // Negative amounts compare as large unsigned ones, so they are caught too.
+                let bits = ty.const_int(ty.get_bit_width() as u64, false);
+                let too_far = self
+                    .builder
+                    .build_int_compare(IntPredicate::UGE, rhs, bits, "too_far")?;
+                self.check(
+                    too_far,
+                    &format!("shift amount out of range at {}", location(span)),
+                )?;
+                let value = if operation == "shl" {
+                    self.builder.build_left_shift(lhs, rhs, "shifted")?
+                } else {
+                    self.builder.build_right_shift(lhs, rhs, signed, "shifted")?
+                };
+                Ok(value.into())
+            }
+            comparison => {
+                let predicate = match (comparison, signed) {
+                    ("eq", _) => IntPredicate::EQ,
+                    ("ne", _) => IntPredicate::NE,
+                    ("lt", true) => IntPredicate::SLT,
+                    ("lt", false) => IntPredicate::ULT,
+                    ("gt", true) => IntPredicate::SGT,
+                    ("gt", false) => IntPredicate::UGT,
+                    ("le", true) => IntPredicate::SLE,
+                    ("le", false) => IntPredicate::ULE,
+                    ("ge", true) => IntPredicate::SGE,
+                    ("ge", false) => IntPredicate::UGE,
+                    _ => unreachable!("`{}` is not an integer intrinsic", comparison),
+                };
+                Ok(self
+                    .builder
+                    .build_int_compare(predicate, lhs, rhs, comparison)?
+                    .into())
+            }
+        }
+    }
+
+    /// Converts between numeric types, panicking if the value doesn't fit the target.
+    ///
+    /// Floats are truncated towards zero when converted to integers.
+    fn generate_cast(
+        &self,
+        value: BasicValueEnum<'ctx>,
+        from: &AstType,
+        to: &AstType,
+        span: &Span,
+    ) -> Result<BasicValueEnum<'ctx>, BuilderError> {
+        let message = format!("value out of range of {} at {}", to.source_name(), location(span));
+        let target = to
+            .to_qir_type()
+            .to_llvm_type(self.context)
+            .expect("numeric types have an LLVM type");
+        match (from.is_float(), to.is_float()) {
+            (false, false) => {
+                let value = value.into_int_value();
+                let target = target.into_int_type();
+                let (from_bits, to_bits) = (value.get_type().get_bit_width(), target.get_bit_width());
+                if to_bits < from_bits {
+                    let truncated = self.builder.build_int_truncate(value, target, "truncated")?;
+                    let restored = if to.is_signed() {
+                        self.builder.build_int_s_extend(truncated, value.get_type(), "restored")?
+                    } else {
+                        self.builder.build_int_z_extend(truncated, value.get_type(), "restored")?
+                    };
+                    let changed = self
+                        .builder
+                        .build_int_compare(IntPredicate::NE, restored, value, "changed")?;
+                    self.check(changed, &message)?;
+                    return Ok(truncated.into());
+                }
+                // Negative values don't fit unsigned types, and unsigned values with the
+                // top bit set don't fit the signed type of the same width.
+                if from.is_signed() != to.is_signed() && (from.is_signed() || to_bits == from_bits) {
+                    let negative = self.compare_to_zero(IntPredicate::SLT, value)?;
+                    self.check(negative, &message)?;
+                }
+                if to_bits == from_bits {
+                    Ok(value.into())
+                } else if from.is_signed() {
+                    Ok(self.builder.build_int_s_extend(value, target, "extended")?.into())
+                } else {
+                    Ok(self.builder.build_int_z_extend(value, target, "extended")?.into())
+                }
+            }
+            (false, true) => {
+                let value = value.into_int_value();
+                let target = target.into_float_type();
+                if from.is_signed() {
+                    Ok(self.builder.build_signed_int_to_float(value, target, "converted")?.into())
+                } else {
+                    Ok(self.builder.build_unsigned_int_to_float(value, target, "converted")?.into())
+                }
+            }
+            (true, false) => {
+                let value = value.into_float_value();
+                let target = target.into_int_type();
+                let bits = target.get_bit_width() as i32;
+                // Both bounds are powers of two, which floats represent exactly. NaN
+                // compares false with both.
+                let (low, low_predicate, high) = if to.is_signed() {
+                    (-(2f64.powi(bits - 1)), FloatPredicate::OGE, 2f64.powi(bits - 1))
+                } else {
+                    (-1.0, FloatPredicate::OGT, 2f64.powi(bits))
+                };
+                let float = value.get_type();
+                let above = self.builder.build_float_compare(
+                    low_predicate,
+                    value,
+                    float.const_float(low),
+                    "above",
+                )?;
+                let below = self.builder.build_float_compare(
+                    FloatPredicate::OLT,
+                    value,
+                    float.const_float(high),
+                    "below",
+                )?;
+                let fits = self.builder.build_and(above, below, "fits")?;
+                let out_of_range = self.builder.build_not(fits, "out_of_range")?;
+                self.check(out_of_range, &message)?;
+                if to.is_signed() {
+                    Ok(self.builder.build_float_to_signed_int(value, target, "converted")?.into())
+                } else {
+                    Ok(self.builder.build_float_to_unsigned_int(value, target, "converted")?.into())
+                }
+            }
+            (true, true) => {
+                let value = value.into_float_value();
+                let target = target.into_float_type();
+                if *from == AstType::Float32 {
+                    Ok(self.builder.build_float_ext(value, target, "extended")?.into())
+                } else {
+                    Ok(self.builder.build_float_trunc(value, target, "truncated")?.into())
+                }
+            }
+        }
+    }
+
+    /// Arrays are implemented by the runtime, which stores every element in a 64 bit slot.
+    fn generate_array_operation(
+        &self,
+        operation: &str,
+        arguments: &[BasicValueEnum<'ctx>],
+        return_type: &QIRType,
+    ) -> Generated<'ctx> {
+        let element = match operation {
+            "push" => Some(1),
+            "set" | "insert" => Some(2),
+            _ => None,
+        };
+        let mut values = arguments.to_vec();
+        if let Some(element) = element {
+            values[element] = self.write_slot(values[element])?.into();
+        }
+        if operation == "new" {
+            // The runtime takes whether the elements are objects as an `int32_t`.
+            let holds_objects = values[2].into_int_value();
+            values[2] = self
+                .builder
+                .build_int_z_extend(holds_objects, self.context.i32_type(), "holds_objects")?
+                .into();
+        }
+        let function = match operation {
+            "pop_unchecked" => "qre_array_pop".to_string(),
+            other => format!("qre_array_{}", other),
+        };
+        let result = self.call(self.runtime_function(&function), &values)?;
+        match (operation, result) {
+            ("get" | "pop_unchecked" | "remove", Some(slot)) => {
+                Ok(Some(self.read_slot(slot.into_int_value(), return_type)?))
+            }
+            (_, result) => Ok(result),
+        }
+    }
+
+    fn write_slot(&self, value: BasicValueEnum<'ctx>) -> Result<IntValue<'ctx>, BuilderError> {
+        let slot = self.context.i64_type();
+        match value {
+            BasicValueEnum::IntValue(value) if value.get_type().get_bit_width() == 64 => Ok(value),
+            BasicValueEnum::IntValue(value) => self.builder.build_int_z_extend(value, slot, "slot"),
+            BasicValueEnum::FloatValue(value) if value.get_type() == self.context.f32_type() => {
+                let bits = self
+                    .builder
+                    .build_bit_cast(value, self.context.i32_type(), "bits")?
+                    .into_int_value();
+                self.builder.build_int_z_extend(bits, slot, "slot")
+            }
+            BasicValueEnum::FloatValue(value) => Ok(self
+                .builder
+                .build_bit_cast(value, slot, "slot")?
+                .into_int_value()),
+            BasicValueEnum::PointerValue(value) => self.builder.build_ptr_to_int(value, slot, "slot"),
+            _ => unreachable!("array elements are numbers, bools or pointers"),
+        }
+    }
+
+    fn read_slot(&self, slot: IntValue<'ctx>, ty: &QIRType) -> Result<BasicValueEnum<'ctx>, BuilderError> {
+        match ty.to_llvm_type(self.context).expect("array elements are never void") {
+            BasicTypeEnum::IntType(ty) if ty.get_bit_width() == 64 => Ok(slot.into()),
+            BasicTypeEnum::IntType(ty) => Ok(self.builder.build_int_truncate(slot, ty, "element")?.into()),
+            BasicTypeEnum::FloatType(ty) if ty == self.context.f32_type() => {
+                let bits = self
+                    .builder
+                    .build_int_truncate(slot, self.context.i32_type(), "bits")?;
+                self.builder.build_bit_cast(bits, ty, "element")
+            }
+            BasicTypeEnum::FloatType(ty) => self.builder.build_bit_cast(slot, ty, "element"),
+            BasicTypeEnum::PointerType(ty) => Ok(self.builder.build_int_to_ptr(slot, ty, "element")?.into()),
+            _ => unreachable!("array elements are numbers, bools or pointers"),
+        }
+    }
+}
+
+fn binary_operands<'ctx>(arguments: &[BasicValueEnum<'ctx>], name: &str) -> [BasicValueEnum<'ctx>; 2] {
+    match arguments {
+        [lhs, rhs] => [*lhs, *rhs],
+        _ => unreachable!("`{}` takes two operands", name),
+    }
+}
@@ -0,0 +1,54 @@
+use crate::backend::llvm::target::write_object;
+use inkwell::module::Module;
+use std::path::Path;
+
+pub mod codegen;
+mod intrinsics;
+mod runtime;
+pub mod target;
+mod types;
+
+/// The forms `--emit` can write the generated module in.
+#[derive(Clone, Copy)]
+pub enum Emit {
+    /// Textual LLVM IR, `--emit=llvm-ir`.
+    LlvmIr,
+    /// LLVM bitcode, `--emit=bitcode`.
+    Bitcode,
+    /// An object file for the machine the compiler runs on, `--emit=obj`.
+    Object,
+    /// The textual form of the QIR the module is generated from, `--emit=qir`.
+    Qir,
+}
+
+impl Emit {
+    pub fn from_flag(value: &str) -> Option<Emit> {
+        match value {
+            "llvm-ir" => Some(Emit::LlvmIr),
+            "bitcode" => Some(Emit::Bitcode),
+            "obj" => Some(Emit::Object),
+            "qir" => Some(Emit::Qir),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Emit::LlvmIr => "ll",
+            Emit::Bitcode => "bc",
+            Emit::Object => "o",
+            Emit::Qir => "qir",
+        }
+    }
+
+    pub fn write(&self, module: &Module, path: &Path) -> Result<(), String> {
+        match self {
+            Emit::LlvmIr => module.print_to_file(path).map_err(|error| error.to_string()),
+            Emit::Bitcode if module.write_bitcode_to_path(path) => Ok(()),
+            Emit::Bitcode => Err(format!("can not write bitcode to {}", path.display())),
+            Emit::Object => write_object(module, path),
+            // Written by `print_program` before there is a module.
+            Emit::Qir => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::backend::llvm::codegen::CodeGenerator;
+use inkwell::attributes::{Attribute, AttributeLoc};
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum};
+use inkwell::values::FunctionValue;
+use inkwell::AddressSpace;
+
+impl<'ctx> CodeGenerator<'ctx> {
+    /// Declares a function of the QRE runtime, the C library every program is linked with.
+    ///
+    /// Arrays store their elements in 64 bit slots, the generated code converts
+    /// values to and from them.
+    pub(crate) fn runtime_function(&self, name: &str) -> FunctionValue<'ctx> {
+        if let Some(function) = self.module.get_function(name) {
+            return function;
+        }
+        let ptr: BasicTypeEnum = self.context.ptr_type(AddressSpace::default()).into();
+        let i64: BasicTypeEnum = self.context.i64_type().into();
+        let i32: BasicTypeEnum = self.context.i32_type().into();
+        let bool: BasicTypeEnum = self.context.bool_type().into();
+
+        let (parameters, returns) = match name {
+            "qre_alloc" => (vec![i64], Some(ptr)),
+            "qre_retain" | "qre_release" => (vec![ptr], Some(i32)),
+            "qre_panic" => (vec![ptr], None),
+            "qre_panic_message" => (vec![ptr, i64], None),
+            "qre_string_new" => (vec![i32, ptr, i64], Some(ptr)),
+            "qre_string_eq" => (vec![ptr, ptr], Some(bool)),
+            "qre_args" => (vec![i32, ptr, i32, i32], Some(ptr)),
+            "qre_array_new" => (vec![i32, i64, i32], Some(ptr)),
+            "qre_array_len" | "qre_array_capacity" => (vec![ptr], Some(i64)),
+            "qre_array_get" => (vec![ptr, i64, ptr], Some(i64)),
+            "qre_array_set" => (vec![ptr, i64, i64, ptr], None),
+            "qre_array_push" => (vec![ptr, i64], None),
+            "qre_array_pop" => (vec![ptr], Some(i64)),
+            "qre_array_insert" => (vec![ptr, i64, i64, ptr], None),
+            "qre_array_remove" => (vec![ptr, i64, ptr], Some(i64)),
+            "qre_array_reserve" => (vec![ptr, i64], None),
+            "qre_array_clear" | "qre_array_drop" | "qre_array_trace" | "qre_closure_drop" => {
+                (vec![ptr], None)
+            }
+            "qre_weak_new" => (vec![i32, ptr], Some(ptr)),
+            "qre_weak_get" => (vec![ptr, i32], Some(ptr)),
+            "qre_weak_drop" | "qre_gc_visit" => (vec![ptr], None),
+            "qre_collect_cycles" => (vec![], Some(i64)),
+            "qre_thread_spawn" => (vec![ptr], Some(i64)),
+            "qre_thread_join" => (vec![i64], None),
+            _ => unreachable!("`{}` is not a function of the runtime", name),
+        };
+        let parameters = parameters
+            .into_iter()
+            .map(BasicMetadataTypeEnum::from)
+            .collect::<Vec<_>>();
+        let function_type = match returns {
+            Some(returns) => returns.fn_type(&parameters, false),
+            None => self.context.void_type().fn_type(&parameters, false),
+        };
+        let function = self.module.add_function(name, function_type, None);
+        if name.starts_with("qre_panic") {
+            let noreturn = Attribute::get_named_enum_kind_id("noreturn");
+            function.add_attribute(
+                AttributeLoc::Function,
+                self.context.create_enum_attribute(noreturn, 0),
+            );
+        }
+        function
+    }
+}
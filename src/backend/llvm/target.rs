@@ -0,0 +1,38 @@
+use inkwell::module::Module;
+use inkwell::targets::{
+    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine,
+};
+use inkwell::OptimizationLevel;
+use std::path::Path;
+
+/// Writes `module` as an object file for the machine the compiler runs on.
+pub fn write_object(module: &Module, path: &Path) -> Result<(), String> {
+    let machine = host_machine()?;
+    module.set_triple(&machine.get_triple());
+    module.set_data_layout(&machine.get_target_data().get_data_layout());
+    machine
+        .write_to_file(module, FileType::Object, path)
+        .map_err(|error| error.to_string())
+}
+
+fn host_machine() -> Result<TargetMachine, String> {
+    Target::initialize_native(&InitializationConfig::default())?;
+    let triple = TargetMachine::get_default_triple();
+    let target = Target::from_triple(&triple).map_err(|error| error.to_string())?;
+    target
+        .create_target_machine(
+            &triple,
+            &TargetMachine::get_host_cpu_name().to_string(),
+            &TargetMachine::get_host_cpu_features().to_string(),
+            OptimizationLevel::Default,
+            // Position independent, as the C compiler links executables by default.
+            RelocMode::PIC,
+            CodeModel::Default,
+        )
+        .ok_or_else(|| {
+            format!(
+                "can not generate code for {}",
+                triple.as_str().to_string_lossy()
+            )
+        })
+}
@@ -0,0 +1,47 @@
+use crate::backend::qir::structs::QIRType;
+use inkwell::context::Context;
+use inkwell::types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum, FunctionType, StructType};
+use inkwell::AddressSpace;
+
+impl QIRType {
+    /// The LLVM type values of this type are held in, `None` for `Void`.
+    ///
+    /// Unsigned integers use the same types as signed ones, the operations on them
+    /// pick the signedness. Unions are pointers to the object or box holding the member.
+    pub fn to_llvm_type<'ctx>(&self, context: &'ctx Context) -> Option<BasicTypeEnum<'ctx>> {
+        match self {
+            QIRType::Int8 | QIRType::UInt8 => Some(context.i8_type().into()),
+            QIRType::Int16 | QIRType::UInt16 => Some(context.i16_type().into()),
+            QIRType::Int32 | QIRType::UInt32 => Some(context.i32_type().into()),
+            QIRType::Int64 | QIRType::UInt64 | QIRType::USize => Some(context.i64_type().into()),
+            QIRType::Float32 => Some(context.f32_type().into()),
+            QIRType::Float64 => Some(context.f64_type().into()),
+            QIRType::Bool => Some(context.bool_type().into()),
+            QIRType::Ptr | QIRType::Union => Some(context.ptr_type(AddressSpace::default()).into()),
+            QIRType::Void => None,
+            QIRType::Invalid => unreachable!("programs with type errors are not lowered"),
+        }
+    }
+}
+
+/// The type of a function taking `parameters` and returning `returns`.
+pub(crate) fn function_type<'ctx>(
+    context: &'ctx Context,
+    parameters: &[BasicMetadataTypeEnum<'ctx>],
+    returns: &QIRType,
+) -> FunctionType<'ctx> {
+    match returns.to_llvm_type(context) {
+        Some(returns) => returns.fn_type(parameters, false),
+        None => context.void_type().fn_type(parameters, false),
+    }
+}
+
+/// The literal structure with the given fields, as `GetFieldPtr` and
+/// `InstantiateStructure` describe them.
+pub(crate) fn struct_type<'ctx>(context: &'ctx Context, fields: &[QIRType]) -> StructType<'ctx> {
+    let fields = fields
+        .iter()
+        .map(|field| field.to_llvm_type(context).expect("fields are never void"))
+        .collect::<Vec<_>>();
+    context.struct_type(&fields, false)
+}
@@ -1 +1,3 @@
+pub mod link;
+pub mod llvm;
 pub mod qir;
@@ -0,0 +1,34 @@
+use crate::backend::qir::layout::object_fields;
+use crate::backend::qir::structs::QIRType;
+use crate::frontend::span::Span;
+
+/// Index of the number of elements in an array object, for `GetFieldPtr`.
+pub const ARRAY_LENGTH_FIELD: i32 = 2;
+/// Index of the number of elements the buffer of an array object has room for.
+pub const ARRAY_CAPACITY_FIELD: i32 = 3;
+/// Index of the pointer to the buffer holding the elements of an array object.
+pub const ARRAY_BUFFER_FIELD: i32 = 4;
+/// Index of whether the elements of an array object are references to objects,
+/// which the array releases when they are removed or the array is freed.
+pub const ARRAY_HOLDS_OBJECTS_FIELD: i32 = 5;
+
+/// The fields of an array object.
+///
+/// The elements live in a separate buffer, so growing an array moves only the
+/// buffer and every reference to the array object sees the new elements.
+pub fn array_fields() -> Vec<QIRType> {
+    object_fields([QIRType::USize, QIRType::USize, QIRType::Ptr, QIRType::Bool])
+}
+
+/// The capacity an array grows to when an element is added to a full buffer.
+pub fn grown_capacity(capacity: u64) -> u64 {
+    (capacity * 2).max(4)
+}
+
+/// The message the program panics with when the index at `span` is out of bounds.
+pub fn bounds_check_message(span: &Span) -> String {
+    format!(
+        "index out of bounds at {}:{}:{}",
+        span.file_name, span.row_start, span.column_start
+    )
+}
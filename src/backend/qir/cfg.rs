@@ -0,0 +1,62 @@
+use crate::backend::qir::structs::{Label, QIRBasicBlock};
+
+/// The blocks that branch to each block of a function.
+pub fn predecessors(blocks: &[QIRBasicBlock]) -> Vec<Vec<Label>> {
+    let mut predecessors = vec![vec![]; blocks.len()];
+    for (label, block) in blocks.iter().enumerate() {
+        for successor in block.terminator.successors() {
+            predecessors[successor].push(label);
+        }
+    }
+    predecessors
+}
+
+/// The blocks control can reach from `from` by following at least one edge, so
+/// `from` is among them only if it is part of a loop.
+pub fn reachable_from(blocks: &[QIRBasicBlock], from: Label) -> Vec<bool> {
+    let mut reachable = vec![false; blocks.len()];
+    let mut pending = blocks[from].terminator.successors();
+    while let Some(label) = pending.pop() {
+        if !reachable[label] {
+            reachable[label] = true;
+            pending.extend(blocks[label].terminator.successors());
+        }
+    }
+    reachable
+}
+
+/// Solves a forward data-flow problem over the blocks of a function, and returns the
+/// state at the start of each block. `transfer` gives the state at the end of a block
+/// from the state at its start, and `join` merges the state from another predecessor
+/// into a state, returning whether it changed.
+///
+/// Blocks the entry doesn't lead to get no state.
+pub fn forward<T: Clone>(
+    blocks: &[QIRBasicBlock],
+    entry: T,
+    mut join: impl FnMut(&mut T, &T) -> bool,
+    mut transfer: impl FnMut(&QIRBasicBlock, &T) -> T,
+) -> Vec<Option<T>> {
+    let mut states = vec![None::<T>; blocks.len()];
+    states[0] = Some(entry);
+    let mut pending = vec![0];
+    while let Some(label) = pending.pop() {
+        let Some(state) = &states[label] else {
+            continue;
+        };
+        let out = transfer(&blocks[label], state);
+        for successor in blocks[label].terminator.successors() {
+            let changed = match &mut states[successor] {
+                Some(state) => join(state, &out),
+                state => {
+                    *state = Some(out.clone());
+                    true
+                }
+            };
+            if changed && !pending.contains(&successor) {
+                pending.push(successor);
+            }
+        }
+    }
+    states
+}
@@ -0,0 +1,184 @@
+use crate::backend::qir::layout::{object_fields, CLOSURE_DROP_FIELD};
+use crate::backend::qir::lower::load_field;
+use crate::backend::qir::structs::{
+    QIRBasicBlock, QIRExpression, QIRHeader, QIRLocalVariable, QIRTerminator, QIRType,
+};
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstType, PathData};
+use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
+
+/// The destructor of arrays, which releases their elements if they are objects.
+const ARRAY_DROP: &str = "<array>::<drop>";
+/// The destructor of closures, which calls the function in their drop field.
+const CLOSURE_DROP: &str = "<closure>::<drop>";
+/// The destructor of weak references, which stops tracking them.
+const WEAK_DROP: &str = "<weak>::<drop>";
+/// The tracer of arrays holding objects, which visits their elements.
+const ARRAY_TRACE: &str = "<array>::<trace>";
+
+/// The function releasing the captures of the closures of `lambda`.
+pub(crate) fn drop_function(lambda: &PathData) -> PathData {
+    PathData {
+        name: format!("{}::<drop>", lambda.name),
+        token: lambda.token.clone(),
+    }
+}
+
+/// Lowers the function that releases the captured values of a lambda's closure
+/// objects, which `layout` describes.
+pub(crate) fn lower_closure_drop(lambda: &PathData, layout: &[QIRType]) -> QIRHeader {
+    let fields = (CLOSURE_DROP_FIELD + 1..layout.len() as i32)
+        .filter(|field| layout[*field as usize].is_refcounted())
+        .collect::<Vec<_>>();
+    for_fields(drop_function(lambda), layout, &fields, release)
+}
+
+/// Lowers a destructor for every type that was given a type ID and holds references,
+/// a tracer for every type whose objects can be part of a reference cycle, and the
+/// tables the runtime looks them up in.
+///
+/// Tracers visit the same references the destructor releases, for the cycle
+/// collector. Closures have none, so cycles through captured values are never
+/// collected.
+///
+/// Runs after everything else is lowered, so every type that can be instantiated
+/// already has its ID. The shared functions have no source location of their own
+/// and are attributed to `token`.
+pub(crate) fn lower_destructors(info: &TypeInformation, token: &Token) -> Vec<QIRHeader> {
+    let path = |name: String| PathData {
+        name,
+        token: token.clone(),
+    };
+    let mut functions = vec![];
+    let mut entries = vec![];
+    let mut tracers = vec![];
+    for (index, ty) in info.type_ids.iter().enumerate() {
+        let type_id = index as u32 + 1;
+        let name = match ty {
+            AstType::ArrayOf(element) => {
+                if element.to_qir_type().is_refcounted() {
+                    tracers.push((type_id, path(ARRAY_TRACE.to_string())));
+                }
+                ARRAY_DROP.to_string()
+            }
+            AstType::Function(..) => CLOSURE_DROP.to_string(),
+            AstType::Weak(_) => WEAK_DROP.to_string(),
+            AstType::Structure(name) => match structure_fields(info, name) {
+                Some(fields) if fields.iter().any(QIRType::is_refcounted) => {
+                    let layout = object_fields(fields);
+                    let tracked = (2..layout.len() as i32)
+                        .filter(|field| layout[*field as usize].is_refcounted())
+                        .collect::<Vec<_>>();
+                    let drop = path(format!("{}::<drop>", name));
+                    let trace = path(format!("{}::<trace>", name));
+                    functions.push(for_fields(drop.clone(), &layout, &tracked, release));
+                    functions.push(for_fields(trace.clone(), &layout, &tracked, visit));
+                    tracers.push((type_id, trace));
+                    drop.name
+                }
+                _ => continue,
+            },
+            _ => continue,
+        };
+        entries.push((type_id, path(name)));
+    }
+
+    let shared = [
+        (ARRAY_DROP, "qre::array::drop"),
+        (CLOSURE_DROP, "qre::closure::drop"),
+        (WEAK_DROP, "qre::weak::drop"),
+        (ARRAY_TRACE, "qre::array::trace"),
+    ];
+    for (shared, intrinsic) in shared {
+        if entries.iter().chain(&tracers).any(|(_, name)| name.name == shared) {
+            let name = PathData {
+                name: shared.to_string(),
+                token: token.clone(),
+            };
+            let call = QIRExpression::Invoke {
+                name: PathData {
+                    name: intrinsic.to_string(),
+                    token: token.clone(),
+                },
+                arguments: vec![load_self()],
+                return_type: QIRType::Void,
+            };
+            functions.push(object_function(name, vec![call]));
+        }
+    }
+    functions.push(QIRHeader::Destructors { entries, tracers });
+    functions
+}
+
+/// The QIR types of the fields of the struct, enum or enum payload named `name`.
+fn structure_fields(info: &TypeInformation, name: &str) -> Option<Vec<QIRType>> {
+    match info.names.get(name) {
+        Some(ProgramType::Structure { fields, .. }) => {
+            return Some(fields.iter().map(|(_, ty)| ty.to_qir_type()).collect());
+        }
+        // The tag and the pointer to the payload.
+        Some(ProgramType::Enum { .. }) => return Some(vec![QIRType::UInt32, QIRType::Ptr]),
+        _ => {}
+    }
+    let (enum_name, variant) = name.rsplit_once("::")?;
+    let Some(ProgramType::Enum { variants, .. }) = info.names.get(enum_name) else {
+        return None;
+    };
+    let (_, fields) = variants.iter().find(|(declared, _)| declared == variant)?;
+    Some(fields.iter().map(|ty| ty.to_qir_type()).collect())
+}
+
+/// A function applying `apply` to each of the given fields of its object.
+fn for_fields(
+    name: PathData,
+    layout: &[QIRType],
+    fields: &[i32],
+    apply: fn(QIRExpression, &Token) -> QIRExpression,
+) -> QIRHeader {
+    let exprs = fields
+        .iter()
+        .map(|field| apply(load_field(load_self(), layout.to_vec(), *field), &name.token))
+        .collect();
+    object_function(name, exprs)
+}
+
+fn release(value: QIRExpression, _: &Token) -> QIRExpression {
+    QIRExpression::Release {
+        ptr: Box::new(value),
+    }
+}
+
+/// Hands a reference to the cycle collector, which is tracing the objects it points to.
+fn visit(value: QIRExpression, token: &Token) -> QIRExpression {
+    QIRExpression::Invoke {
+        name: PathData {
+            name: "qre::gc::visit".to_string(),
+            token: token.clone(),
+        },
+        arguments: vec![value],
+        return_type: QIRType::Void,
+    }
+}
+
+/// A function taking an object as its only parameter.
+fn object_function(name: PathData, exprs: Vec<QIRExpression>) -> QIRHeader {
+    QIRHeader::Function {
+        name,
+        parameters: vec![(QIRLocalVariable { id: 0 }, QIRType::Ptr)],
+        returns: QIRType::Void,
+        locals: vec![QIRType::Ptr],
+        blocks: vec![QIRBasicBlock {
+            exprs,
+            terminator: QIRTerminator::Ret { value: None },
+        }],
+    }
+}
+
+fn load_self() -> QIRExpression {
+    QIRExpression::LoadFromPtr {
+        ptr: Box::new(QIRExpression::GetLocalPtr {
+            local: QIRLocalVariable { id: 0 },
+        }),
+        output_type: QIRType::Ptr,
+    }
+}
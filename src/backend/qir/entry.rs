@@ -0,0 +1,85 @@
+use crate::backend::qir::lower::{Code, FunctionLowering, LoweredProgram};
+use crate::backend::qir::structs::{QIRExpression, QIRHeader, QIRLocalVariable, QIRTerminator, QIRType};
+use crate::frontend::parser::ast::{AstHeader, AstType, PathData};
+use crate::frontend::typecheck::data::TypeInformation;
+
+/// The function the program starts in, which the backend exports as the C `main`.
+///
+/// It takes the `argc` and `argv` of the process and returns its exit code.
+pub const ENTRY_POINT: &str = "<entry>";
+
+/// Lowers the entry point that calls `main`, passing it the command-line arguments
+/// if it takes them and returning what it returns as the exit code.
+///
+/// Programs without a `main` have no entry point, they can't be run.
+pub(crate) fn lower_entry(
+    headers: &[AstHeader],
+    info: &mut TypeInformation,
+    program: &mut LoweredProgram,
+) -> Option<QIRHeader> {
+    let (name, parameters, returns) = headers.iter().find_map(|header| match header {
+        AstHeader::Function {
+            name,
+            parameters,
+            returns,
+            ..
+        } if name.name == "main" => Some((name, parameters, returns)),
+        _ => None,
+    })?;
+    let token = &name.token;
+    let mut lowering = FunctionLowering::new(info, program, ENTRY_POINT, &[], &AstType::Int32);
+    let argc = lowering.temp(QIRType::Int32);
+    let argv = lowering.temp(QIRType::Ptr);
+
+    let mut code = Code::default();
+    let mut arguments = vec![];
+    if !parameters.is_empty() {
+        let string = AstType::Structure("std::string".to_string());
+        let array_type_id = lowering.info.type_id(&AstType::ArrayOf(Box::new(string.clone())));
+        let string_type_id = lowering.info.type_id(&string);
+        arguments.push(lowering.call(
+            "qre::args",
+            vec![
+                lowering.load_local(argc),
+                lowering.load_local(argv),
+                QIRExpression::Integer {
+                    value: array_type_id as i128,
+                    ty: QIRType::UInt32,
+                },
+                QIRExpression::Integer {
+                    value: string_type_id as i128,
+                    ty: QIRType::UInt32,
+                },
+            ],
+            QIRType::Ptr,
+            token,
+        ));
+    }
+    let result = lowering.call(&name.name, arguments, returns.to_qir_type(), token);
+    let exit_code = if *returns == AstType::Void {
+        code.push(result);
+        QIRExpression::Integer {
+            value: 0,
+            ty: QIRType::Int32,
+        }
+    } else {
+        lowering.convert(result, returns, &AstType::Int32, token, &mut code)
+    };
+    code.terminate(QIRTerminator::Ret {
+        value: Some(exit_code),
+    });
+
+    Some(QIRHeader::Function {
+        name: PathData {
+            name: ENTRY_POINT.to_string(),
+            token: token.clone(),
+        },
+        parameters: vec![
+            (QIRLocalVariable { id: argc }, QIRType::Int32),
+            (QIRLocalVariable { id: argv }, QIRType::Ptr),
+        ],
+        returns: QIRType::Int32,
+        locals: lowering.locals,
+        blocks: code.into_blocks(),
+    })
+}
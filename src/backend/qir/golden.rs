@@ -0,0 +1,186 @@
+use crate::backend::qir::parse::parse_program;
+use crate::backend::qir::print::print_program;
+use crate::backend::qir::rc_elision::eliminate_refcounts;
+use crate::backend::qir::refcount::insert_refcounts;
+use crate::backend::qir::structs::QIRHeader;
+use crate::backend::qir::verify::verify_program;
+use std::path::{Path, PathBuf};
+
+/// The `.qir` files in `tests/qir/<directory>`, either the cases or the outputs
+/// expected from them, which end in `.out.qir`.
+fn files(directory: &str, outputs: bool) -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/qir")
+        .join(directory);
+    let mut files = std::fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "qir"))
+        .filter(|path| path.to_string_lossy().ends_with(".out.qir") == outputs)
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+fn parse(path: &Path) -> Vec<QIRHeader> {
+    let text = std::fs::read_to_string(path).unwrap();
+    parse_program(&path.display().to_string(), &text)
+        .unwrap_or_else(|(message, span)| panic!("{:?} {}", span, message))
+}
+
+/// Runs `pass` on each case in `tests/qir/<directory>` and compares what it printed
+/// with the case's `.out.qir`.
+fn check_pass(directory: &str, pass: fn(&mut QIRHeader)) {
+    for case in files(directory, false) {
+        let mut program = parse(&case);
+        program.iter_mut().for_each(pass);
+        let errors = verify_program(&program);
+        assert!(errors.is_empty(), "{}:\n{}", case.display(), errors.join("\n"));
+        check_output(&case.with_extension("out.qir"), &print_program(&program));
+    }
+}
+
+/// Compares `printed` with the expected output at `path`. With `QRE_BLESS` set, it is
+/// written there instead.
+fn check_output(path: &Path, printed: &str) {
+    if std::env::var_os("QRE_BLESS").is_some() {
+        std::fs::write(path, printed).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(path).unwrap_or_default();
+    assert_eq!(
+        printed,
+        expected,
+        "{} changed, run with QRE_BLESS=1 if that was intended",
+        path.display()
+    );
+}
+
+#[test]
+fn printed_programs_parse_back() {
+    let outputs = ["refcount", "rc_elision"]
+        .into_iter()
+        .flat_map(|directory| files(directory, true));
+    for path in files("print", false).into_iter().chain(outputs) {
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(print_program(&parse(&path)), text, "{}", path.display());
+    }
+}
+
+#[test]
+fn parse_errors_point_at_the_problem() {
+    let text = "fn f() -> i32 locals [] {\nbb0:\n    ret (i32 1, i32 2)\n}\n";
+    let (message, span) = parse_program("f.qir", text).err().unwrap();
+    assert_eq!(message, "expected 1 operand, found 2");
+    assert_eq!((span.row_start, span.column_start), (3, 9));
+
+    let text = "fn f() -> void locals [] {\nbb1:\n    ret\n}\n";
+    let (message, span) = parse_program("f.qir", text).err().unwrap();
+    assert_eq!(message, "expected bb0, found bb1");
+    assert_eq!((span.row_start, span.column_start), (2, 1));
+
+    let (message, span) = parse_program("f.qir", "struct S { u32, bytes }").err().unwrap();
+    assert_eq!(message, "expected a type, found `b`");
+    assert_eq!((span.row_start, span.column_start), (1, 17));
+}
+
+#[test]
+fn refcounts_are_inserted() {
+    check_pass("refcount", insert_refcounts);
+}
+
+#[test]
+fn redundant_refcounts_are_eliminated() {
+    check_pass("rc_elision", eliminate_refcounts);
+}
+
+#[test]
+fn broken_programs_are_rejected() {
+    let text = "
+struct Pair { u32, i32 }
+
+fn first(%0: ptr) -> i32 locals [ptr, i32, bool] {
+bb0:
+    store i32 (%1, load i32 (field_ptr i32 { u32, u32, i32 } 3 (load ptr (%0))))
+    store i64 (%1, i64 0)
+    cond_br (load bool (%2)), bb1, bb2
+bb1:
+    store bool (%2, true)
+    br bb2
+bb2:
+    cond_br (i32 1), bb2, bb3
+bb3:
+    call i32 first(i32 1)
+    ret (load ptr (%0))
+}
+";
+    let program = parse_program("broken.qir", text).unwrap();
+    assert_eq!(
+        verify_program(&program),
+        [
+            "struct Pair does not start with the type ID and reference count",
+            "in first: the structure has no field 3: field_ptr i32 { u32, u32, i32 } 3 (load ptr (%0))",
+            "in first: stores i64 to i32: store i64 (%1, i64 0)",
+            "in first: %2 is loaded before it is stored to",
+            "in first: the condition is i32, expected bool: i32 1",
+            "in first: the argument is i32, expected ptr: i32 1",
+            "in first: the returned value is ptr, expected i32: load ptr (%0)",
+        ]
+    );
+}
+
+#[test]
+fn branches_stay_inside_the_function() {
+    let text = "
+fn jump() -> void locals [] {
+bb0:
+    br bb1
+bb1:
+    cond_br (true), bb0, bb2
+}
+
+fn empty() -> void locals [] {
+}
+";
+    let program = parse_program("jump.qir", text).unwrap();
+    assert_eq!(
+        verify_program(&program),
+        [
+            "in jump: bb1 branches back to the entry block",
+            "in jump: bb1 branches to bb2, which does not exist",
+            "in empty: the function has no blocks",
+        ]
+    );
+}
+
+#[test]
+fn loads_need_a_store_that_reaches_them() {
+    let text = "
+fn count(%0: bool) -> i32 locals [bool, i32, i32, i32] {
+bb0:
+    cond_br (load bool (%0)), bb1, bb2
+bb1:
+    store i32 (%1, i32 1)
+    br bb2
+bb2:
+    cond_br (call bool qre::i32::lt(load i32 (%2), i32 10)), bb3, bb4
+bb3:
+    store i32 (%2, load i32 (%1))
+    br bb2
+bb4:
+    ret (call i32 qre::i32::add(load i32 (%2), load i32 (%3)))
+}
+";
+    let program = parse_program("count.qir", text).unwrap();
+    // %1 is only stored on one of the paths, and %2 only at the end of the loop, after
+    // it checked the condition the first time.
+    assert_eq!(
+        verify_program(&program),
+        [
+            "in count: %2 is loaded before it is stored to",
+            "in count: %1 is loaded before it is stored to",
+            "in count: %2 is loaded before it is stored to",
+            "in count: %3 is loaded before it is stored to",
+        ]
+    );
+}
@@ -0,0 +1,176 @@
+use crate::backend::qir::structs::{QIRHeader, QIRType};
+use crate::frontend::parser::ast::{AstHeader, AstType, PathData};
+use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
+
+impl AstHeader {
+    /// The QIR structures that hold values of the type this header declares.
+    ///
+    /// An enum becomes a tagged structure holding the index of its variant and a
+    /// pointer to a refcounted payload structure named `Enum::Variant`. Variants
+    /// that carry no values have no payload structure and store a null pointer.
+    ///
+    /// Every structure starts with the type ID and reference count of the object.
+    pub fn lower_layouts(&self) -> Vec<QIRHeader> {
+        match self {
+            AstHeader::Struct { name, fields, .. } => vec![QIRHeader::Struct {
+                name: name.clone(),
+                fields: object_fields(fields.iter().map(|(_, ty)| ty.to_qir_type())),
+            }],
+            AstHeader::Enum { name, variants } => {
+                let mut layouts = vec![QIRHeader::Struct {
+                    name: name.clone(),
+                    fields: object_fields([QIRType::UInt32, QIRType::Ptr]),
+                }];
+                for variant in variants.iter().filter(|variant| !variant.fields.is_empty()) {
+                    layouts.push(QIRHeader::Struct {
+                        name: PathData {
+                            name: format!("{}::{}", name.name, variant.name),
+                            token: variant.token.clone(),
+                        },
+                        fields: object_fields(variant.fields.iter().map(|ty| ty.to_qir_type())),
+                    });
+                }
+                layouts
+            }
+            AstHeader::Import(_) | AstHeader::Function { .. } | AstHeader::Interface { .. } => vec![],
+        }
+    }
+}
+
+/// Index of the type ID every heap object starts with.
+pub const TYPE_ID_FIELD: i32 = 0;
+/// Index of the reference count of a heap object.
+pub const REFCOUNT_FIELD: i32 = 1;
+/// Set in the reference count of objects other threads can use, which the runtime
+/// retains and releases atomically.
+pub const SHARED_FLAG: i128 = 0x0800_0000;
+/// Index of the value in the box of a union member that is not a heap object itself.
+pub const BOX_VALUE_FIELD: i32 = 2;
+/// Index of the variant of an enum object.
+pub const ENUM_TAG_FIELD: i32 = 2;
+/// Index of the pointer to the payload of an enum object.
+pub const ENUM_PAYLOAD_FIELD: i32 = 3;
+/// Index of the function pointer of a closure object.
+pub const CLOSURE_FUNCTION_FIELD: i32 = 2;
+/// Index of the function that releases the captures of a closure object, or null for
+/// closures without captures. The captured values follow it.
+pub const CLOSURE_DROP_FIELD: i32 = 3;
+
+/// The fields of a heap object, the type ID and reference count followed by `fields`.
+pub(crate) fn object_fields(fields: impl IntoIterator<Item = QIRType>) -> Vec<QIRType> {
+    [QIRType::UInt32, QIRType::UInt32].into_iter().chain(fields).collect()
+}
+
+impl TypeInformation {
+    /// The index `GetFieldPtr` uses for a field of a struct, counting the object header.
+    pub fn field_index(&self, struct_name: &str, field: &str) -> Option<i32> {
+        let Some(ProgramType::Structure { fields, .. }) = self.names.get(struct_name) else {
+            return None;
+        };
+        fields
+            .iter()
+            .position(|(declared, _)| declared == field)
+            .map(|index| index as i32 + 2)
+    }
+
+    /// The QIR structure holding objects of the struct `struct_name`.
+    pub fn struct_layout(&self, struct_name: &str) -> Vec<QIRType> {
+        let Some(ProgramType::Structure { fields, .. }) = self.names.get(struct_name) else {
+            unreachable!("`{}` is checked to be a struct by the typechecker", struct_name);
+        };
+        object_fields(fields.iter().map(|(_, ty)| ty.to_qir_type()))
+    }
+
+    /// The structs that implement `interface`, sorted by name.
+    pub fn implementors(&self, interface: &str) -> Vec<String> {
+        let mut implementors = self
+            .names
+            .values()
+            .filter_map(|ty| match ty {
+                ProgramType::Structure {
+                    name, implements, ..
+                } if implements.iter().any(|implemented| implemented == interface) => {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        implementors.sort();
+        implementors
+    }
+}
+
+impl AstHeader {
+    /// The vtable of an interface, with the implementations of every struct that implements it.
+    pub fn lower_vtable(&self, info: &mut TypeInformation) -> Option<QIRHeader> {
+        let AstHeader::Interface { name, methods, .. } = self else {
+            return None;
+        };
+        let entries = info
+            .implementors(&name.name)
+            .into_iter()
+            .map(|struct_name| {
+                let type_id = info.type_id(&AstType::Structure(struct_name.clone()));
+                let functions = methods
+                    .iter()
+                    .map(|method| PathData {
+                        name: format!("{}::{}", struct_name, method.name.name),
+                        token: method.name.token.clone(),
+                    })
+                    .collect();
+                (type_id, functions)
+            })
+            .collect();
+        Some(QIRHeader::VTable {
+            interface: name.clone(),
+            entries,
+        })
+    }
+}
+
+impl AstType {
+    /// Whether values of this type are pointers to refcounted objects, which union
+    /// values can point to directly instead of boxing them.
+    pub fn is_heap_object(&self) -> bool {
+        matches!(
+            self,
+            AstType::ArrayOf(_) | AstType::Structure(_) | AstType::Function(..) | AstType::Weak(_)
+        )
+    }
+
+    pub fn to_qir_type(&self) -> QIRType {
+        match self {
+            AstType::Int8 => QIRType::Int8,
+            AstType::Int16 => QIRType::Int16,
+            AstType::Int32 => QIRType::Int32,
+            AstType::Int64 => QIRType::Int64,
+            AstType::UInt8 => QIRType::UInt8,
+            AstType::UInt16 => QIRType::UInt16,
+            AstType::UInt32 => QIRType::UInt32,
+            AstType::UInt64 => QIRType::UInt64,
+            AstType::USize => QIRType::USize,
+            AstType::Float32 => QIRType::Float32,
+            AstType::Float64 => QIRType::Float64,
+            AstType::Bool => QIRType::Bool,
+            AstType::None
+            | AstType::ArrayOf(_)
+            | AstType::Structure(_)
+            | AstType::Function(..)
+            | AstType::Weak(_) => QIRType::Ptr,
+            AstType::UnionOf(..) => QIRType::Union,
+            AstType::Void => QIRType::Void,
+            AstType::Invalid => QIRType::Invalid,
+            AstType::Instance(..) => unreachable!("instances of generics are resolved by the typechecker"),
+        }
+    }
+}
+
+impl QIRType {
+    /// Whether values of this type hold a reference to an object, which is retained
+    /// when copied and released when dropped.
+    ///
+    /// `none` is a null `Ptr`, which retaining and releasing ignore.
+    pub fn is_refcounted(&self) -> bool {
+        matches!(self, QIRType::Ptr | QIRType::Union)
+    }
+}
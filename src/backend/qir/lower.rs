@@ -0,0 +1,777 @@
+use crate::backend::qir::arrays::bounds_check_message;
+use crate::backend::qir::cfg::reachable_from;
+use crate::backend::qir::destructors::lower_destructors;
+use crate::backend::qir::entry::lower_entry;
+use crate::backend::qir::rc_elision::eliminate_refcounts;
+use crate::backend::qir::refcount::insert_refcounts;
+use crate::backend::qir::structs::{
+    Label, QIRBasicBlock, QIRExpression, QIRHeader, QIRLocalVariable, QIRTerminator, QIRType,
+};
+use crate::backend::qir::verify::verify_program;
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{
+    AstCodeBlock, AstHeader, AstIterable, AstLocal, AstStatement, AstType,
+    PathData,
+};
+use crate::frontend::typecheck::data::TypeInformation;
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+
+/// Lowers a typechecked program to QIR.
+///
+/// Generic headers must already have been replaced by their instances. With
+/// `optimize_refcounts`, retains and releases that don't change when objects are freed
+/// are removed again.
+pub fn lower_program(
+    headers: &[AstHeader],
+    info: &mut TypeInformation,
+    optimize_refcounts: bool,
+) -> Vec<QIRHeader> {
+    let mut program = LoweredProgram {
+        functions: vec![],
+        thunks: HashSet::new(),
+        lambdas: 0,
+        drops: vec![],
+    };
+    let mut lowered = headers
+        .iter()
+        .flat_map(|header| header.lower_layouts())
+        .collect::<Vec<_>>();
+    lowered.extend(headers.iter().filter_map(|header| header.lower_vtable(info)));
+
+    for header in headers {
+        let AstHeader::Function {
+            name,
+            parameters,
+            returns,
+            code_block,
+            locals,
+            ..
+        } = header
+        else {
+            continue;
+        };
+        let parameters = parameters
+            .iter()
+            .enumerate()
+            .map(|(id, (ty, _))| (QIRLocalVariable { id }, ty.to_qir_type()))
+            .collect();
+        let function = FunctionLowering::new(info, &mut program, &name.name, locals, returns)
+            .lower_body(name.clone(), parameters, Code::default(), code_block);
+        program.functions.push(function);
+    }
+    verify_after("lowering", &program.functions);
+    program.functions.iter_mut().for_each(insert_refcounts);
+    verify_after("inserting refcounts", &program.functions);
+    if optimize_refcounts {
+        program.functions.iter_mut().for_each(eliminate_refcounts);
+        verify_after("eliminating refcounts", &program.functions);
+    }
+
+    // The entry point hands the arguments it creates to `main`, and the destructors
+    // work on objects whose references are all gone, so neither is refcounted.
+    if let Some(entry) = lower_entry(headers, info, &mut program) {
+        program.functions.push(entry);
+    }
+    let token = program.functions.iter().find_map(|function| match function {
+        QIRHeader::Function { name, .. } => Some(name.token.clone()),
+        _ => None,
+    });
+    lowered.extend(program.functions);
+    lowered.extend(program.drops);
+    if let Some(token) = token {
+        lowered.extend(lower_destructors(info, &token));
+    }
+    verify_after("lowering the entry point and destructors", &lowered);
+    lowered
+}
+
+/// Checks the QIR in debug builds, so a pass that breaks it fails right away instead
+/// of producing a module LLVM rejects, or a program that crashes.
+fn verify_after(stage: &str, program: &[QIRHeader]) {
+    if cfg!(debug_assertions) {
+        let errors = verify_program(program);
+        assert!(errors.is_empty(), "invalid QIR after {}:\n{}", stage, errors.join("\n"));
+    }
+}
+
+/// Functions made while lowering the headers of a program.
+pub(crate) struct LoweredProgram {
+    pub(crate) functions: Vec<QIRHeader>,
+    /// Named functions that already have a thunk, which calls them through a closure object.
+    pub(crate) thunks: HashSet<String>,
+    /// The number of lambdas lowered so far, which numbers the functions made for them.
+    pub(crate) lambdas: usize,
+    /// The functions releasing the captures of lambdas.
+    pub(crate) drops: Vec<QIRHeader>,
+}
+
+/// Lowers the body of one function or lambda.
+pub(crate) struct FunctionLowering<'a> {
+    pub(crate) info: &'a mut TypeInformation,
+    pub(crate) program: &'a mut LoweredProgram,
+    /// The name of the function, lambdas declared in it are named after it.
+    pub(crate) name: String,
+    /// The declared types of the locals of the AST, which the first QIR locals hold.
+    pub(crate) ast_locals: Vec<AstType>,
+    /// The AST locals followed by the temporaries introduced while lowering.
+    pub(crate) locals: Vec<QIRType>,
+    pub(crate) returns: AstType,
+    /// The last label given to a block, the entry is labelled 0.
+    pub(crate) labels: Label,
+}
+
+/// What happens to the tail expression of a block.
+#[derive(Clone, Copy)]
+pub(crate) enum Tail {
+    /// Falling off the end of the block falls off the end of the function, so the
+    /// tail is its return value.
+    Return,
+    /// The block is a match arm, the tail is stored to the local holding the result.
+    Store(usize),
+    Discard,
+}
+
+/// A `foreach` loop over a range or an array, which counts a hidden index up by one.
+struct CountingLoop {
+    /// Whether to run the body for the value of the index.
+    condition: QIRExpression,
+    /// Stores the element for the value of the index into the loop variable.
+    first: QIRExpression,
+    index: usize,
+    index_ty: AstType,
+    /// The end of an inclusive range. It can be the largest value of its type, so the
+    /// loop stops once the index reaches it instead of counting past it.
+    last: Option<QIRExpression>,
+}
+
+impl<'a> FunctionLowering<'a> {
+    pub(crate) fn new(
+        info: &'a mut TypeInformation,
+        program: &'a mut LoweredProgram,
+        name: &str,
+        locals: &[AstLocal],
+        returns: &AstType,
+    ) -> FunctionLowering<'a> {
+        FunctionLowering {
+            info,
+            program,
+            name: name.to_string(),
+            ast_locals: locals.iter().map(|local| local.ty.clone()).collect(),
+            locals: locals.iter().map(|local| local.ty.to_qir_type()).collect(),
+            returns: returns.clone(),
+            labels: 0,
+        }
+    }
+
+    /// Lowers `code_block` after the expressions in `entry`, and builds the function.
+    pub(crate) fn lower_body(
+        mut self,
+        name: PathData,
+        parameters: Vec<(QIRLocalVariable, QIRType)>,
+        mut entry: Code,
+        code_block: &AstCodeBlock,
+    ) -> QIRHeader {
+        self.lower_block(code_block, Tail::Return, &mut entry);
+        if self.returns == AstType::Void {
+            if !code_block.always_returns() {
+                entry.terminate(QIRTerminator::Ret { value: None });
+            }
+        } else if code_block.tail.is_none() {
+            // Every path returns through a `return` or the tails of an `if` in tail position.
+            entry.terminate(QIRTerminator::Unreachable);
+        }
+        QIRHeader::Function {
+            name,
+            parameters,
+            returns: self.returns.to_qir_type(),
+            locals: self.locals,
+            blocks: entry.into_blocks(),
+        }
+    }
+
+    /// Lowers the statements of a block into `out`, stopping after the first one that
+    /// always returns.
+    pub(crate) fn lower_block(
+        &mut self,
+        block: &AstCodeBlock,
+        tail: Tail,
+        out: &mut Code,
+    ) {
+        let statement_count = block.statements.len();
+        for (index, stmt) in block.statements.iter().enumerate() {
+            let is_last = index + 1 == statement_count && block.tail.is_none();
+            self.lower_statement(stmt, matches!(tail, Tail::Return) && is_last, out);
+            if stmt.always_returns() {
+                return;
+            }
+        }
+
+        let Some(value) = &block.tail else {
+            return;
+        };
+        let lowered = self.lower_expression(value, out);
+        match tail {
+            _ if value.always_returns() => out.push(lowered),
+            Tail::Return if self.returns != AstType::Void => out.terminate(QIRTerminator::Ret {
+                value: Some(lowered),
+            }),
+            Tail::Store(result) => out.push(self.store_local(result, lowered)),
+            Tail::Return | Tail::Discard => out.push(lowered),
+        }
+    }
+
+    /// Lowers a block that is nested in the current one, like the branches of an `if`.
+    pub(crate) fn lower_nested(&mut self, block: &AstCodeBlock, tail: Tail) -> Code {
+        let mut out = Code::default();
+        self.lower_block(block, tail, &mut out);
+        out
+    }
+
+    fn lower_statement(&mut self, stmt: &AstStatement, in_tail: bool, out: &mut Code) {
+        match stmt {
+            AstStatement::Comment(_) => {}
+            AstStatement::Expression(expr) => {
+                let value = self.lower_expression(expr, out);
+                out.push(value);
+            }
+            AstStatement::DeclareVariable { value, local, .. }
+            | AstStatement::ModifyVariable { value, local, .. } => {
+                let value = self.lower_expression(value, out);
+                out.push(self.store_local(expect_local(local), value));
+            }
+            AstStatement::IfStatement {
+                cond,
+                if_true,
+                if_false,
+            } => {
+                let condition = self.lower_expression(cond, out);
+                let tail = if in_tail { Tail::Return } else { Tail::Discard };
+                let if_true = self.lower_nested(if_true, tail);
+                let if_false = self.lower_nested(if_false, tail);
+                self.branch(condition, if_true, if_false, out);
+            }
+            AstStatement::WhileStatement { cond, do_true } => {
+                let mut condition = Code::default();
+                let keep_going = self.lower_expression(cond, &mut condition);
+                let body = self.lower_nested(do_true, Tail::Discard);
+                self.repeat(condition, keep_going, body, out);
+            }
+            AstStatement::ForEach {
+                iterable,
+                do_true,
+                ty,
+                token,
+                local,
+                source,
+                index,
+                ..
+            } => {
+                let element = ty.get().expect("foreach loops are typechecked");
+                let local = expect_local(local);
+                let source = expect_local(source);
+                match iterable {
+                    AstIterable::Range {
+                        start,
+                        end,
+                        inclusive,
+                    } => {
+                        let index = expect_local(index);
+                        let [start, end] = self.lower_operands([start, end], out);
+                        out.push(self.store_local(index, start));
+                        out.push(self.store_local(source, end));
+                        let comparison = if *inclusive { "le" } else { "lt" };
+                        let condition = self.intrinsic(
+                            element,
+                            comparison,
+                            vec![self.load_local(index), self.load_local(source)],
+                            QIRType::Bool,
+                            token,
+                        );
+                        let counting = CountingLoop {
+                            condition,
+                            first: self.store_local(local, self.load_local(index)),
+                            index,
+                            index_ty: element.clone(),
+                            last: inclusive.then(|| self.load_local(source)),
+                        };
+                        self.lower_counting_loop(counting, do_true, token, out);
+                    }
+                    AstIterable::Value { value, next: None } => {
+                        let index = expect_local(index);
+                        let array = self.lower_expression(value, out);
+                        out.push(self.store_local(source, array));
+                        out.push(self.store_local(
+                            index,
+                            QIRExpression::Integer {
+                                value: 0,
+                                ty: QIRType::USize,
+                            },
+                        ));
+                        let length = self.call(
+                            "qre::array::len",
+                            vec![self.load_local(source)],
+                            QIRType::USize,
+                            token,
+                        );
+                        let condition = self.intrinsic(
+                            &AstType::USize,
+                            "lt",
+                            vec![self.load_local(index), length],
+                            QIRType::Bool,
+                            token,
+                        );
+                        let element_value = self.array_get(
+                            self.load_local(source),
+                            self.load_local(index),
+                            element,
+                            token,
+                        );
+                        let counting = CountingLoop {
+                            condition,
+                            first: self.store_local(local, element_value),
+                            index,
+                            index_ty: AstType::USize,
+                            last: None,
+                        };
+                        self.lower_counting_loop(counting, do_true, token, out);
+                    }
+                    AstIterable::Value {
+                        value,
+                        next: Some(next),
+                    } => {
+                        let iterator = self.lower_expression(value, out);
+                        out.push(self.store_local(source, iterator));
+
+                        // `next` returns `T | none`, the loop runs until it holds `none`.
+                        let next_ty = next.get_type();
+                        let item = self.temp(next_ty.to_qir_type());
+                        let mut condition = Code::default();
+                        let next_value = self.lower_expression(next, &mut condition);
+                        condition.push(self.store_local(item, next_value));
+                        let none_id = self.info.type_id(&AstType::None);
+                        let item_id = self.type_id_of(self.load_local(item));
+                        let keep_going = self.intrinsic(
+                            &AstType::UInt32,
+                            "ne",
+                            vec![
+                                item_id,
+                                QIRExpression::Integer {
+                                    value: none_id as i128,
+                                    ty: QIRType::UInt32,
+                                },
+                            ],
+                            QIRType::Bool,
+                            token,
+                        );
+
+                        let element_value = self.narrow(self.load_local(item), &next_ty, element);
+                        let mut body = Code::default();
+                        body.push(self.store_local(local, element_value));
+                        self.lower_block(do_true, Tail::Discard, &mut body);
+                        self.repeat(condition, keep_going, body, out);
+                    }
+                }
+            }
+            AstStatement::AssignIndex {
+                base,
+                index,
+                value,
+                token,
+                ..
+            } => {
+                let [array, position, value] = self.lower_operands([base, index, value], out);
+                let position = self.convert(position, &index.get_type(), &AstType::USize, token, out);
+                let location = self.string(bounds_check_message(&token.span));
+                out.push(self.call(
+                    "qre::array::set",
+                    vec![array, position, value, location],
+                    QIRType::Void,
+                    token,
+                ));
+            }
+            AstStatement::AssignField {
+                object,
+                field,
+                value,
+                ..
+            } => {
+                let AstType::Structure(struct_name) = object.get_type() else {
+                    unreachable!("only structures have fields");
+                };
+                let [object, value] = self.lower_operands([object, value], out);
+                let layout = self.info.struct_layout(&struct_name);
+                let field = self
+                    .info
+                    .field_index(&struct_name, field)
+                    .expect("fields are checked by the typechecker");
+                out.push(store_field(object, layout, field, value));
+            }
+            AstStatement::Return { value, .. } => {
+                let value = value.as_ref().map(|value| self.lower_expression(value, out));
+                out.terminate(QIRTerminator::Ret { value });
+            }
+        }
+    }
+
+    /// Builds a loop that stores the first value into the loop variable, runs the body
+    /// and counts the index up by one for as long as the condition holds.
+    fn lower_counting_loop(
+        &mut self,
+        counting: CountingLoop,
+        do_true: &AstCodeBlock,
+        token: &Token,
+        out: &mut Code,
+    ) {
+        let CountingLoop {
+            condition,
+            first,
+            index,
+            index_ty,
+            last,
+        } = counting;
+        let mut body = Code::default();
+        body.push(first);
+        self.lower_block(do_true, Tail::Discard, &mut body);
+        if do_true.always_returns() {
+            self.repeat(Code::default(), condition, body, out);
+            return;
+        }
+        let one = QIRExpression::Integer {
+            value: 1,
+            ty: index_ty.to_qir_type(),
+        };
+        let next = self.intrinsic(
+            &index_ty,
+            "add",
+            vec![self.load_local(index), one],
+            index_ty.to_qir_type(),
+            token,
+        );
+        let step = self.store_local(index, next);
+        let Some(last) = last else {
+            body.push(step);
+            self.repeat(Code::default(), condition, body, out);
+            return;
+        };
+
+        // Like `repeat`, but leaving the loop after the body when the index is the last one.
+        let is_last = self.intrinsic(
+            &index_ty,
+            "eq",
+            vec![self.load_local(index), last],
+            QIRType::Bool,
+            token,
+        );
+        let (header, body_label, step_label, exit) =
+            (self.label(), self.label(), self.label(), self.label());
+        out.terminate(QIRTerminator::Br { target: header });
+        out.start(header);
+        out.terminate(QIRTerminator::CondBr {
+            condition,
+            if_true: body_label,
+            if_false: exit,
+        });
+        out.start(body_label);
+        out.extend(body);
+        out.terminate(QIRTerminator::CondBr {
+            condition: is_last,
+            if_true: exit,
+            if_false: step_label,
+        });
+        out.start(step_label);
+        out.push(step);
+        out.terminate(QIRTerminator::Br { target: header });
+        out.start(exit);
+    }
+
+    /// Gives the next block its label.
+    pub(crate) fn label(&mut self) -> Label {
+        self.labels += 1;
+        self.labels
+    }
+
+    /// Runs `if_true` when `condition` holds and `if_false` otherwise, and continues
+    /// in a block both branch to.
+    pub(crate) fn branch(&mut self, condition: QIRExpression, if_true: Code, if_false: Code, out: &mut Code) {
+        let join = self.label();
+        // An empty branch goes straight to the join.
+        let then_label = if if_true.is_empty() { join } else { self.label() };
+        let else_label = if if_false.is_empty() { join } else { self.label() };
+        out.terminate(QIRTerminator::CondBr {
+            condition,
+            if_true: then_label,
+            if_false: else_label,
+        });
+        for (label, code) in [(then_label, if_true), (else_label, if_false)] {
+            if !code.is_empty() {
+                out.start(label);
+                out.extend(code);
+                out.terminate(QIRTerminator::Br { target: join });
+            }
+        }
+        out.start(join);
+    }
+
+    /// Runs `body` for as long as `keep_going` yields true, computed after `condition`
+    /// each time around.
+    pub(crate) fn repeat(&mut self, condition: Code, keep_going: QIRExpression, body: Code, out: &mut Code) {
+        let (header, body_label, exit) = (self.label(), self.label(), self.label());
+        out.terminate(QIRTerminator::Br { target: header });
+        out.start(header);
+        out.extend(condition);
+        out.terminate(QIRTerminator::CondBr {
+            condition: keep_going,
+            if_true: body_label,
+            if_false: exit,
+        });
+        out.start(body_label);
+        out.extend(body);
+        out.terminate(QIRTerminator::Br { target: header });
+        out.start(exit);
+    }
+
+    /// Adds a local that only exists in QIR, returning its ID.
+    pub(crate) fn temp(&mut self, ty: QIRType) -> usize {
+        self.locals.push(ty);
+        self.locals.len() - 1
+    }
+
+    /// Stores `value` to a new temporary, so it can be read more than once.
+    pub(crate) fn spill(
+        &mut self,
+        value: QIRExpression,
+        ty: QIRType,
+        out: &mut Code,
+    ) -> usize {
+        let local = self.temp(ty);
+        out.push(self.store_local(local, value));
+        local
+    }
+
+    pub(crate) fn load_local(&self, local: usize) -> QIRExpression {
+        QIRExpression::LoadFromPtr {
+            ptr: Box::new(QIRExpression::GetLocalPtr {
+                local: QIRLocalVariable { id: local },
+            }),
+            output_type: self.locals[local].clone(),
+        }
+    }
+
+    pub(crate) fn store_local(&self, local: usize, value: QIRExpression) -> QIRExpression {
+        QIRExpression::StoreToPtr {
+            receiver: Box::new(QIRExpression::GetLocalPtr {
+                local: QIRLocalVariable { id: local },
+            }),
+            output_type: self.locals[local].clone(),
+            new_value: Box::new(value),
+        }
+    }
+
+    /// Calls a function or intrinsic by name.
+    pub(crate) fn call(
+        &self,
+        name: &str,
+        arguments: Vec<QIRExpression>,
+        return_type: QIRType,
+        token: &Token,
+    ) -> QIRExpression {
+        QIRExpression::Invoke {
+            name: PathData {
+                name: name.to_string(),
+                token: token.clone(),
+            },
+            arguments,
+            return_type,
+        }
+    }
+
+    /// Calls the intrinsic implementing `operation` for values of `ty`, like `qre::i32::add`.
+    pub(crate) fn intrinsic(
+        &self,
+        ty: &AstType,
+        operation: &str,
+        arguments: Vec<QIRExpression>,
+        return_type: QIRType,
+        token: &Token,
+    ) -> QIRExpression {
+        let type_name = match ty {
+            AstType::Structure(name) if name == "std::string" => "string".to_string(),
+            other => other.source_name(),
+        };
+        self.call(
+            &format!("qre::{}::{}", type_name, operation),
+            arguments,
+            return_type,
+            token,
+        )
+    }
+
+    pub(crate) fn string(&mut self, value: String) -> QIRExpression {
+        let type_id = self
+            .info
+            .type_id(&AstType::Structure("std::string".to_string()));
+        QIRExpression::String { value, type_id }
+    }
+
+    /// Reads the element at `index` of an array, panicking if it is out of bounds.
+    pub(crate) fn array_get(
+        &mut self,
+        array: QIRExpression,
+        index: QIRExpression,
+        element: &AstType,
+        token: &Token,
+    ) -> QIRExpression {
+        let location = self.string(bounds_check_message(&token.span));
+        self.call(
+            "qre::array::get",
+            vec![array, index, location],
+            element.to_qir_type(),
+            token,
+        )
+    }
+
+    pub(crate) fn panic(&mut self, message: String, token: &Token) -> QIRExpression {
+        let message = self.string(message);
+        self.call("qre::panic", vec![message], QIRType::Void, token)
+    }
+}
+
+pub(crate) fn expect_local(local: &OnceCell<usize>) -> usize {
+    *local.get().expect("locals are resolved by the typechecker")
+}
+
+/// Lowered code that is still being put together: expressions, and the terminators
+/// and labels that split them into blocks, in the order they run.
+///
+/// The parts of a function are lowered on their own and joined, so a block can start
+/// in one part and end in another. `into_blocks` splits the code of a whole function.
+#[derive(Default)]
+pub(crate) struct Code {
+    items: Vec<Item>,
+}
+
+enum Item {
+    Expression(QIRExpression),
+    Terminator(QIRTerminator),
+    Label(Label),
+}
+
+impl Code {
+    pub(crate) fn push(&mut self, expr: QIRExpression) {
+        self.items.push(Item::Expression(expr));
+    }
+
+    pub(crate) fn extend(&mut self, code: Code) {
+        self.items.extend(code.items);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Ends the current block.
+    pub(crate) fn terminate(&mut self, terminator: QIRTerminator) {
+        self.items.push(Item::Terminator(terminator));
+    }
+
+    /// Starts the block labelled `label`, which a block that didn't end falls through to.
+    pub(crate) fn start(&mut self, label: Label) {
+        self.items.push(Item::Label(label));
+    }
+
+    /// Splits the code of a function into its blocks, in the order they start, and
+    /// labels them by their index.
+    ///
+    /// Code after a terminator that no label starts, like the statements after a
+    /// `return`, can't run, and is left out with the blocks that only it leads to.
+    pub(crate) fn into_blocks(self) -> Vec<QIRBasicBlock> {
+        let mut started = vec![(Some(0), vec![], None)];
+        for item in self.items {
+            let (_, exprs, terminator) = started.last_mut().expect("there is an entry block");
+            match item {
+                Item::Expression(expr) if terminator.is_some() => started.push((None, vec![expr], None)),
+                Item::Expression(expr) => exprs.push(expr),
+                Item::Terminator(next) if terminator.is_some() => started.push((None, vec![], Some(next))),
+                Item::Terminator(next) => *terminator = Some(next),
+                Item::Label(label) => {
+                    terminator.get_or_insert(QIRTerminator::Br { target: label });
+                    started.push((Some(label), vec![], None));
+                }
+            }
+        }
+
+        let positions = started
+            .iter()
+            .enumerate()
+            .filter_map(|(position, (label, ..))| label.map(|label| (label, position)))
+            .collect::<HashMap<_, _>>();
+        let mut blocks = started
+            .into_iter()
+            .map(|(_, exprs, terminator)| {
+                let mut terminator = terminator.unwrap_or(QIRTerminator::Unreachable);
+                for target in terminator.successors_mut() {
+                    *target = positions[target];
+                }
+                QIRBasicBlock { exprs, terminator }
+            })
+            .collect::<Vec<_>>();
+
+        let mut kept = reachable_from(&blocks, 0);
+        kept[0] = true;
+        let mut labels = vec![0; blocks.len()];
+        let mut next = 0;
+        for (position, kept) in kept.iter().enumerate() {
+            labels[position] = next;
+            next += usize::from(*kept);
+        }
+        let mut kept = kept.into_iter();
+        blocks.retain(|_| kept.next().unwrap_or(false));
+        for block in &mut blocks {
+            for target in block.terminator.successors_mut() {
+                *target = labels[*target];
+            }
+        }
+        blocks
+    }
+}
+
+impl FromIterator<QIRExpression> for Code {
+    fn from_iter<T: IntoIterator<Item = QIRExpression>>(exprs: T) -> Code {
+        Code {
+            items: exprs.into_iter().map(Item::Expression).collect(),
+        }
+    }
+}
+
+pub(crate) fn field_ptr(receiver: QIRExpression, ptr_type: Vec<QIRType>, field: i32) -> QIRExpression {
+    let output_type = ptr_type[field as usize].clone();
+    QIRExpression::GetFieldPtr {
+        receiver: Box::new(receiver),
+        ptr_type,
+        output_type,
+        field,
+    }
+}
+
+pub(crate) fn load_field(receiver: QIRExpression, ptr_type: Vec<QIRType>, field: i32) -> QIRExpression {
+    let output_type = ptr_type[field as usize].clone();
+    QIRExpression::LoadFromPtr {
+        ptr: Box::new(field_ptr(receiver, ptr_type, field)),
+        output_type,
+    }
+}
+
+pub(crate) fn store_field(
+    receiver: QIRExpression,
+    ptr_type: Vec<QIRType>,
+    field: i32,
+    value: QIRExpression,
+) -> QIRExpression {
+    let output_type = ptr_type[field as usize].clone();
+    QIRExpression::StoreToPtr {
+        receiver: Box::new(field_ptr(receiver, ptr_type, field)),
+        output_type,
+        new_value: Box::new(value),
+    }
+}
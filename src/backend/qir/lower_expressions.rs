@@ -0,0 +1,826 @@
+use crate::backend::qir::arrays::bounds_check_message;
+use crate::backend::qir::layout::{
+    object_fields, BOX_VALUE_FIELD, CLOSURE_DROP_FIELD, CLOSURE_FUNCTION_FIELD,
+    ENUM_PAYLOAD_FIELD, ENUM_TAG_FIELD, REFCOUNT_FIELD, SHARED_FLAG, TYPE_ID_FIELD,
+};
+use crate::backend::qir::destructors::{drop_function, lower_closure_drop};
+use crate::backend::qir::lower::{load_field, store_field, Code, FunctionLowering};
+use crate::backend::qir::structs::{
+    QIRBasicBlock, QIRExpression, QIRHeader, QIRLocalVariable, QIRTerminator, QIRType,
+};
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{
+    AstCapture, AstCodeBlock, AstExpression, AstLocal, AstType, PathData,
+};
+use crate::frontend::typecheck::data::ProgramType;
+use std::cell::OnceCell;
+
+impl FunctionLowering<'_> {
+    /// Lowers an expression to the QIR expression computing its value. Anything that has
+    /// to run before it, like the branches of a `match`, is pushed to `out`.
+    pub(crate) fn lower_expression(
+        &mut self,
+        expr: &AstExpression,
+        out: &mut Code,
+    ) -> QIRExpression {
+        match expr {
+            AstExpression::NumberLiteral { content, ty, .. } => {
+                let ty = ty.get().expect("literals are typechecked");
+                if ty.is_float() {
+                    QIRExpression::Float {
+                        value: content.parse().unwrap_or_default(),
+                        ty: ty.to_qir_type(),
+                    }
+                } else {
+                    QIRExpression::Integer {
+                        value: content.parse().unwrap_or_default(),
+                        ty: ty.to_qir_type(),
+                    }
+                }
+            }
+            AstExpression::StringLiteral { content, .. } => self.string(content.clone()),
+            AstExpression::BooleanLiteral { value, .. } => QIRExpression::Bool { value: *value },
+            AstExpression::NoneLiteral { .. } => QIRExpression::Null,
+            AstExpression::VariableLiteral {
+                content,
+                ty,
+                token,
+                local,
+            } => {
+                let ty = expr_type(ty);
+                match local.get() {
+                    Some(local) => {
+                        let declared = self.ast_locals[*local].clone();
+                        self.narrow(self.load_local(*local), &declared, &ty)
+                    }
+                    None => self.lower_function_value(content, &ty, token, out),
+                }
+            }
+            AstExpression::PathLiteral(_) | AstExpression::TypeLiteral { .. } => {
+                unreachable!("paths and types are rejected as values by the typechecker")
+            }
+            AstExpression::ArrayLiteral {
+                content,
+                ty,
+                open_bracket_tok,
+                ..
+            } => {
+                let ty = expr_type(ty);
+                let elements = content.iter().collect::<Vec<_>>();
+                let values = self.lower_sequence(&elements, out);
+                let type_id = self.info.type_id(&ty);
+                let AstType::ArrayOf(element) = &ty else {
+                    unreachable!("array literals have array types");
+                };
+                let new = self.call(
+                    "qre::array::new",
+                    vec![
+                        QIRExpression::Integer {
+                            value: type_id as i128,
+                            ty: QIRType::UInt32,
+                        },
+                        QIRExpression::Integer {
+                            value: values.len() as i128,
+                            ty: QIRType::USize,
+                        },
+                        QIRExpression::Bool {
+                            value: element.to_qir_type().is_refcounted(),
+                        },
+                    ],
+                    QIRType::Ptr,
+                    open_bracket_tok,
+                );
+                let array = self.spill(new, QIRType::Ptr, out);
+                for value in values {
+                    out.push(self.call(
+                        "qre::array::push",
+                        vec![self.load_local(array), value],
+                        QIRType::Void,
+                        open_bracket_tok,
+                    ));
+                }
+                self.load_local(array)
+            }
+            AstExpression::StructureLiteral { ty, fields, .. } => {
+                let AstType::Structure(struct_name) = ty else {
+                    unreachable!("structure literals are resolved to structs by the typechecker");
+                };
+                let values = fields.iter().map(|(_, value)| value).collect::<Vec<_>>();
+                let values = self.lower_sequence(&values, out);
+                let layout = self.info.struct_layout(struct_name);
+                let object = self.instantiate(layout.clone(), ty, out);
+                for ((field, _), value) in fields.iter().zip(values) {
+                    let field = self
+                        .info
+                        .field_index(struct_name, field)
+                        .expect("fields are checked by the typechecker");
+                    out.push(store_field(self.load_local(object), layout.clone(), field, value));
+                }
+                self.load_local(object)
+            }
+            AstExpression::Lambda {
+                parameters,
+                returns,
+                code_block,
+                locals,
+                captures,
+                ty,
+                token,
+            } => self.lower_lambda(
+                parameters,
+                returns,
+                code_block,
+                locals,
+                captures,
+                &expr_type(ty),
+                token,
+                out,
+            ),
+            AstExpression::EnumLiteral {
+                ty,
+                variant,
+                arguments,
+                ..
+            } => self.lower_enum_literal(ty, variant, arguments, out),
+            AstExpression::Add { lhs, rhs, op_tok, .. } => self.lower_arithmetic(lhs, rhs, "add", op_tok, out),
+            AstExpression::Sub { lhs, rhs, op_tok, .. } => self.lower_arithmetic(lhs, rhs, "sub", op_tok, out),
+            AstExpression::Mul { lhs, rhs, op_tok, .. } => self.lower_arithmetic(lhs, rhs, "mul", op_tok, out),
+            AstExpression::Div { lhs, rhs, op_tok, .. } => self.lower_arithmetic(lhs, rhs, "div", op_tok, out),
+            AstExpression::Mod { lhs, rhs, op_tok, .. } => self.lower_arithmetic(lhs, rhs, "rem", op_tok, out),
+            AstExpression::ShiftLeft { lhs, rhs, op_tok, .. } => self.lower_shift(lhs, rhs, "shl", op_tok, out),
+            AstExpression::ShiftRight { lhs, rhs, op_tok, .. } => self.lower_shift(lhs, rhs, "shr", op_tok, out),
+            AstExpression::Equal { lhs, rhs, op_tok, .. } => self.lower_comparison(lhs, rhs, "eq", op_tok, out),
+            AstExpression::NotEqual { lhs, rhs, op_tok, .. } => self.lower_comparison(lhs, rhs, "ne", op_tok, out),
+            AstExpression::LessThan { lhs, rhs, op_tok, .. } => self.lower_comparison(lhs, rhs, "lt", op_tok, out),
+            AstExpression::GreaterThan { lhs, rhs, op_tok, .. } => self.lower_comparison(lhs, rhs, "gt", op_tok, out),
+            AstExpression::LessThanOrEqual { lhs, rhs, op_tok, .. } => self.lower_comparison(lhs, rhs, "le", op_tok, out),
+            AstExpression::GreaterThanOrEqual { lhs, rhs, op_tok, .. } => self.lower_comparison(lhs, rhs, "ge", op_tok, out),
+            AstExpression::Cast {
+                value,
+                target,
+                token,
+                ..
+            } => {
+                let lowered = self.lower_expression(value, out);
+                self.convert(lowered, &value.get_type(), target, token, out)
+            }
+            AstExpression::Is {
+                value,
+                target,
+                token,
+                ..
+            } => {
+                let lowered = self.lower_expression(value, out);
+                let value = self.spill(lowered, QIRType::Union, out);
+                self.type_test(value, target, token)
+            }
+            AstExpression::Match { value, arms, ty, .. } => {
+                self.lower_match(value, arms, &expr_type(ty), out)
+            }
+            AstExpression::Propagate { value, ty, token } => {
+                let value_ty = value.get_type();
+                let lowered = self.lower_expression(value, out);
+                let union = self.spill(lowered, QIRType::Union, out);
+
+                let errors = value_ty
+                    .union_members()
+                    .into_iter()
+                    .filter(|member| self.info.instance_named("Err", member).is_some())
+                    .collect::<Vec<_>>();
+                let is_error = self.type_test(union, &AstType::union(errors), token);
+                // `Err`s are structs, so the union points to the same object the
+                // function returns.
+                let mut returned = Code::default();
+                returned.terminate(QIRTerminator::Ret {
+                    value: Some(self.load_local(union)),
+                });
+                self.branch(is_error, returned, Code::default(), out);
+                self.narrow(self.load_local(union), &value_ty, &expr_type(ty))
+            }
+            AstExpression::FieldAccess { value, field, .. } => {
+                let AstType::Structure(struct_name) = value.get_type() else {
+                    unreachable!("only structures have fields");
+                };
+                let object = self.lower_expression(value, out);
+                let layout = self.info.struct_layout(&struct_name);
+                let field = self
+                    .info
+                    .field_index(&struct_name, field)
+                    .expect("fields are checked by the typechecker");
+                load_field(object, layout, field)
+            }
+            AstExpression::Invoke {
+                receiver,
+                arguments,
+                return_type,
+                open_paren_span,
+                ..
+            } => self.lower_invoke(receiver, arguments, &expr_type(return_type), open_paren_span, out),
+            AstExpression::Index {
+                ty,
+                base,
+                other,
+                token,
+            } => {
+                let [array, index] = self.lower_operands([base, other], out);
+                let index = self.convert(index, &other.get_type(), &AstType::USize, token, out);
+                self.array_get(array, index, &expr_type(ty), token)
+            }
+        }
+    }
+
+    /// Lowers values that are evaluated from left to right.
+    ///
+    /// A value is stored to a temporary when a later one pushes expressions to `out`,
+    /// so it is still computed before them.
+    pub(crate) fn lower_sequence(
+        &mut self,
+        exprs: &[&AstExpression],
+        out: &mut Code,
+    ) -> Vec<QIRExpression> {
+        let mut lowered = exprs
+            .iter()
+            .map(|expr| {
+                let mut code = Code::default();
+                let value = self.lower_expression(expr, &mut code);
+                (code, value, expr.get_type())
+            })
+            .collect::<Vec<_>>();
+
+        let mut later_code = false;
+        for (code, value, ty) in lowered.iter_mut().rev() {
+            if later_code {
+                let computed = std::mem::replace(value, QIRExpression::Null);
+                let local = self.spill(computed, ty.to_qir_type(), code);
+                *value = self.load_local(local);
+            }
+            later_code |= !code.is_empty();
+        }
+
+        lowered
+            .into_iter()
+            .map(|(code, value, _)| {
+                out.extend(code);
+                value
+            })
+            .collect()
+    }
+
+    pub(crate) fn lower_operands<const N: usize>(
+        &mut self,
+        exprs: [&AstExpression; N],
+        out: &mut Code,
+    ) -> [QIRExpression; N] {
+        match self.lower_sequence(&exprs, out).try_into() {
+            Ok(values) => values,
+            Err(_) => unreachable!("every operand is lowered to one value"),
+        }
+    }
+
+    fn lower_arithmetic(
+        &mut self,
+        lhs: &AstExpression,
+        rhs: &AstExpression,
+        operation: &str,
+        token: &Token,
+        out: &mut Code,
+    ) -> QIRExpression {
+        let ty = lhs.get_type();
+        let operands = self.lower_operands([lhs, rhs], out);
+        self.intrinsic(&ty, operation, operands.into(), ty.to_qir_type(), token)
+    }
+
+    fn lower_comparison(
+        &mut self,
+        lhs: &AstExpression,
+        rhs: &AstExpression,
+        operation: &str,
+        token: &Token,
+        out: &mut Code,
+    ) -> QIRExpression {
+        let ty = lhs.get_type();
+        let operands = self.lower_operands([lhs, rhs], out);
+        self.intrinsic(&ty, operation, operands.into(), QIRType::Bool, token)
+    }
+
+    /// Shifts take the amount in the type of the shifted value.
+    fn lower_shift(
+        &mut self,
+        lhs: &AstExpression,
+        rhs: &AstExpression,
+        operation: &str,
+        token: &Token,
+        out: &mut Code,
+    ) -> QIRExpression {
+        let ty = lhs.get_type();
+        let [value, amount] = self.lower_operands([lhs, rhs], out);
+        let amount = self.convert(amount, &rhs.get_type(), &ty, token, out);
+        self.intrinsic(&ty, operation, vec![value, amount], ty.to_qir_type(), token)
+    }
+
+    fn lower_invoke(
+        &mut self,
+        receiver: &AstExpression,
+        arguments: &[AstExpression],
+        returns: &AstType,
+        token: &Token,
+        out: &mut Code,
+    ) -> QIRExpression {
+        let AstExpression::PathLiteral(path) = receiver else {
+            let mut operands = vec![receiver];
+            operands.extend(arguments);
+            let mut values = self.lower_sequence(&operands, out);
+            let closure = values.remove(0);
+            return QIRExpression::InvokeClosure {
+                closure: Box::new(closure),
+                arguments: values,
+                return_type: returns.to_qir_type(),
+            };
+        };
+        if path.name == "qre::array::pop" {
+            return self.lower_array_pop(&arguments[0], returns, token, out);
+        }
+        let arguments = arguments.iter().collect::<Vec<_>>();
+        let mut arguments = self.lower_sequence(&arguments, out);
+        if path.name == "qre::weak::get" {
+            // The runtime boxes `none` itself when the object is gone.
+            arguments.push(QIRExpression::Integer {
+                value: self.info.type_id(&AstType::None) as i128,
+                ty: QIRType::UInt32,
+            });
+        }
+        if path.name == "qre::array::insert" || path.name == "qre::array::remove" {
+            arguments.push(self.string(bounds_check_message(&token.span)));
+        }
+        self.call_function(path, arguments, returns.to_qir_type())
+    }
+
+    /// Calls a named function, dispatching on the type ID of `self` for interface methods.
+    fn call_function(
+        &self,
+        path: &PathData,
+        arguments: Vec<QIRExpression>,
+        return_type: QIRType,
+    ) -> QIRExpression {
+        if let Some((interface, method)) = path.name.rsplit_once("::")
+            && let Some(ProgramType::Interface { methods, .. }) = self.info.names.get(interface)
+            && let Some(index) = methods.iter().position(|declared| declared == method)
+        {
+            return QIRExpression::InvokeDynamic {
+                interface: PathData {
+                    name: interface.to_string(),
+                    token: path.token.clone(),
+                },
+                method: index as u32,
+                arguments,
+                return_type,
+            };
+        }
+        QIRExpression::Invoke {
+            name: path.clone(),
+            arguments,
+            return_type,
+        }
+    }
+
+    /// `pop` yields `none` for empty arrays, so it only takes an element out of
+    /// arrays it checked the length of.
+    fn lower_array_pop(
+        &mut self,
+        array: &AstExpression,
+        returns: &AstType,
+        token: &Token,
+        out: &mut Code,
+    ) -> QIRExpression {
+        let AstType::ArrayOf(element) = array.get_type() else {
+            unreachable!("array methods are only resolved for arrays");
+        };
+        let lowered = self.lower_expression(array, out);
+        let array = self.spill(lowered, QIRType::Ptr, out);
+        let result = self.temp(returns.to_qir_type());
+
+        let length = self.call(
+            "qre::array::len",
+            vec![self.load_local(array)],
+            QIRType::USize,
+            token,
+        );
+        let is_empty = self.intrinsic(
+            &AstType::USize,
+            "eq",
+            vec![
+                length,
+                QIRExpression::Integer {
+                    value: 0,
+                    ty: QIRType::USize,
+                },
+            ],
+            QIRType::Bool,
+            token,
+        );
+
+        let mut empty = Code::default();
+        let none = self.convert(QIRExpression::Null, &AstType::None, returns, token, &mut empty);
+        empty.push(self.store_local(result, none));
+
+        let mut popped = Code::default();
+        let value = self.call(
+            "qre::array::pop_unchecked",
+            vec![self.load_local(array)],
+            element.to_qir_type(),
+            token,
+        );
+        let value = self.convert(value, &element, returns, token, &mut popped);
+        popped.push(self.store_local(result, value));
+
+        self.branch(is_empty, empty, popped, out);
+        self.load_local(result)
+    }
+
+    /// A named function used as a value, which becomes a closure object without
+    /// captures that calls the function through a thunk.
+    fn lower_function_value(
+        &mut self,
+        name: &str,
+        ty: &AstType,
+        token: &Token,
+        out: &mut Code,
+    ) -> QIRExpression {
+        let AstType::Function(parameters, returns) = ty else {
+            unreachable!("functions used as values have function types");
+        };
+        let thunk = PathData {
+            name: format!("{}::<thunk>", name),
+            token: token.clone(),
+        };
+        if self.program.thunks.insert(name.to_string()) {
+            // The thunk takes the closure object first, like every function called through one.
+            let locals = std::iter::once(QIRType::Ptr)
+                .chain(parameters.iter().map(|parameter| parameter.to_qir_type()))
+                .collect::<Vec<_>>();
+            let arguments = (1..locals.len())
+                .map(|id| QIRExpression::LoadFromPtr {
+                    ptr: Box::new(QIRExpression::GetLocalPtr {
+                        local: QIRLocalVariable { id },
+                    }),
+                    output_type: locals[id].clone(),
+                })
+                .collect();
+            let function = PathData {
+                name: name.to_string(),
+                token: token.clone(),
+            };
+            let call = self.call_function(&function, arguments, returns.to_qir_type());
+            let block = if **returns == AstType::Void {
+                QIRBasicBlock {
+                    exprs: vec![call],
+                    terminator: QIRTerminator::Ret { value: None },
+                }
+            } else {
+                QIRBasicBlock {
+                    exprs: vec![],
+                    terminator: QIRTerminator::Ret { value: Some(call) },
+                }
+            };
+            self.program.functions.push(QIRHeader::Function {
+                name: thunk.clone(),
+                parameters: locals
+                    .iter()
+                    .enumerate()
+                    .map(|(id, ty)| (QIRLocalVariable { id }, ty.clone()))
+                    .collect(),
+                returns: returns.to_qir_type(),
+                locals,
+                blocks: vec![block],
+            });
+        }
+
+        let layout = object_fields([QIRType::Ptr, QIRType::Ptr]);
+        let object = self.instantiate(layout.clone(), ty, out);
+        out.push(store_field(
+            self.load_local(object),
+            layout.clone(),
+            CLOSURE_FUNCTION_FIELD,
+            QIRExpression::GetFunctionPtr { function: thunk },
+        ));
+        out.push(store_field(
+            self.load_local(object),
+            layout,
+            CLOSURE_DROP_FIELD,
+            QIRExpression::Null,
+        ));
+        self.load_local(object)
+    }
+
+    /// Lowers the body of a lambda to a function of its own, and creates the closure
+    /// object holding a pointer to it and the captured values.
+    fn lower_lambda(
+        &mut self,
+        parameters: &[(AstType, PathData)],
+        returns: &AstType,
+        code_block: &AstCodeBlock,
+        locals: &[AstLocal],
+        captures: &[AstCapture],
+        ty: &AstType,
+        token: &Token,
+        out: &mut Code,
+    ) -> QIRExpression {
+        self.program.lambdas += 1;
+        let name = PathData {
+            name: format!("{}::<lambda {}>", self.name, self.program.lambdas),
+            token: token.clone(),
+        };
+        let layout = object_fields(
+            [QIRType::Ptr, QIRType::Ptr]
+                .into_iter()
+                .chain(captures.iter().map(|capture| locals[capture.inner].ty.to_qir_type())),
+        );
+
+        let mut lambda = FunctionLowering::new(self.info, self.program, &name.name, locals, returns);
+        let closure = lambda.temp(QIRType::Ptr);
+        let entry = captures
+            .iter()
+            .enumerate()
+            .map(|(index, capture)| {
+                let field = CLOSURE_DROP_FIELD + 1 + index as i32;
+                let value = load_field(lambda.load_local(closure), layout.clone(), field);
+                lambda.store_local(capture.inner, value)
+            })
+            .collect();
+        let lambda_parameters = std::iter::once((QIRLocalVariable { id: closure }, QIRType::Ptr))
+            .chain(
+                parameters
+                    .iter()
+                    .enumerate()
+                    .map(|(id, (ty, _))| (QIRLocalVariable { id }, ty.to_qir_type())),
+            )
+            .collect();
+        let function = lambda.lower_body(name.clone(), lambda_parameters, entry, code_block);
+        self.program.functions.push(function);
+        let drop = lower_closure_drop(&name, &layout);
+        self.program.drops.push(drop);
+
+        // Captures take the type the variable had where the lambda was written, which
+        // can be narrower than the type it was declared with.
+        let captured = captures
+            .iter()
+            .map(|capture| {
+                let declared = self.ast_locals[capture.outer].clone();
+                self.narrow(self.load_local(capture.outer), &declared, &locals[capture.inner].ty)
+            })
+            .collect::<Vec<_>>();
+        let object = self.instantiate(layout.clone(), ty, out);
+        out.push(store_field(
+            self.load_local(object),
+            layout.clone(),
+            CLOSURE_FUNCTION_FIELD,
+            QIRExpression::GetFunctionPtr { function: name.clone() },
+        ));
+        out.push(store_field(
+            self.load_local(object),
+            layout.clone(),
+            CLOSURE_DROP_FIELD,
+            QIRExpression::GetFunctionPtr {
+                function: drop_function(&name),
+            },
+        ));
+        for (index, value) in captured.into_iter().enumerate() {
+            let field = CLOSURE_DROP_FIELD + 1 + index as i32;
+            out.push(store_field(self.load_local(object), layout.clone(), field, value));
+        }
+        self.load_local(object)
+    }
+
+    fn lower_enum_literal(
+        &mut self,
+        ty: &AstType,
+        variant: &str,
+        arguments: &[AstExpression],
+        out: &mut Code,
+    ) -> QIRExpression {
+        let (enum_name, variant_name) = variant
+            .rsplit_once("::")
+            .expect("variant paths contain `::`");
+        let Some(ProgramType::Enum { variants, .. }) = self.info.names.get(enum_name) else {
+            unreachable!("enum literals are only resolved for enums");
+        };
+        let tag = variants
+            .iter()
+            .position(|(name, _)| name == variant_name)
+            .expect("variants are checked by the typechecker");
+        let fields = variants[tag].1.clone();
+
+        let arguments = arguments.iter().collect::<Vec<_>>();
+        let values = self.lower_sequence(&arguments, out);
+        let payload = if fields.is_empty() {
+            QIRExpression::Null
+        } else {
+            let layout = object_fields(fields.iter().map(|field| field.to_qir_type()));
+            let payload_ty = AstType::Structure(variant.to_string());
+            let payload = self.instantiate(layout.clone(), &payload_ty, out);
+            for (index, value) in values.into_iter().enumerate() {
+                out.push(store_field(
+                    self.load_local(payload),
+                    layout.clone(),
+                    index as i32 + 2,
+                    value,
+                ));
+            }
+            self.load_local(payload)
+        };
+
+        let layout = object_fields([QIRType::UInt32, QIRType::Ptr]);
+        let object = self.instantiate(layout.clone(), ty, out);
+        out.push(store_field(
+            self.load_local(object),
+            layout.clone(),
+            ENUM_TAG_FIELD,
+            QIRExpression::Integer {
+                value: tag as i128,
+                ty: QIRType::UInt32,
+            },
+        ));
+        out.push(store_field(self.load_local(object), layout, ENUM_PAYLOAD_FIELD, payload));
+        self.load_local(object)
+    }
+
+    /// Allocates an object and stores its type ID and a reference count of 1, returning
+    /// the temporary holding it. Objects of shareable types are marked as shared.
+    pub(crate) fn instantiate(
+        &mut self,
+        layout: Vec<QIRType>,
+        ty: &AstType,
+        out: &mut Code,
+    ) -> usize {
+        let object = self.spill(
+            QIRExpression::InstantiateStructure {
+                ptr_type: layout.clone(),
+            },
+            QIRType::Ptr,
+            out,
+        );
+        let type_id = self.info.type_id(ty);
+        out.push(store_field(
+            self.load_local(object),
+            layout.clone(),
+            TYPE_ID_FIELD,
+            QIRExpression::Integer {
+                value: type_id as i128,
+                ty: QIRType::UInt32,
+            },
+        ));
+        out.push(store_field(
+            self.load_local(object),
+            layout,
+            REFCOUNT_FIELD,
+            QIRExpression::Integer {
+                value: if self.info.is_shareable(ty) { 1 | SHARED_FLAG } else { 1 },
+                ty: QIRType::UInt32,
+            },
+        ));
+        object
+    }
+
+    /// Converts a value of type `from` to `to`, as the typechecker allows with `as` or
+    /// inserts implicitly.
+    ///
+    /// Conversions that can fail, from a union to some of its members or from an
+    /// interface to a struct, panic when the value holds something else.
+    pub(crate) fn convert(
+        &mut self,
+        value: QIRExpression,
+        from: &AstType,
+        to: &AstType,
+        token: &Token,
+        out: &mut Code,
+    ) -> QIRExpression {
+        if from == to {
+            return value;
+        }
+        if from.is_numeric() && to.is_numeric() {
+            let operation = format!("to_{}", to.source_name());
+            return self.intrinsic(from, &operation, vec![value], to.to_qir_type(), token);
+        }
+        let from_union = matches!(from, AstType::UnionOf(..));
+        let to_union = matches!(to, AstType::UnionOf(..));
+        if !from_union && to_union {
+            return self.box_value(value, from, out);
+        }
+        if from.is_subset_of(to) || from.implements(to, self.info) {
+            return value;
+        }
+        if from.weakens_to(to) {
+            let type_id = QIRExpression::Integer {
+                value: self.info.type_id(to) as i128,
+                ty: QIRType::UInt32,
+            };
+            return self.call("qre::weak::new", vec![type_id, value], QIRType::Ptr, token);
+        }
+
+        let checked = self.spill(value, from.to_qir_type(), out);
+        let holds_target = self.type_test(checked, to, token);
+        let message = format!(
+            "can not cast {} to {} at {}:{}:{}",
+            from.source_name(),
+            to.source_name(),
+            token.span.file_name,
+            token.span.row_start,
+            token.span.column_start
+        );
+        let mut failure = Code::default();
+        failure.push(self.panic(message, token));
+        failure.terminate(QIRTerminator::Unreachable);
+        self.branch(holds_target, Code::default(), failure, out);
+        self.narrow(self.load_local(checked), from, to)
+    }
+
+    /// Reads a value of type `from` as the narrower type `to`, which the caller knows it holds.
+    pub(crate) fn narrow(&self, value: QIRExpression, from: &AstType, to: &AstType) -> QIRExpression {
+        if from == to || !matches!(from, AstType::UnionOf(..)) || matches!(to, AstType::UnionOf(..)) {
+            return value;
+        }
+        match to {
+            AstType::None => QIRExpression::Null,
+            member if member.is_heap_object() => value,
+            member => load_field(value, object_fields([member.to_qir_type()]), BOX_VALUE_FIELD),
+        }
+    }
+
+    /// Converts a value to a union it is a member of. Heap objects already carry their
+    /// type ID, anything else is put in a box that records it.
+    fn box_value(
+        &mut self,
+        value: QIRExpression,
+        member: &AstType,
+        out: &mut Code,
+    ) -> QIRExpression {
+        if member.is_heap_object() {
+            return value;
+        }
+        if *member == AstType::None {
+            let object = self.instantiate(object_fields([]), member, out);
+            return self.load_local(object);
+        }
+        let layout = object_fields([member.to_qir_type()]);
+        let object = self.instantiate(layout.clone(), member, out);
+        out.push(store_field(self.load_local(object), layout, BOX_VALUE_FIELD, value));
+        self.load_local(object)
+    }
+
+    pub(crate) fn type_id_of(&self, object: QIRExpression) -> QIRExpression {
+        load_field(object, object_fields([]), TYPE_ID_FIELD)
+    }
+
+    /// Tests whether the object in `local` holds a value of `ty`, or of any of its
+    /// members if it is a union.
+    pub(crate) fn type_test(&mut self, local: usize, ty: &AstType, token: &Token) -> QIRExpression {
+        let type_ids = ty
+            .union_members()
+            .iter()
+            .flat_map(|member| self.member_type_ids(member))
+            .collect::<Vec<_>>();
+        type_ids
+            .into_iter()
+            .map(|type_id| {
+                let actual = self.type_id_of(self.load_local(local));
+                self.intrinsic(
+                    &AstType::UInt32,
+                    "eq",
+                    vec![
+                        actual,
+                        QIRExpression::Integer {
+                            value: type_id as i128,
+                            ty: QIRType::UInt32,
+                        },
+                    ],
+                    QIRType::Bool,
+                    token,
+                )
+            })
+            .reduce(|either, other| {
+                self.intrinsic(&AstType::Bool, "or", vec![either, other], QIRType::Bool, token)
+            })
+            .unwrap_or(QIRExpression::Bool { value: false })
+    }
+
+    /// The type IDs of the objects a value of `ty` can be, which are those of the
+    /// structs implementing it for interfaces.
+    fn member_type_ids(&mut self, ty: &AstType) -> Vec<u32> {
+        if let AstType::Structure(name) = ty
+            && let Some(ProgramType::Interface { .. }) = self.info.names.get(name)
+        {
+            return self
+                .info
+                .implementors(name)
+                .into_iter()
+                .map(|implementor| self.info.type_id(&AstType::Structure(implementor)))
+                .collect();
+        }
+        vec![self.info.type_id(ty)]
+    }
+}
+
+pub(crate) fn expr_type(ty: &OnceCell<AstType>) -> AstType {
+    ty.get().cloned().expect("expressions are typechecked before lowering")
+}
+
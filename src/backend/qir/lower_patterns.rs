@@ -0,0 +1,297 @@
+use crate::backend::qir::layout::{object_fields, ENUM_PAYLOAD_FIELD, ENUM_TAG_FIELD};
+use crate::backend::qir::lower::{expect_local, load_field, Code, FunctionLowering, Tail};
+use crate::backend::qir::structs::{QIRExpression, QIRTerminator, QIRType};
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstExpression, AstMatchArm, AstPattern, AstType};
+use crate::frontend::typecheck::data::ProgramType;
+
+impl FunctionLowering<'_> {
+    /// Lowers a match to a chain of branches, one per arm, that test the arm's pattern
+    /// and run its body if it matched.
+    ///
+    /// A match without a value yields `Null`, which the statement it is in discards.
+    pub(crate) fn lower_match(
+        &mut self,
+        value: &AstExpression,
+        arms: &[AstMatchArm],
+        ty: &AstType,
+        out: &mut Code,
+    ) -> QIRExpression {
+        let value_ty = value.get_type();
+        let lowered = self.lower_expression(value, out);
+        let scrutinee = self.spill(lowered, value_ty.to_qir_type(), out);
+        let result = (*ty != AstType::Void).then(|| self.temp(ty.to_qir_type()));
+        let matched = self.temp(QIRType::Bool);
+
+        // Bindings are only stored once their pattern matched, and only read in the
+        // body of their arm. They start out with a placeholder so every path stores them.
+        let mut bindings = vec![];
+        arms.iter().for_each(|arm| bound_locals(&arm.pattern, &mut bindings));
+        for local in bindings {
+            out.push(self.store_local(local, placeholder(&self.locals[local])));
+        }
+
+        let chain = self.lower_arms(arms, scrutinee, &value_ty, result, matched);
+        out.extend(chain);
+        match result {
+            Some(result) => self.load_local(result),
+            None => QIRExpression::Null,
+        }
+    }
+
+    fn lower_arms(
+        &mut self,
+        arms: &[AstMatchArm],
+        scrutinee: usize,
+        value_ty: &AstType,
+        result: Option<usize>,
+        matched: usize,
+    ) -> Code {
+        let mut code = Code::default();
+        // Matches are exhaustive, so a value that no arm matched can't exist.
+        let Some((arm, rest)) = arms.split_first() else {
+            code.terminate(QIRTerminator::Unreachable);
+            return code;
+        };
+        code.push(self.store_local(matched, QIRExpression::Bool { value: true }));
+        self.lower_pattern(&arm.pattern, scrutinee, value_ty, matched, &mut code);
+
+        let tail = match result {
+            Some(result) if !arm.body.always_returns() => Tail::Store(result),
+            _ => Tail::Discard,
+        };
+        let body = self.lower_nested(&arm.body, tail);
+        let otherwise = self.lower_arms(rest, scrutinee, value_ty, result, matched);
+        self.branch(self.load_local(matched), body, otherwise, &mut code);
+        code
+    }
+
+    /// Tests whether the value in `value` matches `pattern`, storing false to `matched`
+    /// if it doesn't, and binds the variables of the pattern.
+    ///
+    /// Parts of a value are only read once the tests guarding them passed, so a pattern
+    /// never reads the fields of an object of a different type.
+    fn lower_pattern(
+        &mut self,
+        pattern: &AstPattern,
+        value: usize,
+        value_ty: &AstType,
+        matched: usize,
+        out: &mut Code,
+    ) {
+        match pattern {
+            AstPattern::Wildcard { .. } => {}
+            AstPattern::Binding { local, .. } => {
+                out.push(self.store_local(expect_local(local), self.load_local(value)));
+            }
+            AstPattern::TypeTest { binding, ty, token } => {
+                let mut then = Code::default();
+                if let Some((_, local)) = binding {
+                    let narrowed = self.narrow(self.load_local(value), value_ty, ty);
+                    then.push(self.store_local(expect_local(local), narrowed));
+                }
+                self.guard_member(value, value_ty, ty, matched, then, token, out);
+            }
+            AstPattern::Literal(expr) => {
+                let literal_ty = expr.get_type();
+                let token = expr.get_token();
+                let mut then = Code::default();
+                let member = self.narrowed_value(value, value_ty, &literal_ty, &mut then);
+                let literal = self.lower_expression(expr, &mut then);
+                let equal = self.intrinsic(
+                    &literal_ty,
+                    "eq",
+                    vec![self.load_local(member), literal],
+                    QIRType::Bool,
+                    &token,
+                );
+                self.guard(equal, Code::default(), matched, &mut then);
+                self.guard_member(value, value_ty, &literal_ty, matched, then, &token, out);
+            }
+            AstPattern::Range {
+                start,
+                end,
+                inclusive,
+                token,
+            } => {
+                let range_ty = start.get_type();
+                let mut then = Code::default();
+                let member = self.narrowed_value(value, value_ty, &range_ty, &mut then);
+                let [low, high] = self.lower_operands([start, end], &mut then);
+                let above = self.intrinsic(
+                    &range_ty,
+                    "ge",
+                    vec![self.load_local(member), low],
+                    QIRType::Bool,
+                    token,
+                );
+                let comparison = if *inclusive { "le" } else { "lt" };
+                let below = self.intrinsic(
+                    &range_ty,
+                    comparison,
+                    vec![self.load_local(member), high],
+                    QIRType::Bool,
+                    token,
+                );
+                let mut in_range = Code::default();
+                self.guard(below, Code::default(), matched, &mut in_range);
+                self.guard(above, in_range, matched, &mut then);
+                self.guard_member(value, value_ty, &range_ty, matched, then, token, out);
+            }
+            AstPattern::Structure { name, fields, ty } => {
+                let struct_ty = ty.get().expect("patterns are typechecked").clone();
+                let Some(ProgramType::Structure {
+                    fields: declared_fields,
+                    ..
+                }) = self.info.names.get(&name.name).cloned()
+                else {
+                    unreachable!("structure patterns are checked to name structs");
+                };
+                let layout = self.info.struct_layout(&name.name);
+
+                let mut then = Code::default();
+                let object = self.narrowed_value(value, value_ty, &struct_ty, &mut then);
+                for (field, pattern) in fields {
+                    let (_, field_ty) = declared_fields
+                        .iter()
+                        .find(|(declared, _)| declared == field)
+                        .expect("fields are checked by the typechecker");
+                    let index = self
+                        .info
+                        .field_index(&name.name, field)
+                        .expect("fields are checked by the typechecker");
+                    let field_value = load_field(self.load_local(object), layout.clone(), index);
+                    let field_local = self.spill(field_value, field_ty.to_qir_type(), &mut then);
+                    self.lower_pattern(pattern, field_local, field_ty, matched, &mut then);
+                }
+                self.guard_member(value, value_ty, &struct_ty, matched, then, &name.token, out);
+            }
+            AstPattern::Variant { name, fields, ty } => {
+                let enum_ty = ty.get().expect("patterns are typechecked").clone();
+                let (enum_name, variant_name) = name
+                    .name
+                    .rsplit_once("::")
+                    .expect("variant paths contain `::`");
+                let Some(ProgramType::Enum { variants, .. }) = self.info.names.get(enum_name) else {
+                    unreachable!("variant patterns are checked to name enums");
+                };
+                let tag = variants
+                    .iter()
+                    .position(|(variant, _)| variant == variant_name)
+                    .expect("variants are checked by the typechecker");
+                let field_tys = variants[tag].1.clone();
+                let layout = object_fields([QIRType::UInt32, QIRType::Ptr]);
+
+                let mut then = Code::default();
+                let object = self.narrowed_value(value, value_ty, &enum_ty, &mut then);
+                let mut payload_tests = Code::default();
+                if !fields.is_empty() {
+                    let payload_layout = object_fields(field_tys.iter().map(|ty| ty.to_qir_type()));
+                    let payload = load_field(self.load_local(object), layout.clone(), ENUM_PAYLOAD_FIELD);
+                    let payload = self.spill(payload, QIRType::Ptr, &mut payload_tests);
+                    for (index, (pattern, field_ty)) in fields.iter().zip(&field_tys).enumerate() {
+                        let field_value =
+                            load_field(self.load_local(payload), payload_layout.clone(), index as i32 + 2);
+                        let field_local =
+                            self.spill(field_value, field_ty.to_qir_type(), &mut payload_tests);
+                        self.lower_pattern(pattern, field_local, field_ty, matched, &mut payload_tests);
+                    }
+                }
+                let actual = load_field(self.load_local(object), layout, ENUM_TAG_FIELD);
+                let is_variant = self.intrinsic(
+                    &AstType::UInt32,
+                    "eq",
+                    vec![
+                        actual,
+                        QIRExpression::Integer {
+                            value: tag as i128,
+                            ty: QIRType::UInt32,
+                        },
+                    ],
+                    QIRType::Bool,
+                    &name.token,
+                );
+                self.guard(is_variant, payload_tests, matched, &mut then);
+                self.guard_member(value, value_ty, &enum_ty, matched, then, &name.token, out);
+            }
+        }
+    }
+
+    /// Runs `then` if `condition` holds, and stores false to `matched` otherwise.
+    fn guard(&mut self, condition: QIRExpression, then: Code, matched: usize, out: &mut Code) {
+        let mut failed = Code::default();
+        failed.push(self.store_local(matched, QIRExpression::Bool { value: false }));
+        self.branch(condition, then, failed, out);
+    }
+
+    /// Runs `then` if the value holds the union member `member`, which it always does
+    /// when it isn't a union.
+    fn guard_member(
+        &mut self,
+        value: usize,
+        value_ty: &AstType,
+        member: &AstType,
+        matched: usize,
+        then: Code,
+        token: &Token,
+        out: &mut Code,
+    ) {
+        if value_ty == member || !matches!(value_ty, AstType::UnionOf(..)) {
+            out.extend(then);
+            return;
+        }
+        let holds_member = self.type_test(value, member, token);
+        self.guard(holds_member, then, matched, out);
+    }
+
+    /// The local holding the value as the union member `member`, unboxing it into a
+    /// temporary if needed.
+    fn narrowed_value(
+        &mut self,
+        value: usize,
+        value_ty: &AstType,
+        member: &AstType,
+        out: &mut Code,
+    ) -> usize {
+        if value_ty == member || !matches!(value_ty, AstType::UnionOf(..)) {
+            return value;
+        }
+        let narrowed = self.narrow(self.load_local(value), value_ty, member);
+        self.spill(narrowed, member.to_qir_type(), out)
+    }
+}
+
+/// Adds the locals `pattern` binds to `out`.
+fn bound_locals(pattern: &AstPattern, out: &mut Vec<usize>) {
+    match pattern {
+        AstPattern::Binding { local, .. }
+        | AstPattern::TypeTest {
+            binding: Some((_, local)),
+            ..
+        } => out.push(expect_local(local)),
+        AstPattern::Structure { fields, .. } => {
+            fields.iter().for_each(|(_, field)| bound_locals(field, out));
+        }
+        AstPattern::Variant { fields, .. } => fields.iter().for_each(|field| bound_locals(field, out)),
+        AstPattern::Wildcard { .. }
+        | AstPattern::TypeTest { binding: None, .. }
+        | AstPattern::Literal(_)
+        | AstPattern::Range { .. } => {}
+    }
+}
+
+/// A value of type `ty` to store before there is a real one.
+fn placeholder(ty: &QIRType) -> QIRExpression {
+    match ty {
+        QIRType::Bool => QIRExpression::Bool { value: false },
+        QIRType::Float32 | QIRType::Float64 => QIRExpression::Float {
+            value: 0.0,
+            ty: ty.clone(),
+        },
+        QIRType::Ptr | QIRType::Union => QIRExpression::Null,
+        integer => QIRExpression::Integer {
+            value: 0,
+            ty: integer.clone(),
+        },
+    }
+}
@@ -1 +1,17 @@
+pub mod arrays;
+pub mod cfg;
+mod destructors;
+pub mod entry;
+#[cfg(test)]
+mod golden;
+pub mod layout;
+pub mod lower;
+mod lower_expressions;
+mod lower_patterns;
+pub mod parse;
+pub mod print;
+mod rc_elision;
+mod refcount;
 pub mod structs;
+mod types;
+mod verify;
@@ -0,0 +1,523 @@
+use crate::backend::qir::print::is_name_char;
+use crate::backend::qir::structs::{
+    Label, QIRBasicBlock, QIRExpression, QIRHeader, QIRLocalVariable, QIRTerminator, QIRType,
+};
+use crate::frontend::lexer::tokens::{Token, TokenType};
+use crate::frontend::parser::ast::PathData;
+use crate::frontend::span::Span;
+use std::str::FromStr;
+
+/// Reads a program written by `print_program` back, or written by hand in the same
+/// form. `//` starts a comment that runs to the end of the line.
+pub fn parse_program(file_name: &str, text: &str) -> Result<Vec<QIRHeader>, (String, Span)> {
+    let mut reader = Reader {
+        chars: text.chars().collect(),
+        index: 0,
+        row: 1,
+        column: 1,
+        file_name: file_name.to_string(),
+    };
+    let mut headers = vec![];
+    while reader.skip_whitespace() {
+        headers.push(reader.parse_header()?);
+    }
+    Ok(headers)
+}
+
+struct Reader {
+    chars: Vec<char>,
+    index: usize,
+    row: usize,
+    column: usize,
+    file_name: String,
+}
+
+impl Reader {
+    fn span(&self, length: usize) -> Span {
+        Span {
+            row_start: self.row,
+            row_end: self.row,
+            column_start: self.column,
+            column_end: self.column + length,
+            file_name: self.file_name.clone(),
+        }
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, (String, Span)> {
+        let found = match self.peek() {
+            Some(c) => format!("`{}`", c),
+            None => "the end of the file".to_string(),
+        };
+        Err((format!("expected {}, found {}", expected, found), self.span(1)))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.row += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    /// Skips whitespace and comments, and returns whether anything is left.
+    fn skip_whitespace(&mut self) -> bool {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.advance();
+                }
+                Some('/') if self.chars.get(self.index + 1) == Some(&'/') => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.advance();
+                    }
+                }
+                Some(_) => return true,
+                None => return false,
+            }
+        }
+    }
+
+    /// Consumes `symbol` if it comes next.
+    fn eat(&mut self, symbol: &str) -> bool {
+        self.skip_whitespace();
+        let matches = symbol
+            .chars()
+            .enumerate()
+            .all(|(offset, c)| self.chars.get(self.index + offset) == Some(&c));
+        // Keywords must not be the start of a longer word.
+        let ends_word = !symbol.chars().all(is_name_char)
+            || !self
+                .chars
+                .get(self.index + symbol.chars().count())
+                .is_some_and(|c| is_name_char(*c));
+        if matches && ends_word {
+            symbol.chars().for_each(|_| {
+                self.advance();
+            });
+        }
+        matches && ends_word
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), (String, Span)> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            self.error(&format!("`{}`", symbol))
+        }
+    }
+
+    /// Reads the characters `accept` takes, and returns them with the span they cover.
+    fn word(&mut self, accept: fn(char) -> bool) -> (String, Span) {
+        self.skip_whitespace();
+        let mut span = self.span(0);
+        let mut word = String::new();
+        while let Some(c) = self.peek().filter(|c| accept(*c)) {
+            word.push(c);
+            self.advance();
+        }
+        span.column_end = self.column;
+        (word, span)
+    }
+
+    fn number<T: FromStr>(&mut self, what: &str) -> Result<T, (String, Span)> {
+        self.skip_whitespace();
+        let (word, span) = self.word(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'));
+        word.parse()
+            .map_err(|_| (format!("expected {}, found `{}`", what, word), span))
+    }
+
+    fn parse_name(&mut self) -> Result<PathData, (String, Span)> {
+        self.skip_whitespace();
+        let (name, span) = if self.peek() == Some('"') {
+            let span = self.span(0);
+            let name = self.parse_string()?;
+            (name, Span { column_end: self.column, ..span })
+        } else {
+            self.word(is_name_char)
+        };
+        if name.is_empty() {
+            return self.error("a name");
+        }
+        Ok(PathData {
+            token: Token {
+                token_type: TokenType::Identifier { content: name.clone() },
+                span,
+            },
+            name,
+        })
+    }
+
+    /// Reads a string in the quotes and escapes of Rust's `Debug` formatting.
+    fn parse_string(&mut self) -> Result<String, (String, Span)> {
+        self.expect("\"")?;
+        let mut value = String::new();
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(value),
+                Some('\\') => {
+                    let escaped = match self.advance() {
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('0') => '\0',
+                        Some(c @ ('\\' | '"' | '\'')) => c,
+                        Some('u') => {
+                            self.expect("{")?;
+                            let (digits, span) = self.word(|c| c.is_ascii_hexdigit());
+                            self.expect("}")?;
+                            u32::from_str_radix(&digits, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or((format!("`{}` is not a character", digits), span))?
+                        }
+                        _ => return self.error("an escape"),
+                    };
+                    value.push(escaped);
+                }
+                Some(c) => value.push(c),
+                None => return self.error("`\"`"),
+            }
+        }
+    }
+
+    fn parse_type(&mut self) -> Result<QIRType, (String, Span)> {
+        const TYPES: [(&str, QIRType); 16] = [
+            ("i8", QIRType::Int8),
+            ("i16", QIRType::Int16),
+            ("i32", QIRType::Int32),
+            ("i64", QIRType::Int64),
+            ("u8", QIRType::UInt8),
+            ("u16", QIRType::UInt16),
+            ("u32", QIRType::UInt32),
+            ("u64", QIRType::UInt64),
+            ("usize", QIRType::USize),
+            ("f32", QIRType::Float32),
+            ("f64", QIRType::Float64),
+            ("bool", QIRType::Bool),
+            ("ptr", QIRType::Ptr),
+            ("void", QIRType::Void),
+            ("invalid", QIRType::Invalid),
+            ("union", QIRType::Union),
+        ];
+        match TYPES.into_iter().find(|(name, _)| self.eat(name)) {
+            Some((_, ty)) => Ok(ty),
+            None => self.error("a type"),
+        }
+    }
+
+    /// Reads `{ u32, u32, i32 }`.
+    fn parse_struct_type(&mut self) -> Result<Vec<QIRType>, (String, Span)> {
+        self.expect("{")?;
+        let fields = self.parse_list('}', Reader::parse_type)?;
+        self.expect("}")?;
+        Ok(fields)
+    }
+
+    /// Reads items separated by commas, up to `end`, which is left for the caller.
+    fn parse_list<T>(
+        &mut self,
+        end: char,
+        mut item: impl FnMut(&mut Reader) -> Result<T, (String, Span)>,
+    ) -> Result<Vec<T>, (String, Span)> {
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(end) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if !self.eat(",") {
+                return Ok(items);
+            }
+        }
+    }
+
+    fn parse_local(&mut self) -> Result<QIRLocalVariable, (String, Span)> {
+        self.expect("%")?;
+        Ok(QIRLocalVariable {
+            id: self.number("a local")?,
+        })
+    }
+
+    /// Reads the entries of a vtable or of the destructors, `id: ..` on each line.
+    fn parse_entries<T>(
+        &mut self,
+        mut value: impl FnMut(&mut Reader) -> Result<T, (String, Span)>,
+    ) -> Result<Vec<(u32, T)>, (String, Span)> {
+        self.expect("{")?;
+        let mut entries = vec![];
+        while !self.eat("}") {
+            let type_id = self.number("a type ID")?;
+            self.expect(":")?;
+            entries.push((type_id, value(self)?));
+        }
+        Ok(entries)
+    }
+
+    fn parse_header(&mut self) -> Result<QIRHeader, (String, Span)> {
+        if self.eat("global") {
+            let ty = self.parse_type()?;
+            Ok(QIRHeader::GlobalVariable {
+                name: self.parse_name()?,
+                ty,
+            })
+        } else if self.eat("fn") {
+            let name = self.parse_name()?;
+            self.expect("(")?;
+            let parameters = self.parse_list(')', |reader| {
+                let local = reader.parse_local()?;
+                reader.expect(":")?;
+                Ok((local, reader.parse_type()?))
+            })?;
+            self.expect(")")?;
+            self.expect("->")?;
+            let returns = self.parse_type()?;
+            self.expect("locals")?;
+            self.expect("[")?;
+            let locals = self.parse_list(']', Reader::parse_type)?;
+            self.expect("]")?;
+            Ok(QIRHeader::Function {
+                name,
+                parameters,
+                returns,
+                locals,
+                blocks: self.parse_blocks()?,
+            })
+        } else if self.eat("struct") {
+            Ok(QIRHeader::Struct {
+                name: self.parse_name()?,
+                fields: self.parse_struct_type()?,
+            })
+        } else if self.eat("vtable") {
+            let interface = self.parse_name()?;
+            let entries = self.parse_entries(|reader| {
+                reader.expect("[")?;
+                let methods = reader.parse_list(']', Reader::parse_name)?;
+                reader.expect("]")?;
+                Ok(methods)
+            })?;
+            Ok(QIRHeader::VTable { interface, entries })
+        } else if self.eat("destructors") {
+            let entries = self.parse_entries(Reader::parse_name)?;
+            self.expect("tracers")?;
+            let tracers = self.parse_entries(Reader::parse_name)?;
+            Ok(QIRHeader::Destructors { entries, tracers })
+        } else {
+            self.error("`global`, `fn`, `struct`, `vtable` or `destructors`")
+        }
+    }
+
+    /// Reads the body of a function, its blocks in the order of their labels.
+    fn parse_blocks(&mut self) -> Result<Vec<QIRBasicBlock>, (String, Span)> {
+        self.expect("{")?;
+        let mut blocks = vec![];
+        while !self.eat("}") {
+            self.skip_whitespace();
+            let span = self.span(1);
+            let label = self.parse_label()?;
+            if label != blocks.len() {
+                return Err((format!("expected bb{}, found bb{}", blocks.len(), label), span));
+            }
+            self.expect(":")?;
+            let mut exprs = vec![];
+            let terminator = loop {
+                match self.parse_terminator()? {
+                    Some(terminator) => break terminator,
+                    None => exprs.push(self.parse_expression()?),
+                }
+            };
+            blocks.push(QIRBasicBlock { exprs, terminator });
+        }
+        Ok(blocks)
+    }
+
+    /// Reads `bb3`.
+    fn parse_label(&mut self) -> Result<Label, (String, Span)> {
+        let (word, span) = self.word(|c| c.is_ascii_alphanumeric());
+        word.strip_prefix("bb")
+            .and_then(|label| label.parse().ok())
+            .ok_or((format!("expected a label, found `{}`", word), span))
+    }
+
+    /// Reads the terminator of a block, or nothing if an expression comes next.
+    fn parse_terminator(&mut self) -> Result<Option<QIRTerminator>, (String, Span)> {
+        if self.eat("br") {
+            return Ok(Some(QIRTerminator::Br {
+                target: self.parse_label()?,
+            }));
+        }
+        if self.eat("cond_br") {
+            let [condition] = self.parse_exact_operands()?;
+            self.expect(",")?;
+            let if_true = self.parse_label()?;
+            self.expect(",")?;
+            return Ok(Some(QIRTerminator::CondBr {
+                condition: *condition,
+                if_true,
+                if_false: self.parse_label()?,
+            }));
+        }
+        if self.eat("ret") {
+            self.skip_whitespace();
+            let value = if self.peek() == Some('(') {
+                let [value] = self.parse_exact_operands()?;
+                Some(*value)
+            } else {
+                None
+            };
+            return Ok(Some(QIRTerminator::Ret { value }));
+        }
+        if self.eat("unreachable") {
+            return Ok(Some(QIRTerminator::Unreachable));
+        }
+        Ok(None)
+    }
+
+    /// Reads `(first, second, ..)`.
+    fn parse_operands(&mut self) -> Result<Vec<QIRExpression>, (String, Span)> {
+        self.expect("(")?;
+        let operands = self.parse_list(')', Reader::parse_expression)?;
+        self.expect(")")?;
+        Ok(operands)
+    }
+
+    /// Reads the operands of an expression that takes exactly `N` of them.
+    fn parse_exact_operands<const N: usize>(
+        &mut self,
+    ) -> Result<[Box<QIRExpression>; N], (String, Span)> {
+        self.skip_whitespace();
+        let span = self.span(1);
+        let operands = self.parse_operands()?;
+        let found = operands.len();
+        operands
+            .into_iter()
+            .map(Box::new)
+            .collect::<Vec<_>>()
+            .try_into()
+            .map_err(|_| {
+                let operands = if N == 1 { "operand" } else { "operands" };
+                (format!("expected {} {}, found {}", N, operands, found), span)
+            })
+    }
+
+    fn parse_expression(&mut self) -> Result<QIRExpression, (String, Span)> {
+        if self.eat("retain") {
+            let [ptr] = self.parse_exact_operands()?;
+            return Ok(QIRExpression::Retain { ptr });
+        }
+        if self.eat("release") {
+            let [ptr] = self.parse_exact_operands()?;
+            return Ok(QIRExpression::Release { ptr });
+        }
+        if self.eat("call_closure") {
+            let return_type = self.parse_type()?;
+            let [closure] = self.parse_exact_operands()?;
+            return Ok(QIRExpression::InvokeClosure {
+                closure,
+                arguments: self.parse_operands()?,
+                return_type,
+            });
+        }
+        if self.eat("call_dynamic") {
+            let return_type = self.parse_type()?;
+            let interface = self.parse_name()?;
+            self.expect("#")?;
+            return Ok(QIRExpression::InvokeDynamic {
+                interface,
+                method: self.number("a method index")?,
+                arguments: self.parse_operands()?,
+                return_type,
+            });
+        }
+        if self.eat("call") {
+            let return_type = self.parse_type()?;
+            return Ok(QIRExpression::Invoke {
+                name: self.parse_name()?,
+                arguments: self.parse_operands()?,
+                return_type,
+            });
+        }
+        if self.eat("fn_ptr") {
+            return Ok(QIRExpression::GetFunctionPtr {
+                function: self.parse_name()?,
+            });
+        }
+        if self.eat("true") {
+            return Ok(QIRExpression::Bool { value: true });
+        }
+        if self.eat("false") {
+            return Ok(QIRExpression::Bool { value: false });
+        }
+        if self.eat("string") {
+            let type_id = self.number("a type ID")?;
+            self.skip_whitespace();
+            return Ok(QIRExpression::String {
+                value: self.parse_string()?,
+                type_id,
+            });
+        }
+        if self.eat("null") {
+            return Ok(QIRExpression::Null);
+        }
+        if self.eat("new") {
+            return Ok(QIRExpression::InstantiateStructure {
+                ptr_type: self.parse_struct_type()?,
+            });
+        }
+        if self.eat("store") {
+            let output_type = self.parse_type()?;
+            let [receiver, new_value] = self.parse_exact_operands()?;
+            return Ok(QIRExpression::StoreToPtr {
+                receiver,
+                output_type,
+                new_value,
+            });
+        }
+        if self.eat("field_ptr") {
+            let output_type = self.parse_type()?;
+            let ptr_type = self.parse_struct_type()?;
+            let field = self.number("a field index")?;
+            let [receiver] = self.parse_exact_operands()?;
+            return Ok(QIRExpression::GetFieldPtr {
+                receiver,
+                ptr_type,
+                output_type,
+                field,
+            });
+        }
+        if self.eat("load") {
+            let output_type = self.parse_type()?;
+            let [ptr] = self.parse_exact_operands()?;
+            return Ok(QIRExpression::LoadFromPtr { ptr, output_type });
+        }
+        if self.peek() == Some('%') {
+            return Ok(QIRExpression::GetLocalPtr {
+                local: self.parse_local()?,
+            });
+        }
+        if self.eat("@") {
+            return Ok(QIRExpression::GetGlobalPtr {
+                global: self.parse_name()?,
+            });
+        }
+        let ty = self.parse_type().or_else(|_| self.error("an expression"))?;
+        match ty {
+            QIRType::Float32 | QIRType::Float64 => Ok(QIRExpression::Float {
+                value: self.number("a float")?,
+                ty,
+            }),
+            _ => Ok(QIRExpression::Integer {
+                value: self.number("an integer")?,
+                ty,
+            }),
+        }
+    }
+}
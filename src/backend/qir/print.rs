@@ -0,0 +1,254 @@
+use crate::backend::qir::structs::{QIRExpression, QIRHeader, QIRTerminator, QIRType};
+use std::fmt::{Display, Formatter, Result};
+
+/// Prints a program in the textual form of QIR, which `parse_program` reads back.
+///
+/// Headers are separated by blank lines. Locals are written `%id`, globals `@name`,
+/// and every expression starts with a keyword, followed by the type it yields when
+/// that isn't implied, any constant operands and then the expressions it works on
+/// in parentheses. The body of a function is its blocks, each a label followed by
+/// its expressions and its terminator:
+///
+/// ```qir
+/// fn abs(%0: i32) -> i32 locals [i32] {
+/// bb0:
+///     cond_br (call bool qre::i32::lt(load i32 (%0), i32 0)), bb1, bb2
+/// bb1:
+///     ret (call i32 qre::i32::neg(load i32 (%0)))
+/// bb2:
+///     ret (load i32 (%0))
+/// }
+/// ```
+pub fn print_program(program: &[QIRHeader]) -> String {
+    program
+        .iter()
+        .map(|header| header.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Display for QIRHeader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            QIRHeader::GlobalVariable { name, ty } => writeln!(f, "global {} {}", ty, Name(&name.name)),
+            QIRHeader::Function {
+                name,
+                parameters,
+                returns,
+                locals,
+                blocks,
+            } => {
+                write!(f, "fn {}(", Name(&name.name))?;
+                for (index, (local, ty)) in parameters.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "%{}: {}", local.id, ty)?;
+                }
+                write!(f, ") -> {} locals [", returns)?;
+                write_list(f, locals)?;
+                writeln!(f, "] {{")?;
+                for (label, block) in blocks.iter().enumerate() {
+                    writeln!(f, "bb{}:", label)?;
+                    for expr in &block.exprs {
+                        writeln!(f, "    {}", expr)?;
+                    }
+                    writeln!(f, "    {}", block.terminator)?;
+                }
+                writeln!(f, "}}")
+            }
+            QIRHeader::Struct { name, fields } => {
+                writeln!(f, "struct {} {}", Name(&name.name), StructType(fields))
+            }
+            QIRHeader::VTable { interface, entries } => {
+                writeln!(f, "vtable {} {{", Name(&interface.name))?;
+                for (type_id, methods) in entries {
+                    let methods = methods.iter().map(|method| Name(&method.name)).collect::<Vec<_>>();
+                    write!(f, "    {}: [", type_id)?;
+                    write_list(f, &methods)?;
+                    writeln!(f, "]")?;
+                }
+                writeln!(f, "}}")
+            }
+            QIRHeader::Destructors { entries, tracers } => {
+                writeln!(f, "destructors {{")?;
+                for (type_id, function) in entries {
+                    writeln!(f, "    {}: {}", type_id, Name(&function.name))?;
+                }
+                writeln!(f, "}} tracers {{")?;
+                for (type_id, function) in tracers {
+                    writeln!(f, "    {}: {}", type_id, Name(&function.name))?;
+                }
+                writeln!(f, "}}")
+            }
+        }
+    }
+}
+
+impl Display for QIRExpression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write_expression(f, self)
+    }
+}
+
+impl Display for QIRTerminator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            QIRTerminator::Br { target } => write!(f, "br bb{}", target),
+            QIRTerminator::CondBr {
+                condition,
+                if_true,
+                if_false,
+            } => write!(f, "cond_br ({}), bb{}, bb{}", condition, if_true, if_false),
+            QIRTerminator::Ret { value: None } => write!(f, "ret"),
+            QIRTerminator::Ret { value: Some(value) } => write!(f, "ret ({})", value),
+            QIRTerminator::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+impl Display for QIRType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let name = match self {
+            QIRType::Int8 => "i8",
+            QIRType::Int16 => "i16",
+            QIRType::Int32 => "i32",
+            QIRType::Int64 => "i64",
+            QIRType::UInt8 => "u8",
+            QIRType::UInt16 => "u16",
+            QIRType::UInt32 => "u32",
+            QIRType::UInt64 => "u64",
+            QIRType::USize => "usize",
+            QIRType::Float32 => "f32",
+            QIRType::Float64 => "f64",
+            QIRType::Bool => "bool",
+            QIRType::Ptr => "ptr",
+            QIRType::Void => "void",
+            QIRType::Invalid => "invalid",
+            QIRType::Union => "union",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The name of a function, struct, interface or global. Names with characters that
+/// delimit the other parts of an expression, like the spaces in `List<i32 | none>`,
+/// are quoted.
+pub(crate) struct Name<'a>(pub(crate) &'a str);
+
+impl Display for Name<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if !self.0.is_empty() && self.0.chars().all(is_name_char) {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "{:?}", self.0)
+        }
+    }
+}
+
+pub(crate) fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | ':' | '<' | '>' | '.')
+}
+
+/// The fields of a structure, `{ u32, u32, i32 }`.
+struct StructType<'a>(&'a [QIRType]);
+
+impl Display for StructType<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{{ ")?;
+        write_list(f, self.0)?;
+        write!(f, " }}")
+    }
+}
+
+fn write_list(f: &mut Formatter<'_>, items: &[impl Display]) -> Result {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+    Ok(())
+}
+
+/// Writes `(first, second, ..)`.
+fn write_operands<'e>(f: &mut Formatter<'_>, operands: impl IntoIterator<Item = &'e QIRExpression>) -> Result {
+    write!(f, "(")?;
+    for (index, operand) in operands.into_iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write_expression(f, operand)?;
+    }
+    write!(f, ")")
+}
+
+fn write_expression(f: &mut Formatter<'_>, expr: &QIRExpression) -> Result {
+    match expr {
+        QIRExpression::Retain { ptr } => {
+            write!(f, "retain ")?;
+            write_operands(f, [ptr.as_ref()])
+        }
+        QIRExpression::Release { ptr } => {
+            write!(f, "release ")?;
+            write_operands(f, [ptr.as_ref()])
+        }
+        QIRExpression::Invoke {
+            name,
+            arguments,
+            return_type,
+        } => {
+            write!(f, "call {} {}", return_type, Name(&name.name))?;
+            write_operands(f, arguments)
+        }
+        QIRExpression::InvokeClosure {
+            closure,
+            arguments,
+            return_type,
+        } => {
+            write!(f, "call_closure {} ", return_type)?;
+            write_operands(f, [closure.as_ref()])?;
+            write_operands(f, arguments)
+        }
+        QIRExpression::GetFunctionPtr { function } => write!(f, "fn_ptr {}", Name(&function.name)),
+        QIRExpression::InvokeDynamic {
+            interface,
+            method,
+            arguments,
+            return_type,
+        } => {
+            write!(f, "call_dynamic {} {}#{}", return_type, Name(&interface.name), method)?;
+            write_operands(f, arguments)
+        }
+        QIRExpression::Integer { value, ty } => write!(f, "{} {}", ty, value),
+        // Debug formatting keeps the decimal point, and reads back to the same value.
+        QIRExpression::Float { value, ty } => write!(f, "{} {:?}", ty, value),
+        QIRExpression::Bool { value } => write!(f, "{}", value),
+        QIRExpression::String { value, type_id } => write!(f, "string {} {:?}", type_id, value),
+        QIRExpression::Null => write!(f, "null"),
+        QIRExpression::InstantiateStructure { ptr_type } => write!(f, "new {}", StructType(ptr_type)),
+        QIRExpression::StoreToPtr {
+            receiver,
+            output_type,
+            new_value,
+        } => {
+            write!(f, "store {} ", output_type)?;
+            write_operands(f, [receiver.as_ref(), new_value.as_ref()])
+        }
+        QIRExpression::GetFieldPtr {
+            receiver,
+            ptr_type,
+            output_type,
+            field,
+        } => {
+            write!(f, "field_ptr {} {} {} ", output_type, StructType(ptr_type), field)?;
+            write_operands(f, [receiver.as_ref()])
+        }
+        QIRExpression::LoadFromPtr { ptr, output_type } => {
+            write!(f, "load {} ", output_type)?;
+            write_operands(f, [ptr.as_ref()])
+        }
+        QIRExpression::GetLocalPtr { local } => write!(f, "%{}", local.id),
+        QIRExpression::GetGlobalPtr { global } => write!(f, "@{}", Name(&global.name)),
+    }
+}
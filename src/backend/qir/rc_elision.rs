@@ -0,0 +1,570 @@
+use crate::backend::qir::cfg::{forward, predecessors, reachable_from};
+use crate::backend::qir::structs::{Label, QIRBasicBlock, QIRExpression, QIRHeader, QIRTerminator};
+use std::collections::{HashMap, HashSet};
+
+/// Removes retains and releases that `insert_refcounts` places where they don't change
+/// when an object is freed.
+///
+/// - Releases of locals that still hold the null they start out with are dropped.
+/// - A local that borrows a parameter, or a local that is assigned once, and never
+///   passes the value on doesn't hold a reference of its own.
+/// - A retain that is released again before anything could free the object cancels out.
+/// - A local whose value is stored or returned by its last use hands over its own
+///   reference instead of a new one.
+pub(crate) fn eliminate_refcounts(header: &mut QIRHeader) {
+    let QIRHeader::Function {
+        parameters,
+        blocks,
+        ..
+    } = header
+    else {
+        return;
+    };
+    let parameters = parameters
+        .iter()
+        .map(|(parameter, _)| parameter.id)
+        .collect::<HashSet<_>>();
+    // The locals that own their values: the parameters and the locals the function
+    // starts by setting to null. Temporaries of the pass only hold values briefly.
+    let initialized = blocks[0]
+        .exprs
+        .iter()
+        .map_while(|expr| match expr {
+            QIRExpression::StoreToPtr {
+                receiver,
+                new_value,
+                ..
+            } if matches!(**new_value, QIRExpression::Null) => match **receiver {
+                QIRExpression::GetLocalPtr { local } => Some(local.id),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let owners = Owners {
+        parameters,
+        initialized: initialized.iter().copied().collect(),
+        prologue: initialized.len(),
+    };
+
+    release_known_null(blocks);
+    remove_dead_stores(blocks);
+    let borrowed = skip_non_escaping(blocks, &owners);
+    blocks.iter_mut().for_each(cancel_pairs);
+    move_last_uses(blocks, &owners, &borrowed);
+    remove_dead_stores(blocks);
+}
+
+/// The locals of a function that own the values they hold.
+struct Owners {
+    parameters: HashSet<usize>,
+    /// Locals other than the parameters, which the function starts by setting to null.
+    initialized: HashSet<usize>,
+    /// The number of stores at the start of the function that set them to null.
+    prologue: usize,
+}
+
+impl Owners {
+    fn owns(&self, local: usize) -> bool {
+        self.parameters.contains(&local) || self.initialized.contains(&local)
+    }
+}
+
+/// Drops the retains and releases of locals that hold null, which the runtime ignores.
+/// A local holds null at the start of a block if it does on every path there.
+fn release_known_null(blocks: &mut [QIRBasicBlock]) {
+    let states = forward(
+        blocks,
+        HashSet::new(),
+        |null, other| {
+            let before = null.len();
+            null.retain(|local| other.contains(local));
+            null.len() != before
+        },
+        |block, null| {
+            let mut null = null.clone();
+            block.expressions().for_each(|expr| track_nulls(expr, &mut null));
+            null
+        },
+    );
+    for (block, null) in blocks.iter_mut().zip(states) {
+        let Some(mut null) = null else {
+            continue;
+        };
+        for expr in std::mem::take(&mut block.exprs) {
+            if let QIRExpression::Retain { ptr } | QIRExpression::Release { ptr } = &expr {
+                if loaded_local(ptr).is_some_and(|local| null.contains(&local)) {
+                    continue;
+                }
+            }
+            track_nulls(&expr, &mut null);
+            block.exprs.push(expr);
+        }
+    }
+}
+
+/// Updates which locals hold null after `expr` ran.
+fn track_nulls(expr: &QIRExpression, null: &mut HashSet<usize>) {
+    for child in expr.children() {
+        track_nulls(child, null);
+    }
+    if let QIRExpression::StoreToPtr {
+        receiver,
+        new_value,
+        ..
+    } = expr
+    {
+        if let QIRExpression::GetLocalPtr { local } = **receiver {
+            let stores_null = match &**new_value {
+                QIRExpression::Null => true,
+                value => loaded_local(value).is_some_and(|loaded| null.contains(&loaded)),
+            };
+            if stores_null {
+                null.insert(local.id);
+            } else {
+                null.remove(&local.id);
+            }
+        }
+    }
+}
+
+/// Removes stores of values without side effects to locals that are never read.
+fn remove_dead_stores(blocks: &mut [QIRBasicBlock]) {
+    let mut read = HashSet::new();
+    for block in blocks.iter() {
+        if let Some(QIRExpression::GetLocalPtr { local }) = block.terminator.operand() {
+            read.insert(local.id);
+        }
+        block.walk(&mut |expr| {
+            for child in expr.children() {
+                if let QIRExpression::LoadFromPtr { ptr, .. } = child {
+                    if let QIRExpression::GetLocalPtr { local } = **ptr {
+                        read.insert(local.id);
+                    }
+                } else if let QIRExpression::GetLocalPtr { local } = child {
+                    // Only a pointer that a store writes to doesn't read the local.
+                    if !matches!(expr, QIRExpression::StoreToPtr { receiver, .. } if std::ptr::eq(&**receiver, child)) {
+                        read.insert(local.id);
+                    }
+                }
+            }
+        });
+    }
+    for block in blocks {
+        block.exprs.retain(|expr| match expr {
+            QIRExpression::StoreToPtr {
+                receiver,
+                new_value,
+                ..
+            } => match **receiver {
+                QIRExpression::GetLocalPtr { local } => read.contains(&local.id) || !is_pure(new_value),
+                _ => true,
+            },
+            _ => true,
+        });
+    }
+}
+
+/// Keeps locals from holding a reference of their own when the object they hold stays
+/// alive until the function returns without them.
+///
+/// That is a local that is assigned once and never passes its value on, copying
+/// another local that holds the object for the whole function: a parameter that is
+/// never assigned, or a local assigned once outside of loops. Returns the locals
+/// objects are borrowed from this way.
+fn skip_non_escaping(blocks: &mut [QIRBasicBlock], owners: &Owners) -> HashSet<usize> {
+    // Removing dead stores can only have shortened the prologue.
+    let prologue = blocks[0]
+        .exprs
+        .iter()
+        .take(owners.prologue)
+        .take_while(|expr| {
+            matches!(expr, QIRExpression::StoreToPtr { new_value, .. } if matches!(**new_value, QIRExpression::Null))
+        })
+        .count();
+    let mut uses = HashMap::new();
+    for (label, block) in blocks.iter().enumerate() {
+        let in_loop = reachable_from(blocks, label)[label];
+        let skipped = if label == 0 { prologue } else { 0 };
+        for expr in block.expressions().skip(skipped) {
+            count_uses(expr, in_loop, &mut uses);
+        }
+    }
+    let owns_for_whole_function = |local: usize| {
+        let uses = uses.get(&local).cloned().unwrap_or_default();
+        if owners.parameters.contains(&local) {
+            uses.stores.is_empty()
+        } else {
+            owners.initialized.contains(&local) && uses.stores.len() == 1 && !uses.stored_in_loop
+        }
+    };
+    let borrowers = uses
+        .iter()
+        .filter_map(|(local, uses)| match uses.stores.as_slice() {
+            [Some(source)]
+                if owners.initialized.contains(local)
+                    && !uses.retained
+                    && !uses.escapes
+                    && source != local
+                    && owns_for_whole_function(*source) =>
+            {
+                Some((*local, *source))
+            }
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut skipped = vec![];
+    let mut borrowed = HashSet::new();
+    for block in blocks.iter_mut() {
+        let mut index = 1;
+        while index < block.exprs.len() {
+            let borrow = match (&block.exprs[index - 1], &block.exprs[index]) {
+                (
+                    QIRExpression::Retain { ptr },
+                    QIRExpression::StoreToPtr {
+                        receiver,
+                        new_value,
+                        ..
+                    },
+                ) => match **receiver {
+                    QIRExpression::GetLocalPtr { local } => borrowers
+                        .get(&local.id)
+                        .filter(|source| {
+                            loaded_local(ptr) == Some(**source) && loaded_local(new_value) == Some(**source)
+                        })
+                        .map(|source| (local.id, *source)),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match borrow {
+                Some((local, source)) => {
+                    block.exprs.remove(index - 1);
+                    skipped.push(local);
+                    borrowed.insert(source);
+                }
+                None => index += 1,
+            }
+        }
+    }
+    for local in skipped {
+        blocks.iter_mut().for_each(|block| remove_releases(local, block));
+    }
+    borrowed
+}
+
+/// How the code of a function uses a local.
+#[derive(Clone, Default)]
+struct LocalUses {
+    /// The local each store to it copies, or `None` for other values.
+    stores: Vec<Option<usize>>,
+    stored_in_loop: bool,
+    /// Whether its value is retained, to be passed on.
+    retained: bool,
+    /// Whether its address is used other than to load from or store to it.
+    escapes: bool,
+}
+
+fn count_uses(expr: &QIRExpression, in_loop: bool, uses: &mut HashMap<usize, LocalUses>) {
+    match expr {
+        QIRExpression::StoreToPtr {
+            receiver,
+            new_value,
+            ..
+        } => {
+            match **receiver {
+                QIRExpression::GetLocalPtr { local } => {
+                    let local = uses.entry(local.id).or_default();
+                    local.stores.push(loaded_local(new_value));
+                    local.stored_in_loop |= in_loop;
+                }
+                _ => count_uses(receiver, in_loop, uses),
+            }
+            count_uses(new_value, in_loop, uses);
+        }
+        QIRExpression::LoadFromPtr { ptr, .. } if matches!(**ptr, QIRExpression::GetLocalPtr { .. }) => {}
+        QIRExpression::Retain { ptr } => {
+            if let Some(local) = loaded_local(ptr) {
+                uses.entry(local).or_default().retained = true;
+            }
+            count_uses(ptr, in_loop, uses);
+        }
+        QIRExpression::GetLocalPtr { local } => uses.entry(local.id).or_default().escapes = true,
+        other => {
+            for child in other.children() {
+                count_uses(child, in_loop, uses);
+            }
+        }
+    }
+}
+
+/// Removes a retain of a local that is released again before anything in between
+/// could release the object or change the local.
+fn cancel_pairs(block: &mut QIRBasicBlock) {
+    let mut index = 0;
+    while index < block.exprs.len() {
+        let retained = match &block.exprs[index] {
+            QIRExpression::Retain { ptr } => loaded_local(ptr),
+            _ => None,
+        };
+        let release = retained.and_then(|local| {
+            block.exprs[index + 1..]
+                .iter()
+                .take_while(|expr| !is_release_of(expr, local))
+                .all(|expr| is_neutral(expr, local))
+                .then(|| {
+                    block.exprs[index + 1..]
+                        .iter()
+                        .position(|expr| is_release_of(expr, local))
+                })
+                .flatten()
+        });
+        match release {
+            Some(offset) => {
+                block.exprs.remove(index + 1 + offset);
+                block.exprs.remove(index);
+            }
+            None => index += 1,
+        }
+    }
+}
+
+/// Hands the reference of a local over to where its value goes on its last use, for
+/// the retain before it and the release that comes after it.
+fn move_last_uses(blocks: &mut [QIRBasicBlock], owners: &Owners, borrowed: &HashSet<usize>) {
+    let predecessors = predecessors(blocks);
+    for label in 0..blocks.len() {
+        let mut index = 0;
+        while index < blocks[label].exprs.len() {
+            let retained = match &blocks[label].exprs[index] {
+                QIRExpression::Retain { ptr } => loaded_local(ptr)
+                    .filter(|local| owners.owns(*local) && !borrowed.contains(local)),
+                _ => None,
+            };
+            let moved = retained.is_some_and(|local| {
+                move_last_use(blocks, &predecessors, label, index, local, owners)
+            });
+            match moved {
+                true => {
+                    blocks[label].exprs.remove(index);
+                }
+                false => index += 1,
+            }
+        }
+    }
+}
+
+/// Drops the release that balances the retain of `local` at `retain` in the block
+/// `label`, if the next use of the local passes its value on and nothing uses it until
+/// that release. Returns whether the retain can go too.
+fn move_last_use(
+    blocks: &mut [QIRBasicBlock],
+    predecessors: &[Vec<Label>],
+    label: Label,
+    retain: usize,
+    local: usize,
+    owners: &Owners,
+) -> bool {
+    let block = &blocks[label];
+    let Some(offset) = block.exprs[retain + 1..]
+        .iter()
+        .position(|expr| references(expr, local) > 0)
+    else {
+        // A return stores the returned value and releases the locals, in a block that
+        // only the returning block branches to.
+        let QIRTerminator::Br { target } = block.terminator else {
+            return false;
+        };
+        if predecessors[target] != [label] || !is_return_sequence(&blocks[target]) {
+            return false;
+        }
+        let returned = &mut blocks[target];
+        let returns_local = matches!(
+            returned.exprs.first(),
+            Some(QIRExpression::StoreToPtr { new_value, .. }) if loaded_local(new_value) == Some(local)
+        );
+        let uses = returned
+            .expressions()
+            .skip(1)
+            .filter(|expr| references(expr, local) > 0)
+            .count();
+        if returns_local && uses == 1 {
+            remove_releases(local, returned);
+            return true;
+        }
+        return false;
+    };
+    let consumer = retain + 1 + offset;
+
+    // Otherwise only a store to something that owns its value can take it, a store
+    // never frees an object.
+    let QIRExpression::StoreToPtr {
+        receiver,
+        new_value,
+        ..
+    } = &block.exprs[consumer]
+    else {
+        return false;
+    };
+    let simple_receiver = match &**receiver {
+        QIRExpression::GetLocalPtr { local: stored } => stored.id != local && owners.owns(stored.id),
+        QIRExpression::GetFieldPtr { receiver, .. } => {
+            loaded_local(receiver).is_some_and(|object| object != local)
+        }
+        _ => false,
+    };
+    if !simple_receiver || loaded_local(new_value) != Some(local) {
+        return false;
+    }
+
+    let rest = consumer + 1;
+    let next_use = block
+        .expressions()
+        .skip(rest)
+        .position(|expr| references(expr, local) > 0);
+    match next_use {
+        // The local is overwritten: the value it held before is released.
+        Some(offset) => {
+            let overwrite = rest + offset;
+            let Some(
+                [QIRExpression::StoreToPtr {
+                    receiver: snapshot,
+                    new_value: previous,
+                    ..
+                }, QIRExpression::StoreToPtr { receiver, .. }, QIRExpression::Release { ptr }],
+            ) = block.exprs.get(overwrite..overwrite + 3)
+            else {
+                return false;
+            };
+            let snapshot = match **snapshot {
+                QIRExpression::GetLocalPtr { local } => local.id,
+                _ => return false,
+            };
+            let overwritten = matches!(**receiver, QIRExpression::GetLocalPtr { local: stored } if stored.id == local);
+            if loaded_local(previous) != Some(local) || !overwritten || loaded_local(ptr) != Some(snapshot) {
+                return false;
+            }
+            blocks[label].exprs.remove(overwrite + 2);
+            true
+        }
+        // Nothing uses the local again in the block. If the blocks that follow can only
+        // be reached through it, and only their returns release the local, that is the
+        // last thing that happens to it.
+        None => {
+            let after = reachable_from(blocks, label);
+            if after[label] {
+                return false;
+            }
+            let entered_elsewhere = (0..blocks.len())
+                .filter(|successor| after[*successor])
+                .any(|successor| {
+                    predecessors[successor]
+                        .iter()
+                        .any(|predecessor| *predecessor != label && !after[*predecessor])
+                });
+            if entered_elsewhere {
+                return false;
+            }
+            let mut releases = 0;
+            let mut references_after = 0;
+            for (label, block) in blocks.iter().enumerate() {
+                if !after[label] {
+                    continue;
+                }
+                if is_return_sequence(block) {
+                    releases += block.exprs.iter().filter(|expr| is_release_of(expr, local)).count();
+                }
+                references_after += block.expressions().map(|expr| references(expr, local)).sum::<usize>();
+            }
+            if releases != references_after {
+                return false;
+            }
+            for (label, block) in blocks.iter_mut().enumerate() {
+                if after[label] && is_return_sequence(block) {
+                    remove_releases(local, block);
+                }
+            }
+            true
+        }
+    }
+}
+
+/// Whether a block is a return that releases the locals of the function, as
+/// `insert_refcounts` builds it.
+fn is_return_sequence(block: &QIRBasicBlock) -> bool {
+    if !matches!(block.terminator, QIRTerminator::Ret { .. }) {
+        return false;
+    }
+    let releases = match block.exprs.first() {
+        Some(QIRExpression::StoreToPtr { .. }) => &block.exprs[1..],
+        _ => &block.exprs[..],
+    };
+    releases
+        .iter()
+        .all(|expr| matches!(expr, QIRExpression::Release { ptr } if loaded_local(ptr).is_some()))
+}
+
+/// Removes the releases of `local` from a block.
+fn remove_releases(local: usize, block: &mut QIRBasicBlock) {
+    block.exprs.retain(|expr| !is_release_of(expr, local));
+}
+
+/// How many times an expression refers to a local.
+fn references(expr: &QIRExpression, local: usize) -> usize {
+    let mut count = 0;
+    expr.walk(&mut |expr| {
+        if matches!(expr, QIRExpression::GetLocalPtr { local: used } if used.id == local) {
+            count += 1;
+        }
+    });
+    count
+}
+
+/// The local an expression loads, if it is a load of a local.
+fn loaded_local(expr: &QIRExpression) -> Option<usize> {
+    match expr {
+        QIRExpression::LoadFromPtr { ptr, .. } => match **ptr {
+            QIRExpression::GetLocalPtr { local } => Some(local.id),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_release_of(expr: &QIRExpression, local: usize) -> bool {
+    matches!(expr, QIRExpression::Release { ptr } if loaded_local(ptr) == Some(local))
+}
+
+/// Whether an expression can neither free an object nor use `local`.
+fn is_neutral(expr: &QIRExpression, local: usize) -> bool {
+    let mut neutral = references(expr, local) == 0;
+    expr.walk(&mut |expr| match expr {
+        QIRExpression::Invoke { .. }
+        | QIRExpression::InvokeClosure { .. }
+        | QIRExpression::InvokeDynamic { .. }
+        | QIRExpression::Release { .. } => neutral = false,
+        _ => {}
+    });
+    neutral
+}
+
+/// Whether computing an expression has no effect besides its value.
+fn is_pure(expr: &QIRExpression) -> bool {
+    let mut pure = true;
+    expr.walk(&mut |expr| {
+        pure &= matches!(
+            expr,
+            QIRExpression::Integer { .. }
+                | QIRExpression::Float { .. }
+                | QIRExpression::Bool { .. }
+                | QIRExpression::Null
+                | QIRExpression::GetFunctionPtr { .. }
+                | QIRExpression::GetLocalPtr { .. }
+                | QIRExpression::GetGlobalPtr { .. }
+                | QIRExpression::GetFieldPtr { .. }
+                | QIRExpression::LoadFromPtr { .. }
+        )
+    });
+    pure
+}
@@ -0,0 +1,438 @@
+use crate::backend::qir::structs::{
+    QIRBasicBlock, QIRExpression, QIRHeader, QIRLocalVariable, QIRTerminator, QIRType,
+};
+
+/// Inserts the retains and releases that keep the reference count of every object
+/// equal to the number of references to it.
+///
+/// A function owns a reference to the values of its parameters and of every local
+/// holding an object, and releases them when it returns. Values returned by calls
+/// and new objects are owned by whoever receives them, values loaded from memory are
+/// borrowed, so they are retained when stored or passed on. Storing to a location
+/// releases the value it held before.
+pub(crate) fn insert_refcounts(header: &mut QIRHeader) {
+    let QIRHeader::Function {
+        parameters,
+        locals,
+        blocks,
+        ..
+    } = header
+    else {
+        return;
+    };
+    let mut pass = RefcountInsertion {
+        untracked: vec![false; locals.len()],
+        locals: std::mem::take(locals),
+    };
+    blocks.iter_mut().for_each(|block| pass.transform_block(block));
+
+    let owned = (0..pass.locals.len())
+        .filter(|local| pass.locals[*local].is_refcounted() && !pass.untracked[*local])
+        .collect::<Vec<_>>();
+    pass.release_on_return(blocks, &owned);
+
+    // Locals start out null, so the first store has nothing to release. Nothing
+    // branches back to the entry, so this runs once.
+    let prologue = owned
+        .iter()
+        .filter(|local| parameters.iter().all(|(parameter, _)| parameter.id != **local))
+        .map(|local| QIRExpression::StoreToPtr {
+            receiver: Box::new(local_ptr(*local)),
+            output_type: pass.locals[*local].clone(),
+            new_value: Box::new(QIRExpression::Null),
+        })
+        .collect::<Vec<_>>();
+    blocks[0].exprs.splice(0..0, prologue);
+    *locals = pass.locals;
+}
+
+/// What the expression a value is computed for does with it.
+#[derive(Clone, Copy, PartialEq)]
+enum Use {
+    /// The value is stored or passed to a function, which takes over a reference to it.
+    Consume,
+    /// The value is only read while the expression runs.
+    Borrow,
+    /// Nothing uses the value.
+    Discard,
+}
+
+/// Whether the code computing a value has to give up a reference to it.
+#[derive(Clone, Copy, PartialEq)]
+enum Ownership {
+    /// A reference that has to be released or handed on, like a new object.
+    Owned,
+    /// A reference that someone else holds, like a value loaded from a local.
+    Borrowed,
+    /// The value holds no reference.
+    Unowned,
+}
+
+struct RefcountInsertion {
+    locals: Vec<QIRType>,
+    /// Temporaries of the pass that never own what they hold, indexed like `locals`.
+    untracked: Vec<bool>,
+}
+
+impl RefcountInsertion {
+    /// Transforms the expressions of a block, whose values are discarded, and the
+    /// operand of its terminator: a condition is only read, a returned value is handed
+    /// to the caller.
+    fn transform_block(&mut self, block: &mut QIRBasicBlock) {
+        let mut exprs = vec![];
+        for expr in std::mem::take(&mut block.exprs) {
+            let value = self.transform(expr, Use::Discard, &mut exprs);
+            exprs.push(value);
+        }
+        let usage = match block.terminator {
+            QIRTerminator::Ret { .. } => Use::Consume,
+            _ => Use::Borrow,
+        };
+        if let Some(operand) = block.terminator.operand_mut() {
+            let value = std::mem::replace(operand, QIRExpression::Null);
+            *operand = self.transform(value, usage, &mut exprs);
+        }
+        block.exprs = exprs;
+    }
+
+    /// Transforms an expression and adapts its value to `usage`, pushing what has to
+    /// run before it to `out`.
+    fn transform(&mut self, expr: QIRExpression, usage: Use, out: &mut Vec<QIRExpression>) -> QIRExpression {
+        let (expr, ownership) = self.transform_value(expr, out);
+        match (ownership, usage) {
+            (Ownership::Owned, Use::Discard) => QIRExpression::Release {
+                ptr: Box::new(expr),
+            },
+            (Ownership::Owned, Use::Borrow) => {
+                let local = self.hold(expr, out);
+                self.load(local)
+            }
+            (Ownership::Borrowed, Use::Consume) => {
+                let value = self.stabilize(expr, out);
+                out.push(QIRExpression::Retain {
+                    ptr: Box::new(value.clone()),
+                });
+                value
+            }
+            _ => expr,
+        }
+    }
+
+    fn transform_value(&mut self, expr: QIRExpression, out: &mut Vec<QIRExpression>) -> (QIRExpression, Ownership) {
+        match expr {
+            QIRExpression::Retain { ptr } => {
+                let ptr = Box::new(self.transform(*ptr, Use::Borrow, out));
+                (QIRExpression::Retain { ptr }, Ownership::Unowned)
+            }
+            QIRExpression::Release { ptr } => {
+                let ptr = Box::new(self.transform(*ptr, Use::Borrow, out));
+                (QIRExpression::Release { ptr }, Ownership::Unowned)
+            }
+            QIRExpression::Invoke {
+                name,
+                arguments,
+                return_type,
+            } => {
+                let operands = arguments
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, argument)| (argument, argument_use(&name.name, index)))
+                    .collect();
+                let arguments = self.transform_operands(operands, out);
+                let ownership = match name.name.as_str() {
+                    _ if !return_type.is_refcounted() => Ownership::Unowned,
+                    // The element stays in the array.
+                    "qre::array::get" => Ownership::Borrowed,
+                    _ => Ownership::Owned,
+                };
+                let invoke = QIRExpression::Invoke {
+                    name,
+                    arguments,
+                    return_type,
+                };
+                (invoke, ownership)
+            }
+            QIRExpression::InvokeClosure {
+                closure,
+                arguments,
+                return_type,
+            } => {
+                let operands = std::iter::once(*closure)
+                    .chain(arguments)
+                    .map(|operand| (operand, Use::Consume))
+                    .collect();
+                let mut arguments = self.transform_operands(operands, out);
+                let closure = Box::new(arguments.remove(0));
+                let ownership = returned(&return_type);
+                let invoke = QIRExpression::InvokeClosure {
+                    closure,
+                    arguments,
+                    return_type,
+                };
+                (invoke, ownership)
+            }
+            QIRExpression::InvokeDynamic {
+                interface,
+                method,
+                arguments,
+                return_type,
+            } => {
+                let operands = arguments
+                    .into_iter()
+                    .map(|argument| (argument, Use::Consume))
+                    .collect();
+                let arguments = self.transform_operands(operands, out);
+                let ownership = returned(&return_type);
+                let invoke = QIRExpression::InvokeDynamic {
+                    interface,
+                    method,
+                    arguments,
+                    return_type,
+                };
+                (invoke, ownership)
+            }
+            QIRExpression::String { .. } | QIRExpression::InstantiateStructure { .. } => {
+                (expr, Ownership::Owned)
+            }
+            QIRExpression::GetFunctionPtr { .. }
+            | QIRExpression::Integer { .. }
+            | QIRExpression::Float { .. }
+            | QIRExpression::Bool { .. }
+            | QIRExpression::Null
+            | QIRExpression::GetLocalPtr { .. }
+            | QIRExpression::GetGlobalPtr { .. } => (expr, Ownership::Unowned),
+            QIRExpression::StoreToPtr {
+                receiver,
+                output_type,
+                new_value,
+            } => {
+                let store = self.transform_store(*receiver, output_type, *new_value, out);
+                (store, Ownership::Unowned)
+            }
+            QIRExpression::GetFieldPtr {
+                receiver,
+                ptr_type,
+                output_type,
+                field,
+            } => {
+                let receiver = Box::new(self.transform(*receiver, Use::Borrow, out));
+                let field_ptr = QIRExpression::GetFieldPtr {
+                    receiver,
+                    ptr_type,
+                    output_type,
+                    field,
+                };
+                (field_ptr, Ownership::Unowned)
+            }
+            QIRExpression::LoadFromPtr { ptr, output_type } => {
+                let ptr = Box::new(self.transform(*ptr, Use::Borrow, out));
+                let ownership = if output_type.is_refcounted() {
+                    Ownership::Borrowed
+                } else {
+                    Ownership::Unowned
+                };
+                (QIRExpression::LoadFromPtr { ptr, output_type }, ownership)
+            }
+        }
+    }
+
+    /// Transforms operands that are evaluated from left to right.
+    ///
+    /// An operand is stored to a temporary when a later one pushes expressions to
+    /// `out`, so it is still computed before them.
+    fn transform_operands(
+        &mut self,
+        operands: Vec<(QIRExpression, Use)>,
+        out: &mut Vec<QIRExpression>,
+    ) -> Vec<QIRExpression> {
+        let mut values: Vec<QIRExpression> = vec![];
+        for (operand, usage) in operands {
+            let mut code = vec![];
+            let value = self.transform(operand, usage, &mut code);
+            if !code.is_empty() {
+                for earlier in values.iter_mut() {
+                    let computed = std::mem::replace(earlier, QIRExpression::Null);
+                    *earlier = self.stabilize(computed, out);
+                }
+                out.extend(code);
+            }
+            values.push(value);
+        }
+        values
+    }
+
+    fn transform_store(
+        &mut self,
+        receiver: QIRExpression,
+        output_type: QIRType,
+        new_value: QIRExpression,
+        out: &mut Vec<QIRExpression>,
+    ) -> QIRExpression {
+        if !output_type.is_refcounted() {
+            let operands = vec![(receiver, Use::Borrow), (new_value, Use::Borrow)];
+            let mut values = self.transform_operands(operands, out).into_iter();
+            let (Some(receiver), Some(new_value)) = (values.next(), values.next()) else {
+                unreachable!("a store has a receiver and a value");
+            };
+            return QIRExpression::StoreToPtr {
+                receiver: Box::new(receiver),
+                output_type,
+                new_value: Box::new(new_value),
+            };
+        }
+
+        // The receiver is computed again to load the value it held before, after the
+        // new value is computed, which can store to it too.
+        let receiver = self.transform(receiver, Use::Borrow, out);
+        let receiver = match receiver {
+            QIRExpression::GetFieldPtr {
+                receiver: object,
+                ptr_type,
+                output_type,
+                field,
+            } => QIRExpression::GetFieldPtr {
+                receiver: Box::new(self.stabilize(*object, out)),
+                ptr_type,
+                output_type,
+                field,
+            },
+            other => self.stabilize(other, out),
+        };
+        let new_value = self.transform(new_value, Use::Consume, out);
+        let new_value = self.stabilize(new_value, out);
+        let previous = QIRExpression::LoadFromPtr {
+            ptr: Box::new(receiver.clone()),
+            output_type: output_type.clone(),
+        };
+        let previous = self.snapshot(previous, out);
+        out.push(QIRExpression::StoreToPtr {
+            receiver: Box::new(receiver),
+            output_type,
+            new_value: Box::new(new_value),
+        });
+        QIRExpression::Release {
+            ptr: Box::new(self.load(previous)),
+        }
+    }
+
+    /// Returns an expression yielding the value of `expr` that can be computed again,
+    /// storing it to a temporary if it could yield something else the next time.
+    fn stabilize(&mut self, expr: QIRExpression, out: &mut Vec<QIRExpression>) -> QIRExpression {
+        if is_stable(&expr) {
+            return expr;
+        }
+        let local = self.snapshot(expr, out);
+        self.load(local)
+    }
+
+    /// Stores a value to a new temporary that doesn't own it.
+    fn snapshot(&mut self, value: QIRExpression, out: &mut Vec<QIRExpression>) -> usize {
+        self.locals.push(value.value_type());
+        self.untracked.push(true);
+        let local = self.locals.len() - 1;
+        out.push(self.store(local, value));
+        local
+    }
+
+    /// Stores an owned value to a new temporary, which releases it when it is
+    /// overwritten or the function returns.
+    fn hold(&mut self, value: QIRExpression, out: &mut Vec<QIRExpression>) -> usize {
+        self.locals.push(value.value_type());
+        self.untracked.push(false);
+        let local = self.locals.len() - 1;
+        // The temporary still holds the value of the last time this ran in a loop.
+        out.push(QIRExpression::Release {
+            ptr: Box::new(self.load(local)),
+        });
+        out.push(self.store(local, value));
+        local
+    }
+
+    /// Releases the locals in `owned` before every return, after the returned value
+    /// is computed. Each return moves to a block of its own that the block returning
+    /// before branches to, which `eliminate_refcounts` recognizes.
+    fn release_on_return(&mut self, blocks: &mut Vec<QIRBasicBlock>, owned: &[usize]) {
+        if owned.is_empty() {
+            return;
+        }
+        for label in 0..blocks.len() {
+            let target = blocks.len();
+            let QIRTerminator::Ret { value } = &mut blocks[label].terminator else {
+                continue;
+            };
+            let mut exprs = vec![];
+            let value = value.take().map(|value| {
+                let local = self.snapshot(value, &mut exprs);
+                self.load(local)
+            });
+            exprs.extend(owned.iter().map(|local| QIRExpression::Release {
+                ptr: Box::new(self.load(*local)),
+            }));
+            blocks[label].terminator = QIRTerminator::Br { target };
+            blocks.push(QIRBasicBlock {
+                exprs,
+                terminator: QIRTerminator::Ret { value },
+            });
+        }
+    }
+
+    fn load(&self, local: usize) -> QIRExpression {
+        QIRExpression::LoadFromPtr {
+            ptr: Box::new(local_ptr(local)),
+            output_type: self.locals[local].clone(),
+        }
+    }
+
+    fn store(&self, local: usize, value: QIRExpression) -> QIRExpression {
+        QIRExpression::StoreToPtr {
+            receiver: Box::new(local_ptr(local)),
+            output_type: self.locals[local].clone(),
+            new_value: Box::new(value),
+        }
+    }
+}
+
+/// Whether an argument of `function` takes over the reference to its value.
+///
+/// Functions own their parameters. Intrinsics only read their arguments, except
+/// for the elements they store into an array.
+fn argument_use(function: &str, index: usize) -> Use {
+    match (function, index) {
+        ("qre::array::push", 1) | ("qre::array::set" | "qre::array::insert", 2) => Use::Consume,
+        _ if function.starts_with("qre::") => Use::Borrow,
+        _ => Use::Consume,
+    }
+}
+
+/// The ownership of a value returned by a function, which hands over its reference.
+fn returned(return_type: &QIRType) -> Ownership {
+    if return_type.is_refcounted() {
+        Ownership::Owned
+    } else {
+        Ownership::Unowned
+    }
+}
+
+/// Whether computing the expression again yields the same value, even after the
+/// code pushed before a later operand ran.
+fn is_stable(expr: &QIRExpression) -> bool {
+    match expr {
+        QIRExpression::Integer { .. }
+        | QIRExpression::Float { .. }
+        | QIRExpression::Bool { .. }
+        | QIRExpression::Null
+        | QIRExpression::GetFunctionPtr { .. }
+        | QIRExpression::GetLocalPtr { .. }
+        | QIRExpression::GetGlobalPtr { .. } => true,
+        QIRExpression::LoadFromPtr { ptr, .. } => {
+            matches!(**ptr, QIRExpression::GetLocalPtr { .. })
+        }
+        _ => false,
+    }
+}
+
+fn local_ptr(local: usize) -> QIRExpression {
+    QIRExpression::GetLocalPtr {
+        local: QIRLocalVariable { id: local },
+    }
+}
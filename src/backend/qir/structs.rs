@@ -8,23 +8,72 @@ pub enum QIRHeader {
         name: PathData,
         parameters: Vec<(QIRLocalVariable, QIRType)>,
         returns: QIRType,
-        code_block: QIRBasicBlock,
+        /// The type of every local of the function, indexed by `QIRLocalVariable::id`.
+        /// Parameters are locals too, their values are stored to them on entry.
+        locals: Vec<QIRType>,
+        /// The code of the function, starting with the block labelled 0.
+        blocks: Vec<QIRBasicBlock>,
     },
     /// A very low level interface.
     ///
     /// Field indexing is 0-based.
     ///
-    /// The struct must have 2 u32's at the start, for type ID and reference count specifically.
+    /// The struct must have 2 `QIRType::UInt32`s at the start, for type ID and reference count specifically.
     Struct {
         name: PathData,
         fields: Vec<QIRType>,
     },
+    /// The implementations of an interface's methods, in the order the interface
+    /// declares them, for each type ID of a struct implementing it.
+    VTable {
+        interface: PathData,
+        entries: Vec<(u32, Vec<PathData>)>,
+    },
+    /// The function that releases what an object of each type ID holds, called by the
+    /// runtime before it frees an object whose reference count dropped to 0.
+    ///
+    /// Types without an entry hold no references.
+    ///
+    /// `tracers` hold the function that visits the references an object of each type
+    /// ID holds, for the cycle collector. Objects of types without one are never
+    /// looked into.
+    Destructors {
+        entries: Vec<(u32, PathData)>,
+        tracers: Vec<(u32, PathData)>,
+    },
 }
 
+/// The label of a block, its index in the blocks of its function.
+pub type Label = usize;
+
+/// Expressions that run one after another, and the terminator that decides where
+/// control goes after them.
+#[derive(Clone)]
 pub struct QIRBasicBlock {
     pub(crate) exprs: Vec<QIRExpression>,
+    pub(crate) terminator: QIRTerminator,
 }
 
+#[derive(Clone)]
+pub enum QIRTerminator {
+    /// Continues with the block `target`.
+    Br { target: Label },
+    /// Continues with `if_true` when `condition` yields true, and with `if_false` otherwise.
+    ///
+    /// `?` branches on the type ID of its value, to a block that returns it when it is an `Err`.
+    CondBr {
+        condition: QIRExpression,
+        if_true: Label,
+        if_false: Label,
+    },
+    /// Returns from the function.
+    Ret { value: Option<QIRExpression> },
+    /// A point the typechecker proved can't be reached, like the end of a match that
+    /// no arm matched, or the code after a panic.
+    Unreachable,
+}
+
+#[derive(Clone)]
 pub enum QIRExpression {
     /// Returns the new reference count.
     /// Increments the reference count by 1.
@@ -41,8 +90,39 @@ pub enum QIRExpression {
         arguments: Vec<QIRExpression>,
         return_type: QIRType,
     },
+    /// Calls a closure object, a Ptr to a structure holding a function pointer in field 2,
+    /// the function releasing its captures in field 3 and the captured values after it.
+    ///
+    /// The closure object is passed to the function before `arguments`, so it can load its captures.
+    InvokeClosure {
+        closure: Box<QIRExpression>,
+        arguments: Vec<QIRExpression>,
+        return_type: QIRType,
+    },
+    /// Always yields a Ptr value, pointing to a function.
+    GetFunctionPtr { function: PathData },
+    /// Calls a method of an interface on `arguments[0]`, which must be a Ptr to a structure.
+    ///
+    /// The implementation is looked up in the interface's `VTable` by the type ID of the structure.
+    InvokeDynamic {
+        interface: PathData,
+        /// Index of the method in the interface's declaration order.
+        method: u32,
+        arguments: Vec<QIRExpression>,
+        return_type: QIRType,
+    },
+    /// Integer constants, `ty` is one of the integer types.
+    Integer { value: i128, ty: QIRType },
+    Float { value: f64, ty: QIRType },
+    Bool { value: bool },
+    /// Always yields a Ptr value, pointing to a new string object holding `value`.
+    String { value: String, type_id: u32 },
+    /// Always yields a Ptr value that points nowhere, the value of `none`.
+    Null,
     /// Always creates a QIRType::Ptr type value
-    InstantiateStructure { size: i32 },
+    ///
+    /// The type ID and reference count are left for the caller to store.
+    InstantiateStructure { ptr_type: Vec<QIRType> },
     /// Stores a value to a Ptr
     StoreToPtr {
         receiver: Box<QIRExpression>,
@@ -62,15 +142,9 @@ pub enum QIRExpression {
         field: i32,
     },
     /// Loads the value directly from a pointer.
-    LoadFromPtr { ptr: Box<QIRExpression> },
-    /// Executes the given basic block directly
-    GotoBlock { block: QIRBasicBlock },
-    /// Branch if a condition is true
-    BranchIf {
-        condition: Box<QIRExpression>,
-        if_true: QIRBasicBlock,
-        if_false: QIRBasicBlock,
-        continuation: QIRBasicBlock,
+    LoadFromPtr {
+        ptr: Box<QIRExpression>,
+        output_type: QIRType,
     },
     /// Always yields a Ptr value, pointing to a local variable.
     GetLocalPtr { local: QIRLocalVariable },
@@ -78,13 +152,27 @@ pub enum QIRExpression {
     GetGlobalPtr { global: PathData },
 }
 
-pub struct QIRLocalVariable {}
+#[derive(Clone, Copy)]
+pub struct QIRLocalVariable {
+    pub(crate) id: usize,
+}
 
+#[derive(Clone, PartialEq)]
 pub enum QIRType {
+    Int8,
+    Int16,
     Int32,
     Int64,
+    /// Unsigned integers share their representation with the signed ones, but select
+    /// the unsigned forms of division, remainder, comparison, right shifts and overflow checks.
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    USize,
     Float32,
     Float64,
+    Bool,
     Ptr,
     Void,
     Invalid,
@@ -0,0 +1,160 @@
+use crate::backend::qir::structs::{Label, QIRBasicBlock, QIRExpression, QIRTerminator, QIRType};
+
+impl QIRExpression {
+    /// The type of the value the expression yields.
+    pub fn value_type(&self) -> QIRType {
+        match self {
+            QIRExpression::Retain { .. } | QIRExpression::Release { .. } => QIRType::UInt32,
+            QIRExpression::Invoke { return_type, .. }
+            | QIRExpression::InvokeClosure { return_type, .. }
+            | QIRExpression::InvokeDynamic { return_type, .. } => return_type.clone(),
+            QIRExpression::Integer { ty, .. } | QIRExpression::Float { ty, .. } => ty.clone(),
+            QIRExpression::Bool { .. } => QIRType::Bool,
+            QIRExpression::GetFunctionPtr { .. }
+            | QIRExpression::String { .. }
+            | QIRExpression::Null
+            | QIRExpression::InstantiateStructure { .. }
+            | QIRExpression::GetFieldPtr { .. }
+            | QIRExpression::GetLocalPtr { .. }
+            | QIRExpression::GetGlobalPtr { .. } => QIRType::Ptr,
+            QIRExpression::LoadFromPtr { output_type, .. } => output_type.clone(),
+            QIRExpression::StoreToPtr { .. } => QIRType::Void,
+        }
+    }
+
+    /// The expressions directly nested in this one, in the order they run.
+    pub fn children(&self) -> Vec<&QIRExpression> {
+        match self {
+            QIRExpression::Retain { ptr }
+            | QIRExpression::Release { ptr }
+            | QIRExpression::LoadFromPtr { ptr, .. } => vec![ptr],
+            QIRExpression::Invoke { arguments, .. } | QIRExpression::InvokeDynamic { arguments, .. } => {
+                arguments.iter().collect()
+            }
+            QIRExpression::InvokeClosure {
+                closure, arguments, ..
+            } => std::iter::once(&**closure).chain(arguments).collect(),
+            QIRExpression::StoreToPtr {
+                receiver,
+                new_value,
+                ..
+            } => vec![receiver, new_value],
+            QIRExpression::GetFieldPtr { receiver, .. } => vec![receiver],
+            QIRExpression::GetFunctionPtr { .. }
+            | QIRExpression::Integer { .. }
+            | QIRExpression::Float { .. }
+            | QIRExpression::Bool { .. }
+            | QIRExpression::String { .. }
+            | QIRExpression::Null
+            | QIRExpression::InstantiateStructure { .. }
+            | QIRExpression::GetLocalPtr { .. }
+            | QIRExpression::GetGlobalPtr { .. } => vec![],
+        }
+    }
+
+    /// Like [`QIRExpression::children`], for changing them.
+    pub fn children_mut(&mut self) -> Vec<&mut QIRExpression> {
+        match self {
+            QIRExpression::Retain { ptr }
+            | QIRExpression::Release { ptr }
+            | QIRExpression::LoadFromPtr { ptr, .. } => vec![ptr],
+            QIRExpression::Invoke { arguments, .. } | QIRExpression::InvokeDynamic { arguments, .. } => {
+                arguments.iter_mut().collect()
+            }
+            QIRExpression::InvokeClosure {
+                closure, arguments, ..
+            } => std::iter::once(&mut **closure).chain(arguments).collect(),
+            QIRExpression::StoreToPtr {
+                receiver,
+                new_value,
+                ..
+            } => vec![receiver, new_value],
+            QIRExpression::GetFieldPtr { receiver, .. } => vec![receiver],
+            QIRExpression::GetFunctionPtr { .. }
+            | QIRExpression::Integer { .. }
+            | QIRExpression::Float { .. }
+            | QIRExpression::Bool { .. }
+            | QIRExpression::String { .. }
+            | QIRExpression::Null
+            | QIRExpression::InstantiateStructure { .. }
+            | QIRExpression::GetLocalPtr { .. }
+            | QIRExpression::GetGlobalPtr { .. } => vec![],
+        }
+    }
+
+    /// Calls `visit` on every expression nested in this one and then on this one,
+    /// innermost first.
+    pub fn walk(&self, visit: &mut impl FnMut(&QIRExpression)) {
+        for child in self.children() {
+            child.walk(visit);
+        }
+        visit(self);
+    }
+
+    /// Like [`QIRExpression::walk`], for changing the expressions.
+    pub fn walk_mut(&mut self, visit: &mut impl FnMut(&mut QIRExpression)) {
+        for child in self.children_mut() {
+            child.walk_mut(visit);
+        }
+        visit(self);
+    }
+}
+
+impl QIRBasicBlock {
+    /// The expressions of the block and the operand of its terminator, in the order
+    /// they run.
+    pub fn expressions(&self) -> impl Iterator<Item = &QIRExpression> {
+        self.exprs.iter().chain(self.terminator.operand())
+    }
+
+    pub fn walk(&self, visit: &mut impl FnMut(&QIRExpression)) {
+        self.expressions().for_each(|expr| expr.walk(visit));
+    }
+
+    pub fn walk_mut(&mut self, visit: &mut impl FnMut(&mut QIRExpression)) {
+        self.exprs.iter_mut().for_each(|expr| expr.walk_mut(visit));
+        if let Some(operand) = self.terminator.operand_mut() {
+            operand.walk_mut(visit);
+        }
+    }
+}
+
+impl QIRTerminator {
+    /// The condition of a `CondBr` or the value of a `Ret`.
+    pub fn operand(&self) -> Option<&QIRExpression> {
+        match self {
+            QIRTerminator::CondBr { condition, .. } => Some(condition),
+            QIRTerminator::Ret { value } => value.as_ref(),
+            QIRTerminator::Br { .. } | QIRTerminator::Unreachable => None,
+        }
+    }
+
+    pub fn operand_mut(&mut self) -> Option<&mut QIRExpression> {
+        match self {
+            QIRTerminator::CondBr { condition, .. } => Some(condition),
+            QIRTerminator::Ret { value } => value.as_mut(),
+            QIRTerminator::Br { .. } | QIRTerminator::Unreachable => None,
+        }
+    }
+
+    /// The blocks control can go to next.
+    pub fn successors(&self) -> Vec<Label> {
+        match self {
+            QIRTerminator::Br { target } => vec![*target],
+            QIRTerminator::CondBr {
+                if_true, if_false, ..
+            } => vec![*if_true, *if_false],
+            QIRTerminator::Ret { .. } | QIRTerminator::Unreachable => vec![],
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> Vec<&mut Label> {
+        match self {
+            QIRTerminator::Br { target } => vec![target],
+            QIRTerminator::CondBr {
+                if_true, if_false, ..
+            } => vec![if_true, if_false],
+            QIRTerminator::Ret { .. } | QIRTerminator::Unreachable => vec![],
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use crate::backend::qir::cfg::forward;
+use crate::backend::qir::print::Name;
+use crate::backend::qir::structs::{Label, QIRBasicBlock, QIRExpression, QIRHeader, QIRTerminator, QIRType};
+use std::collections::HashMap;
+
+/// Checks the invariants of QIR its types don't express, and returns a message for
+/// each place that breaks one:
+///
+/// - Structures start with the two `UInt32`s for the type ID and reference count,
+///   and `GetFieldPtr` only reaches the fields its structure has.
+/// - Values are stored, loaded, passed and returned as the type the other side
+///   expects. Unions are pointers to boxes, so `Ptr` and `Union` mix.
+/// - A load from a local comes after a store to it on every path that gets there,
+///   unless it is a parameter.
+/// - Functions have at least one block, and branch only to blocks they have other
+///   than the entry, so the entry runs once. Conditions are `Bool`s.
+pub(crate) fn verify_program(program: &[QIRHeader]) -> Vec<String> {
+    let mut functions = HashMap::new();
+    let mut globals = HashMap::new();
+    let mut errors = vec![];
+    for header in program {
+        match header {
+            QIRHeader::Function {
+                name,
+                parameters,
+                returns,
+                ..
+            } => {
+                let parameters = parameters.iter().map(|(_, ty)| ty.clone()).collect();
+                functions.insert(name.name.as_str(), (parameters, returns.clone()));
+            }
+            QIRHeader::GlobalVariable { name, ty } => {
+                globals.insert(name.name.as_str(), ty.clone());
+            }
+            QIRHeader::Struct { name, fields } if !has_header(fields) => errors.push(format!(
+                "struct {} does not start with the type ID and reference count",
+                Name(&name.name)
+            )),
+            _ => {}
+        }
+    }
+
+    for header in program {
+        let QIRHeader::Function {
+            name,
+            parameters,
+            returns,
+            locals,
+            blocks,
+        } = header
+        else {
+            continue;
+        };
+        let mut verifier = Verifier {
+            function: &name.name,
+            returns,
+            locals,
+            functions: &functions,
+            globals: &globals,
+            undefined: vec![],
+            errors: vec![],
+        };
+        let mut defined = vec![false; locals.len()];
+        for (local, ty) in parameters {
+            match locals.get(local.id) {
+                Some(local_ty) if compatible(local_ty, ty) => defined[local.id] = true,
+                Some(local_ty) => verifier.error(format!(
+                    "parameter %{} is {}, but the local holds {}",
+                    local.id, ty, local_ty
+                )),
+                None => verifier.error(format!("parameter %{} is not a local", local.id)),
+            }
+        }
+        let branches_to_blocks = blocks
+            .iter()
+            .flat_map(|block| block.terminator.successors())
+            .all(|target| target < blocks.len());
+        if blocks.is_empty() {
+            verifier.error("the function has no blocks".to_string());
+        } else if branches_to_blocks {
+            verifier.undefined = undefined_loads(blocks, defined);
+        }
+        for (label, block) in blocks.iter().enumerate() {
+            verifier.block(label, block, blocks.len());
+        }
+        errors.extend(verifier.errors);
+    }
+    errors
+}
+
+fn has_header(fields: &[QIRType]) -> bool {
+    fields.starts_with(&[QIRType::UInt32, QIRType::UInt32])
+}
+
+/// Whether a value of type `actual` can be used where `expected` is.
+fn compatible(expected: &QIRType, actual: &QIRType) -> bool {
+    expected == actual
+        || matches!(
+            (expected, actual),
+            (QIRType::Ptr | QIRType::Union, QIRType::Ptr | QIRType::Union)
+        )
+}
+
+struct Verifier<'a> {
+    function: &'a str,
+    returns: &'a QIRType,
+    locals: &'a [QIRType],
+    functions: &'a HashMap<&'a str, (Vec<QIRType>, QIRType)>,
+    globals: &'a HashMap<&'a str, QIRType>,
+    /// The loads from locals no store reaches, reported when the walk gets to them.
+    undefined: Vec<&'a QIRExpression>,
+    errors: Vec<String>,
+}
+
+/// The loads from locals that some path reaches without a store, following the
+/// branches between the blocks. `defined` holds the parameters.
+fn undefined_loads(blocks: &[QIRBasicBlock], defined: Vec<bool>) -> Vec<&QIRExpression> {
+    let states = forward(
+        blocks,
+        defined,
+        |state, incoming| {
+            let mut changed = false;
+            for (defined, incoming) in state.iter_mut().zip(incoming) {
+                changed |= *defined && !*incoming;
+                *defined &= incoming;
+            }
+            changed
+        },
+        |block, state| {
+            let mut state = state.clone();
+            block.expressions().for_each(|expr| stores(expr, &mut state, &mut vec![]));
+            state
+        },
+    );
+    let mut undefined = vec![];
+    for (block, state) in blocks.iter().zip(states) {
+        let Some(mut state) = state else {
+            continue;
+        };
+        block.expressions().for_each(|expr| stores(expr, &mut state, &mut undefined));
+    }
+    undefined
+}
+
+impl<'a> Verifier<'a> {
+    fn error(&mut self, message: String) {
+        self.errors
+            .push(format!("in {}: {}", Name(self.function), message));
+    }
+
+    /// Checks that `expr` yields a value of type `expected`, `what` names it for the error.
+    fn expect_type(&mut self, expr: &QIRExpression, expected: &QIRType, what: &str) {
+        let actual = expr.value_type();
+        if !compatible(expected, &actual) {
+            self.error(format!("{} is {}, expected {}: {}", what, actual, expected, expr));
+        }
+    }
+
+    /// The type of the memory `ptr` points to, when it is known.
+    fn pointee(&self, ptr: &QIRExpression) -> Option<QIRType> {
+        match ptr {
+            QIRExpression::GetLocalPtr { local } => self.locals.get(local.id).cloned(),
+            QIRExpression::GetGlobalPtr { global } => self.globals.get(global.name.as_str()).cloned(),
+            QIRExpression::GetFieldPtr { output_type, .. } => Some(output_type.clone()),
+            _ => None,
+        }
+    }
+
+    /// Checks the block `label` of a function with `count` blocks.
+    fn block(&mut self, label: Label, block: &'a QIRBasicBlock, count: usize) {
+        block.exprs.iter().for_each(|expr| self.expression(expr));
+        for target in block.terminator.successors() {
+            if target >= count {
+                self.error(format!("bb{} branches to bb{}, which does not exist", label, target));
+            } else if target == 0 {
+                self.error(format!("bb{} branches back to the entry block", label));
+            }
+        }
+        match &block.terminator {
+            QIRTerminator::CondBr { condition, .. } => {
+                self.expression(condition);
+                self.expect_type(condition, &QIRType::Bool, "the condition");
+            }
+            QIRTerminator::Ret { value: Some(value) } => {
+                self.expression(value);
+                self.expect_type(value, self.returns, "the returned value");
+            }
+            QIRTerminator::Ret { value: None } if *self.returns != QIRType::Void => {
+                self.error(format!("returns nothing from a function returning {}", self.returns))
+            }
+            _ => {}
+        }
+    }
+
+    fn expression(&mut self, expr: &'a QIRExpression) {
+        match expr {
+            QIRExpression::StoreToPtr {
+                receiver,
+                output_type,
+                new_value,
+            } => {
+                self.expression(new_value);
+                self.expect_type(new_value, output_type, "the stored value");
+                self.expression(receiver);
+                self.expect_type(receiver, &QIRType::Ptr, "the pointer stored to");
+                if let Some(pointee) = self.pointee(receiver)
+                    && !compatible(&pointee, output_type)
+                {
+                    self.error(format!("stores {} to {}: {}", output_type, pointee, expr));
+                }
+            }
+            QIRExpression::LoadFromPtr { ptr, output_type } => {
+                if let QIRExpression::GetLocalPtr { local } = ptr.as_ref()
+                    && self.undefined.iter().any(|load| std::ptr::eq(*load, expr))
+                {
+                    self.error(format!("%{} is loaded before it is stored to", local.id));
+                }
+                self.expression(ptr);
+                self.expect_type(ptr, &QIRType::Ptr, "the pointer loaded from");
+                if let Some(pointee) = self.pointee(ptr)
+                    && !compatible(&pointee, output_type)
+                {
+                    self.error(format!("loads {} from {}: {}", output_type, pointee, expr));
+                }
+            }
+            QIRExpression::GetFieldPtr {
+                receiver,
+                ptr_type,
+                output_type,
+                field,
+            } => {
+                self.expression(receiver);
+                self.expect_type(receiver, &QIRType::Ptr, "the structure");
+                if !has_header(ptr_type) {
+                    self.error(format!(
+                        "the structure does not start with the type ID and reference count: {}",
+                        expr
+                    ));
+                }
+                match usize::try_from(*field).ok().and_then(|field| ptr_type.get(field)) {
+                    Some(field_type) if field_type == output_type => {}
+                    Some(field_type) => self.error(format!(
+                        "field {} is {}, not {}: {}",
+                        field, field_type, output_type, expr
+                    )),
+                    None => self.error(format!("the structure has no field {}: {}", field, expr)),
+                }
+            }
+            QIRExpression::InstantiateStructure { ptr_type } if !has_header(ptr_type) => {
+                self.error(format!(
+                    "the structure does not start with the type ID and reference count: {}",
+                    expr
+                ))
+            }
+            QIRExpression::Integer { ty, .. }
+                if !matches!(
+                    ty,
+                    QIRType::Int8
+                        | QIRType::Int16
+                        | QIRType::Int32
+                        | QIRType::Int64
+                        | QIRType::UInt8
+                        | QIRType::UInt16
+                        | QIRType::UInt32
+                        | QIRType::UInt64
+                        | QIRType::USize
+                ) =>
+            {
+                self.error(format!("{} is not an integer type: {}", ty, expr))
+            }
+            QIRExpression::Float { ty, .. } if !matches!(ty, QIRType::Float32 | QIRType::Float64) => {
+                self.error(format!("{} is not a float type: {}", ty, expr))
+            }
+            QIRExpression::Retain { ptr } | QIRExpression::Release { ptr } => {
+                self.expression(ptr);
+                self.expect_type(ptr, &QIRType::Ptr, "the refcounted value");
+            }
+            QIRExpression::Invoke {
+                name,
+                arguments,
+                return_type,
+            } => {
+                arguments.iter().for_each(|argument| self.expression(argument));
+                // Intrinsics and runtime functions are checked when they are generated.
+                let Some((parameters, returns)) = self.functions.get(name.name.as_str()) else {
+                    return;
+                };
+                if parameters.len() != arguments.len() {
+                    self.error(format!(
+                        "{} takes {} arguments, not {}: {}",
+                        Name(&name.name),
+                        parameters.len(),
+                        arguments.len(),
+                        expr
+                    ));
+                }
+                for (argument, parameter) in arguments.iter().zip(parameters) {
+                    self.expect_type(argument, parameter, "the argument");
+                }
+                if !compatible(returns, return_type) {
+                    self.error(format!(
+                        "{} returns {}, not {}",
+                        Name(&name.name),
+                        returns,
+                        return_type
+                    ));
+                }
+            }
+            QIRExpression::InvokeClosure { closure, arguments, .. } => {
+                self.expression(closure);
+                self.expect_type(closure, &QIRType::Ptr, "the closure");
+                arguments.iter().for_each(|argument| self.expression(argument));
+            }
+            QIRExpression::InvokeDynamic { arguments, .. } => {
+                match arguments.first() {
+                    Some(receiver) => self.expect_type(receiver, &QIRType::Ptr, "the receiver"),
+                    None => self.error(format!("a dynamic call has no receiver: {}", expr)),
+                }
+                arguments.iter().for_each(|argument| self.expression(argument));
+            }
+            QIRExpression::GetLocalPtr { local } if local.id >= self.locals.len() => {
+                self.error(format!("%{} is not a local", local.id))
+            }
+            QIRExpression::GetGlobalPtr { global } if !self.globals.contains_key(global.name.as_str()) => {
+                self.error(format!("@{} is not a global", Name(&global.name)))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Marks the locals `expr` stores to in `defined`, in the order it runs, and adds the
+/// loads from locals before that to `undefined`. Locals that don't exist are
+/// reported on their own, so they count as defined.
+fn stores<'a>(expr: &'a QIRExpression, defined: &mut [bool], undefined: &mut Vec<&'a QIRExpression>) {
+    match expr {
+        QIRExpression::StoreToPtr {
+            receiver,
+            new_value,
+            ..
+        } => {
+            stores(new_value, defined, undefined);
+            match receiver.as_ref() {
+                QIRExpression::GetLocalPtr { local } if local.id < defined.len() => defined[local.id] = true,
+                receiver => stores(receiver, defined, undefined),
+            }
+        }
+        QIRExpression::LoadFromPtr { ptr, .. } => match ptr.as_ref() {
+            QIRExpression::GetLocalPtr { local } if !defined.get(local.id).unwrap_or(&true) => {
+                undefined.push(expr)
+            }
+            ptr => stores(ptr, defined, undefined),
+        },
+        _ => expr
+            .children()
+            .into_iter()
+            .for_each(|child| stores(child, defined, undefined)),
+    }
+}
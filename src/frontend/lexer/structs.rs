@@ -53,9 +53,13 @@ impl Lexer {
     }
 
     pub fn peek_char(&mut self) -> char {
+        self.peek_char_at(0)
+    }
+
+    pub fn peek_char_at(&mut self, offset: usize) -> char {
         self.file_contents
             .chars()
-            .nth(self.character_offset)
+            .nth(self.character_offset + offset)
             .unwrap_or('\0')
     }
 
@@ -68,7 +72,12 @@ impl Lexer {
                 ch if ('0'..='9').contains(&ch) => {
                     let mut content = String::new();
                     content.push(ch);
-                    while ('0'..='9').contains(&self.peek_char()) || self.peek_char() == '.' {
+                    // A dot only continues the number if a digit follows it, so `1..5` lexes as a range.
+                    while ('0'..='9').contains(&self.peek_char())
+                        || (self.peek_char() == '.'
+                            && ('0'..='9').contains(&self.peek_char_at(1))
+                            && !content.contains('.'))
+                    {
                         content.push(self.read_char());
                     }
                     self.push_token(TokenType::Number { content })
@@ -99,7 +108,7 @@ impl Lexer {
                     self.read_char();
                     self.push_token(TokenType::StringValue { content })
                 }
-                ch if ('a'..='z').contains(&ch) || ('A'..='Z').contains(&ch) => {
+                ch if ('a'..='z').contains(&ch) || ('A'..='Z').contains(&ch) || ch == '_' => {
                     let mut content = String::new();
                     content.push(ch);
 
@@ -116,9 +125,21 @@ impl Lexer {
                             "interface" => self.push_token(TokenType::InterfaceKeyword),
                             "loop" => self.push_token(TokenType::LoopKeyword),
                             "struct" => self.push_token(TokenType::StructKeyword),
+                            "shared" => self.push_token(TokenType::SharedKeyword),
+                            "enum" => self.push_token(TokenType::EnumKeyword),
                             "while" => self.push_token(TokenType::WhileKeyword),
                             "foreach" => self.push_token(TokenType::ForEachKeyword),
+                            "in" => self.push_token(TokenType::InKeyword),
                             "c" => self.push_token(TokenType::CKeyword),
+                            "return" => self.push_token(TokenType::ReturnKeyword),
+                            "let" => self.push_token(TokenType::LetKeyword),
+                            "as" => self.push_token(TokenType::AsKeyword),
+                            "is" => self.push_token(TokenType::IsKeyword),
+                            "none" => self.push_token(TokenType::NoneKeyword),
+                            "weak" => self.push_token(TokenType::WeakKeyword),
+                            "match" => self.push_token(TokenType::MatchKeyword),
+                            "true" => self.push_token(TokenType::TrueKeyword),
+                            "false" => self.push_token(TokenType::FalseKeyword),
                             _ => self.push_token(TokenType::Identifier { content }),
                         }
                     }
@@ -144,6 +165,9 @@ impl Lexer {
                     if self.peek_char() == '=' {
                         self.read_char();
                         self.push_token(TokenType::DoubleEqual);
+                    } else if self.peek_char() == '>' {
+                        self.read_char();
+                        self.push_token(TokenType::FatArrow);
                     } else {
                         self.push_token(TokenType::Equal);
                     }
@@ -152,6 +176,9 @@ impl Lexer {
                     if self.peek_char() == '=' {
                         self.read_char();
                         self.push_token(TokenType::GreaterThanOrEqual);
+                    } else if self.peek_char() == '>' {
+                        self.read_char();
+                        self.push_token(TokenType::ShiftRight);
                     } else {
                         self.push_token(TokenType::GreaterThan);
                     }
@@ -160,6 +187,9 @@ impl Lexer {
                     if self.peek_char() == '=' {
                         self.read_char();
                         self.push_token(TokenType::LessThanOrEqual);
+                    } else if self.peek_char() == '<' {
+                        self.read_char();
+                        self.push_token(TokenType::ShiftLeft);
                     } else {
                         self.push_token(TokenType::LessThan);
                     }
@@ -16,6 +16,8 @@ pub enum TokenType {
     ImportKeyword,
     FnKeyword,
     StructKeyword,
+    SharedKeyword,
+    EnumKeyword,
     IfKeyword,
     ElseKeyword,
     WhileKeyword,
@@ -23,7 +25,17 @@ pub enum TokenType {
     BreakKeyword,
     InterfaceKeyword,
     ForEachKeyword,
+    InKeyword,
     CKeyword,
+    ReturnKeyword,
+    LetKeyword,
+    AsKeyword,
+    IsKeyword,
+    NoneKeyword,
+    WeakKeyword,
+    MatchKeyword,
+    TrueKeyword,
+    FalseKeyword,
 
     OpenParen,
     CloseParen,
@@ -39,6 +51,7 @@ pub enum TokenType {
     Dot,
     DoubleDot,
     Arrow,
+    FatArrow,
 
     Tilde,
     Grave,
@@ -61,6 +74,8 @@ pub enum TokenType {
 
     LessThan,
     GreaterThan,
+    ShiftLeft,
+    ShiftRight,
     LessThanOrEqual,
     GreaterThanOrEqual,
     Equal,
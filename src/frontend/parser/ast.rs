@@ -1,26 +1,75 @@
 use crate::frontend::lexer::tokens::Token;
+use crate::frontend::span::Span;
 use std::cell::OnceCell;
-use std::collections::HashMap;
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum AstHeader {
     Import(String),
     Function {
         name: PathData,
-        parameters: Vec<(AstType, String)>,
+        /// Names of the type parameters, `T` in `fn first<T>(..)`.
+        generics: Vec<String>,
+        parameters: Vec<(AstType, PathData)>,
         returns: AstType,
         code_block: AstCodeBlock,
-        locals: HashMap<String, AstType>,
+        locals: Vec<AstLocal>,
     },
     Struct {
         name: PathData,
+        generics: Vec<String>,
         fields: Vec<(String, AstType)>,
+        /// The interfaces the struct declares it implements, `Iterator<i32>` in
+        /// `struct Counter: Iterator<i32>`.
+        implements: Vec<(AstType, Token)>,
+        /// Declared `shared struct`, its objects are refcounted atomically and can be sent
+        /// to other threads. Their fields can't be assigned after they are created.
+        shared: bool,
     },
+    Enum {
+        name: PathData,
+        variants: Vec<AstEnumVariant>,
+    },
+    Interface {
+        name: PathData,
+        /// Names of the type parameters, `T` in `interface Iterator<T>`.
+        generics: Vec<String>,
+        methods: Vec<AstInterfaceMethod>,
+    },
+}
+
+/// A method every implementation of an interface has to provide, as
+/// `fn Type::name(self, ..)` on the implementing type.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AstInterfaceMethod {
+    pub name: PathData,
+    /// Includes `self`, typed as the interface.
+    pub parameters: Vec<(AstType, PathData)>,
+    pub returns: AstType,
+}
+
+/// A variant of an enum, with the types of the values it carries.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AstEnumVariant {
+    pub name: String,
+    pub fields: Vec<AstType>,
+    pub token: Token,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct AstCodeBlock {
     pub statements: Vec<AstStatement>,
+    /// The trailing expression of the block, written without a semicolon.
+    pub tail: Option<Box<AstExpression>>,
+    /// Indices into the function's locals of the variables declared directly in this block.
+    pub locals: Vec<usize>,
+}
+
+/// A local variable or parameter of a function, recorded at its declaration site.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AstLocal {
+    pub name: String,
+    pub ty: AstType,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -28,10 +77,20 @@ pub enum AstStatement {
     Comment(String),
     Expression(AstExpression),
 
+    DeclareVariable {
+        name: String,
+        declared_ty: Option<AstType>,
+        ty: OnceCell<AstType>,
+        value: AstExpression,
+        token: Token,
+        local: OnceCell<usize>,
+    },
     ModifyVariable {
         name: String,
         ty: OnceCell<AstType>,
         value: AstExpression,
+        token: Token,
+        local: OnceCell<usize>,
     },
     IfStatement {
         cond: AstExpression,
@@ -42,6 +101,128 @@ pub enum AstStatement {
         cond: AstExpression,
         do_true: AstCodeBlock,
     },
+    /// `foreach name in iterable { .. }`
+    ///
+    /// Lowers to basic blocks that count `index` up to the length of the array or
+    /// the end of the range, or that call `next` on the iterator until it returns `none`.
+    ForEach {
+        name: String,
+        iterable: AstIterable,
+        do_true: AstCodeBlock,
+        /// The type of the loop variable.
+        ty: OnceCell<AstType>,
+        token: Token,
+        /// The loop variable.
+        local: OnceCell<usize>,
+        /// The hidden local holding the array, the end of the range or the iterator.
+        source: OnceCell<usize>,
+        /// The hidden local counting through an array or a range, unset for iterators.
+        index: OnceCell<usize>,
+    },
+    /// `array[index] = value;`
+    AssignIndex {
+        base: AstExpression,
+        index: AstExpression,
+        /// The element type of the array.
+        ty: OnceCell<AstType>,
+        value: AstExpression,
+        token: Token,
+    },
+    /// `object.field = value;`
+    ///
+    /// Lowers to a `StoreToPtr` through the `GetFieldPtr` of the field.
+    AssignField {
+        object: AstExpression,
+        field: String,
+        /// The type of the field.
+        ty: OnceCell<AstType>,
+        value: AstExpression,
+        token: Token,
+    },
+    Return {
+        value: Option<AstExpression>,
+        token: Token,
+    },
+}
+
+/// What a `foreach` loop iterates over.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AstIterable {
+    /// `start..end` or `start..=end` over integers.
+    Range {
+        start: AstExpression,
+        end: AstExpression,
+        inclusive: bool,
+    },
+    /// An array, or an iterator with a method `next(self) -> T | none`.
+    Value {
+        value: AstExpression,
+        /// The call of `next` on the hidden local holding an iterator, set by the typechecker.
+        next: Option<Box<AstExpression>>,
+    },
+}
+
+/// A variable of the enclosing function read by a closure.
+///
+/// Captures are copied into the closure object when it is created, so the closure
+/// can not assign to them.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AstCapture {
+    /// Index into the locals of the enclosing function.
+    pub outer: usize,
+    /// Index into the locals of the closure body.
+    pub inner: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AstMatchArm {
+    pub pattern: AstPattern,
+    pub body: AstCodeBlock,
+    /// Indices into the function's locals of the variables bound by the pattern.
+    pub locals: Vec<usize>,
+    pub token: Token,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum AstPattern {
+    /// `_`, matches anything.
+    Wildcard { token: Token },
+    /// `name`, matches anything and binds it.
+    Binding {
+        name: String,
+        token: Token,
+        local: OnceCell<usize>,
+    },
+    /// `name: Type`, `_: Type` or `none`, matches one member of a union.
+    TypeTest {
+        binding: Option<(String, OnceCell<usize>)>,
+        ty: AstType,
+        token: Token,
+    },
+    /// A number, string or boolean literal.
+    Literal(AstExpression),
+    /// `start..end` or `start..=end` over integers.
+    Range {
+        start: AstExpression,
+        end: AstExpression,
+        inclusive: bool,
+        token: Token,
+    },
+    /// `Name { field: pattern, other }`, matches a structure and its fields.
+    ///
+    /// Fields that are not mentioned match anything.
+    Structure {
+        name: PathData,
+        fields: Vec<(String, AstPattern)>,
+        ty: OnceCell<AstType>,
+    },
+    /// `Enum::Variant` or `Enum::Variant(patterns)`, matches one variant of an enum
+    /// and the values it carries.
+    Variant {
+        name: PathData,
+        fields: Vec<AstPattern>,
+        ty: OnceCell<AstType>,
+    },
 }
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
@@ -62,10 +243,24 @@ pub enum AstExpression {
         ty: OnceCell<AstType>,
         token: Token,
     },
+    BooleanLiteral {
+        value: bool,
+        ty: OnceCell<AstType>,
+        token: Token,
+    },
+    NoneLiteral {
+        ty: OnceCell<AstType>,
+        token: Token,
+    },
+    /// A local variable, or a function used as a value.
+    ///
+    /// `local` stays unset for functions, which are called through a closure
+    /// object without any captures when used as values.
     VariableLiteral {
         content: String,
         ty: OnceCell<AstType>,
         token: Token,
+        local: OnceCell<usize>,
     },
     PathLiteral(PathData),
     ArrayLiteral {
@@ -74,14 +269,46 @@ pub enum AstExpression {
         open_bracket_tok: Token,
         close_bracket_tok: Token,
     },
+    /// `Name { field: value, .. }`, giving every field of the struct a value.
+    ///
+    /// Lowers to an `InstantiateStructure`, followed by a `StoreToPtr` of the type ID,
+    /// a reference count of 1 and each field through `GetFieldPtr`.
+    ///
+    /// The type arguments of a generic struct are inferred from the values of its fields.
     StructureLiteral {
         ty: AstType,
         fields: Vec<(String, AstExpression)>,
+        token: Token,
     },
     TypeLiteral {
         ty: AstType,
         token: Token,
     },
+    /// `\(x: i32) -> i32 { x + offset }`, an anonymous function.
+    ///
+    /// Lowers to a function that takes the closure object as its first parameter, and
+    /// to an `InstantiateStructure` of the closure object, which holds a pointer to that
+    /// function followed by the captured values.
+    Lambda {
+        parameters: Vec<(AstType, PathData)>,
+        returns: AstType,
+        code_block: AstCodeBlock,
+        /// The locals of the body, starting with the parameters.
+        locals: Vec<AstLocal>,
+        captures: Vec<AstCapture>,
+        ty: OnceCell<AstType>,
+        token: Token,
+    },
+    /// `Enum::Variant` or `Enum::Variant(values)`.
+    ///
+    /// The parser reads these as paths and calls, the typechecker replaces them
+    /// once it knows the path names an enum variant.
+    EnumLiteral {
+        ty: AstType,
+        variant: String,
+        arguments: Vec<AstExpression>,
+        token: Token,
+    },
 
     Add {
         ty: OnceCell<AstType>,
@@ -114,6 +341,101 @@ pub enum AstExpression {
         op_tok: Token,
     },
 
+    ShiftLeft {
+        ty: OnceCell<AstType>,
+        lhs: Box<AstExpression>,
+        rhs: Box<AstExpression>,
+        op_tok: Token,
+    },
+    ShiftRight {
+        ty: OnceCell<AstType>,
+        lhs: Box<AstExpression>,
+        rhs: Box<AstExpression>,
+        op_tok: Token,
+    },
+
+    Equal {
+        ty: OnceCell<AstType>,
+        lhs: Box<AstExpression>,
+        rhs: Box<AstExpression>,
+        op_tok: Token,
+    },
+    NotEqual {
+        ty: OnceCell<AstType>,
+        lhs: Box<AstExpression>,
+        rhs: Box<AstExpression>,
+        op_tok: Token,
+    },
+    LessThan {
+        ty: OnceCell<AstType>,
+        lhs: Box<AstExpression>,
+        rhs: Box<AstExpression>,
+        op_tok: Token,
+    },
+    GreaterThan {
+        ty: OnceCell<AstType>,
+        lhs: Box<AstExpression>,
+        rhs: Box<AstExpression>,
+        op_tok: Token,
+    },
+    LessThanOrEqual {
+        ty: OnceCell<AstType>,
+        lhs: Box<AstExpression>,
+        rhs: Box<AstExpression>,
+        op_tok: Token,
+    },
+    GreaterThanOrEqual {
+        ty: OnceCell<AstType>,
+        lhs: Box<AstExpression>,
+        rhs: Box<AstExpression>,
+        op_tok: Token,
+    },
+
+    /// Explicit `value as Type` conversions, and implicit widenings inserted by the typechecker.
+    Cast {
+        value: Box<AstExpression>,
+        target: AstType,
+        ty: OnceCell<AstType>,
+        token: Token,
+    },
+
+    Match {
+        value: Box<AstExpression>,
+        arms: Vec<AstMatchArm>,
+        ty: OnceCell<AstType>,
+        token: Token,
+    },
+
+    /// `value?`, returns the `Err` members of a result union from the enclosing function
+    /// and evaluates to the remaining members otherwise.
+    ///
+    /// Lowers to a branch on the type ID of the value, which returns it in one arm and
+    /// unboxes it in the other.
+    Propagate {
+        value: Box<AstExpression>,
+        ty: OnceCell<AstType>,
+        token: Token,
+    },
+
+    /// `value.field`, lowers to a `LoadFromPtr` of the `GetFieldPtr` of the field.
+    FieldAccess {
+        value: Box<AstExpression>,
+        field: String,
+        ty: OnceCell<AstType>,
+        /// The name of the field.
+        token: Token,
+    },
+
+    /// Tests which member of a union a value currently holds.
+    Is {
+        value: Box<AstExpression>,
+        target: AstType,
+        ty: OnceCell<AstType>,
+        token: Token,
+    },
+
+    /// Calls a named function when the receiver is a `PathLiteral` after typechecking,
+    /// and a closure value otherwise.
     Invoke {
         receiver: Box<AstExpression>,
         arguments: Vec<AstExpression>,
@@ -123,23 +445,222 @@ pub enum AstExpression {
         resolve_as_ufcs: bool,
     },
 
+    /// `array[index]`, checked against the length of the array at runtime.
     Index {
         ty: OnceCell<AstType>,
         base: Box<AstExpression>,
         other: Box<AstExpression>,
+        /// The opening bracket, the location a failed bounds check reports.
+        token: Token,
     },
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
 pub enum AstType {
+    Int8,
+    Int16,
     Int32,
     Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
+    USize,
     Float32,
     Float64,
+    Bool,
+    /// The type of `none`, which carries no data.
+    None,
+    /// `[T]`, a pointer to a refcounted, growable array.
     ArrayOf(Box<AstType>),
+    /// A named type declared by a `struct`, `enum` or `interface` header, or a type
+    /// parameter of the generic header it appears in.
+    ///
+    /// Values of an interface type are pointers to an object of a struct that
+    /// implements it, and calls to its methods are dispatched on the type ID of the object.
     Structure(String),
     Void,
     Invalid,
 
+    /// Values of a union type are pointers to a refcounted object whose type ID slot
+    /// records which member it holds. Members that are not already heap objects are
+    /// boxed when they are converted to the union.
+    ///
+    /// Unions are kept normalized by `AstType::union`, so two unions with the same
+    /// members compare equal.
     UnionOf(Box<AstType>, Box<AstType>),
+
+    /// A generic struct applied to type arguments, `List<i32>`.
+    ///
+    /// The typechecker replaces these with the `Structure` of a copy of the struct made
+    /// for those arguments, named like `List<i32>`.
+    Instance(String, Vec<AstType>),
+
+    /// `fn(i32, i32) -> i32`, a pointer to a refcounted closure object.
+    Function(Vec<AstType>, Box<AstType>),
+
+    /// `weak T`, a pointer to a refcounted weak reference to an object, which doesn't
+    /// keep the object alive. Values of `T` convert to it implicitly, and `get` returns
+    /// the object, or `none` once it was freed.
+    Weak(Box<AstType>),
+}
+
+impl AstType {
+    /// Looks up a builtin type by the name it is written as.
+    pub fn from_name(name: &str) -> Option<AstType> {
+        match name {
+            "i8" => Some(AstType::Int8),
+            "i16" => Some(AstType::Int16),
+            "i32" => Some(AstType::Int32),
+            "i64" => Some(AstType::Int64),
+            "u8" | "byte" => Some(AstType::UInt8),
+            "u16" => Some(AstType::UInt16),
+            "u32" => Some(AstType::UInt32),
+            "u64" => Some(AstType::UInt64),
+            "usize" => Some(AstType::USize),
+            "f32" => Some(AstType::Float32),
+            "f64" => Some(AstType::Float64),
+            "bool" => Some(AstType::Bool),
+            "void" => Some(AstType::Void),
+            "string" => Some(AstType::Structure("std::string".to_string())),
+            _ => None,
+        }
+    }
+
+    /// Builds the union of the given types, flattening nested unions and
+    /// removing duplicate members.
+    pub fn union(members: Vec<AstType>) -> AstType {
+        let mut flattened = members
+            .iter()
+            .flat_map(|member| member.union_members())
+            .collect::<Vec<_>>();
+        flattened.sort();
+        flattened.dedup();
+
+        let mut members = flattened.into_iter().rev();
+        let last = members.next().expect("unions must have at least one member");
+        members.fold(last, |union, member| {
+            AstType::UnionOf(Box::new(member), Box::new(union))
+        })
+    }
+
+    /// The members of a union, or the type itself if it is not a union.
+    pub fn union_members(&self) -> Vec<AstType> {
+        match self {
+            AstType::UnionOf(lhs, rhs) => {
+                let mut members = lhs.union_members();
+                members.extend(rhs.union_members());
+                members
+            }
+            other => vec![other.clone()],
+        }
+    }
+
+    /// The type as it is written in source code, used to name instances of generics.
+    pub fn source_name(&self) -> String {
+        match self {
+            AstType::Int8 => "i8".to_string(),
+            AstType::Int16 => "i16".to_string(),
+            AstType::Int32 => "i32".to_string(),
+            AstType::Int64 => "i64".to_string(),
+            AstType::UInt8 => "u8".to_string(),
+            AstType::UInt16 => "u16".to_string(),
+            AstType::UInt32 => "u32".to_string(),
+            AstType::UInt64 => "u64".to_string(),
+            AstType::USize => "usize".to_string(),
+            AstType::Float32 => "f32".to_string(),
+            AstType::Float64 => "f64".to_string(),
+            AstType::Bool => "bool".to_string(),
+            AstType::None => "none".to_string(),
+            AstType::ArrayOf(inner) => format!("[{}]", inner.source_name()),
+            AstType::Structure(name) => name.clone(),
+            AstType::Void => "void".to_string(),
+            AstType::Invalid => "<invalid>".to_string(),
+            AstType::UnionOf(..) => self
+                .union_members()
+                .iter()
+                .map(|member| member.source_name())
+                .collect::<Vec<_>>()
+                .join(" | "),
+            AstType::Instance(name, arguments) => format!(
+                "{}<{}>",
+                name,
+                arguments
+                    .iter()
+                    .map(|argument| argument.source_name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            AstType::Function(parameters, returns) => format!(
+                "fn({}) -> {}",
+                parameters
+                    .iter()
+                    .map(|parameter| parameter.source_name())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                returns.source_name()
+            ),
+            AstType::Weak(target) => format!("weak {}", target.source_name()),
+        }
+    }
+}
+
+impl AstPattern {
+    pub fn get_token(&self) -> Token {
+        match self {
+            AstPattern::Wildcard { token } => token.clone(),
+            AstPattern::Binding { token, .. } => token.clone(),
+            AstPattern::TypeTest { token, .. } => token.clone(),
+            AstPattern::Literal(expr) => expr.get_token(),
+            AstPattern::Range { token, .. } => token.clone(),
+            AstPattern::Structure { name, .. } => name.token.clone(),
+            AstPattern::Variant { name, .. } => name.token.clone(),
+        }
+    }
+}
+
+impl AstExpression {
+    pub fn get_span(&self) -> Span {
+        self.get_token().span
+    }
+
+    pub fn get_token(&self) -> Token {
+        match self {
+            AstExpression::NumberLiteral { token, .. } => token.clone(),
+            AstExpression::StringLiteral { token, .. } => token.clone(),
+            AstExpression::BooleanLiteral { token, .. } => token.clone(),
+            AstExpression::NoneLiteral { token, .. } => token.clone(),
+            AstExpression::VariableLiteral { token, .. } => token.clone(),
+            AstExpression::PathLiteral(path) => path.token.clone(),
+            AstExpression::ArrayLiteral {
+                open_bracket_tok, ..
+            } => open_bracket_tok.clone(),
+            AstExpression::StructureLiteral { token, .. } => token.clone(),
+            AstExpression::TypeLiteral { token, .. } => token.clone(),
+            AstExpression::Lambda { token, .. } => token.clone(),
+            AstExpression::EnumLiteral { token, .. } => token.clone(),
+            AstExpression::Add { op_tok, .. }
+            | AstExpression::Sub { op_tok, .. }
+            | AstExpression::Mul { op_tok, .. }
+            | AstExpression::Div { op_tok, .. }
+            | AstExpression::Mod { op_tok, .. }
+            | AstExpression::ShiftLeft { op_tok, .. }
+            | AstExpression::ShiftRight { op_tok, .. }
+            | AstExpression::Equal { op_tok, .. }
+            | AstExpression::NotEqual { op_tok, .. }
+            | AstExpression::LessThan { op_tok, .. }
+            | AstExpression::GreaterThan { op_tok, .. }
+            | AstExpression::LessThanOrEqual { op_tok, .. }
+            | AstExpression::GreaterThanOrEqual { op_tok, .. } => op_tok.clone(),
+            AstExpression::Invoke {
+                open_paren_span, ..
+            } => open_paren_span.clone(),
+            AstExpression::Cast { token, .. } => token.clone(),
+            AstExpression::Is { token, .. } => token.clone(),
+            AstExpression::Propagate { token, .. } => token.clone(),
+            AstExpression::FieldAccess { token, .. } => token.clone(),
+            AstExpression::Match { token, .. } => token.clone(),
+            AstExpression::Index { base, .. } => base.get_token(),
+        }
+    }
 }
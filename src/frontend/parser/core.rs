@@ -5,6 +5,9 @@ use crate::frontend::span::Span;
 pub struct Parser {
     pub tokens: TokenIterator,
     pub errors: Vec<(String, Span)>,
+    /// Set while parsing the expression before the block of an `if`, `while` or
+    /// `match`, where `Name {` opens the block instead of a structure literal.
+    pub in_condition: bool,
 }
 
 impl Parser {
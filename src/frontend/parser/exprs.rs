@@ -1,5 +1,6 @@
 use crate::frontend::lexer::tokens::TokenType;
-use crate::frontend::parser::ast::AstExpression;
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstExpression, AstType, PathData};
 use crate::frontend::parser::core::Parser;
 use crate::frontend::span::Span;
 use crate::match_token_type;
@@ -7,7 +8,110 @@ use std::cell::OnceCell;
 
 impl Parser {
     pub(crate) fn parse_expression(&mut self) -> Result<AstExpression, (String, Span)> {
-        self.parse_ufcs()
+        self.parse_comparison()
+    }
+
+    /// Parses the expression before the block of an `if`, `while` or `match`.
+    pub(crate) fn parse_condition(&mut self) -> Result<AstExpression, (String, Span)> {
+        let in_condition = std::mem::replace(&mut self.in_condition, true);
+        let cond = self.parse_expression();
+        self.in_condition = in_condition;
+        cond
+    }
+
+    /// Parses an expression enclosed in brackets, where structure literals are
+    /// allowed even inside a condition.
+    pub(crate) fn parse_nested_expression(&mut self) -> Result<AstExpression, (String, Span)> {
+        let in_condition = std::mem::replace(&mut self.in_condition, false);
+        let expr = self.parse_expression();
+        self.in_condition = in_condition;
+        expr
+    }
+
+    fn parse_comparison(&mut self) -> Result<AstExpression, (String, Span)> {
+        type Constructor =
+            fn(OnceCell<AstType>, Box<AstExpression>, Box<AstExpression>, Token) -> AstExpression;
+
+        let mut expr = self.parse_shift()?;
+        while let Some(tok) = self.tokens.peek().cloned() {
+            let constructor: Constructor = match tok.token_type {
+                TokenType::DoubleEqual => |ty, lhs, rhs, op_tok| AstExpression::Equal {
+                    ty,
+                    lhs,
+                    rhs,
+                    op_tok,
+                },
+                TokenType::NotEqual => |ty, lhs, rhs, op_tok| AstExpression::NotEqual {
+                    ty,
+                    lhs,
+                    rhs,
+                    op_tok,
+                },
+                TokenType::LessThan => |ty, lhs, rhs, op_tok| AstExpression::LessThan {
+                    ty,
+                    lhs,
+                    rhs,
+                    op_tok,
+                },
+                TokenType::GreaterThan => |ty, lhs, rhs, op_tok| AstExpression::GreaterThan {
+                    ty,
+                    lhs,
+                    rhs,
+                    op_tok,
+                },
+                TokenType::LessThanOrEqual => {
+                    |ty, lhs, rhs, op_tok| AstExpression::LessThanOrEqual {
+                        ty,
+                        lhs,
+                        rhs,
+                        op_tok,
+                    }
+                }
+                TokenType::GreaterThanOrEqual => {
+                    |ty, lhs, rhs, op_tok| AstExpression::GreaterThanOrEqual {
+                        ty,
+                        lhs,
+                        rhs,
+                        op_tok,
+                    }
+                }
+                _ => break,
+            };
+            self.tokens.next();
+            let rhs = self.parse_shift()?;
+            expr = constructor(OnceCell::new(), Box::new(expr), Box::new(rhs), tok);
+        }
+        Ok(expr)
+    }
+
+    fn parse_shift(&mut self) -> Result<AstExpression, (String, Span)> {
+        let mut expr = self.parse_ufcs()?;
+        while let Some(tok) = self.tokens.peek().cloned() {
+            match tok.token_type {
+                TokenType::ShiftLeft => {
+                    self.tokens.next();
+                    let rhs = self.parse_ufcs()?;
+                    expr = AstExpression::ShiftLeft {
+                        ty: OnceCell::new(),
+                        lhs: Box::new(expr),
+                        rhs: Box::new(rhs),
+                        op_tok: tok.clone(),
+                    };
+                }
+                TokenType::ShiftRight => {
+                    self.tokens.next();
+                    let rhs = self.parse_ufcs()?;
+                    expr = AstExpression::ShiftRight {
+                        ty: OnceCell::new(),
+                        lhs: Box::new(expr),
+                        rhs: Box::new(rhs),
+                        op_tok: tok.clone(),
+                    };
+                }
+                _ => break,
+            };
+        }
+        Ok(expr)
     }
 
     fn parse_ufcs(&mut self) -> Result<AstExpression, (String, Span)> {
@@ -16,6 +120,7 @@ impl Parser {
             && tok.token_type == TokenType::Dot
         {
             self.tokens.next();
+
             let rhs = self.parse_factor()?;
 
             match rhs {
@@ -73,6 +178,16 @@ impl Parser {
                         op_tok: tok.clone(),
                     });
                 }
+                TokenType::Percent => {
+                    self.tokens.next();
+                    let rhs = self.parse_factor()?;
+                    expr = expr.map(|lhs| AstExpression::Mod {
+                        ty: OnceCell::new(),
+                        lhs: Box::new(lhs),
+                        rhs: Box::new(rhs),
+                        op_tok: tok.clone(),
+                    });
+                }
                 _ => break,
             };
         }
@@ -116,7 +231,7 @@ impl Parser {
                 TokenType::OpenBracket => {
                     self.tokens.next();
 
-                    let index_by = self.parse_expression()?;
+                    let index_by = self.parse_nested_expression()?;
 
                     let Some(close_brack_tok) = self.tokens.peek().cloned() else {
                         return Err((
@@ -139,6 +254,7 @@ impl Parser {
                         ty: OnceCell::new(),
                         base: Box::new(expr),
                         other: Box::new(index_by),
+                        token: tok,
                     }
                 }
                 TokenType::OpenParen => {
@@ -152,7 +268,7 @@ impl Parser {
                             break;
                         };
 
-                        let arg = self.parse_expression()?;
+                        let arg = self.parse_nested_expression()?;
                         arguments.push(arg);
 
                         if let Some(peeked) = self.tokens.peek().cloned()
@@ -189,12 +305,122 @@ impl Parser {
                         resolve_as_ufcs: false,
                     }
                 }
+                // `value.field`, unless the name is called like `value.function()`.
+                TokenType::Dot
+                    if self.tokens.vector.get(self.tokens.index + 1).is_some_and(|name| {
+                        matches!(name.token_type, TokenType::Identifier { .. })
+                    }) && !self.tokens.vector.get(self.tokens.index + 2).is_some_and(|next| {
+                        matches!(next.token_type, TokenType::OpenParen | TokenType::DoubleColon)
+                    }) =>
+                {
+                    self.tokens.next();
+                    let field_tok = self.tokens.next().cloned().unwrap();
+                    let TokenType::Identifier { content } = field_tok.token_type.clone() else {
+                        unreachable!("checked above");
+                    };
+                    expr = AstExpression::FieldAccess {
+                        value: Box::new(expr),
+                        field: content,
+                        ty: OnceCell::new(),
+                        token: field_tok,
+                    }
+                }
+                TokenType::QuestionMark => {
+                    self.tokens.next();
+                    expr = AstExpression::Propagate {
+                        value: Box::new(expr),
+                        ty: OnceCell::new(),
+                        token: tok,
+                    }
+                }
+                TokenType::IsKeyword => {
+                    self.tokens.next();
+                    let target = self.parse_base_type()?;
+                    expr = AstExpression::Is {
+                        value: Box::new(expr),
+                        target,
+                        ty: OnceCell::new(),
+                        token: tok,
+                    }
+                }
+                TokenType::AsKeyword => {
+                    self.tokens.next();
+                    let target = self.parse_base_type()?;
+                    expr = AstExpression::Cast {
+                        value: Box::new(expr),
+                        target,
+                        ty: OnceCell::new(),
+                        token: tok,
+                    }
+                }
                 _ => break,
             };
         }
         Ok(expr)
     }
 
+    /// Parses the fields of `Name { field: value, .. }` after the name.
+    fn parse_structure_literal(&mut self, name: PathData) -> Result<AstExpression, (String, Span)> {
+        self.expect_token(TokenType::OpenBrace)?;
+        let mut fields = Vec::new();
+        while !self.peek_is(TokenType::CloseBrace) {
+            let field_tok = self.tokens.next().cloned();
+            let Some(Token {
+                token_type: TokenType::Identifier { content: field },
+                ..
+            }) = field_tok
+            else {
+                return Err((
+                    format!(
+                        "expected Identifier, found {:?}",
+                        field_tok.as_ref().map(|tok| &tok.token_type)
+                    ),
+                    self.tokens.vector[self.tokens.index - 1].span.clone(),
+                ));
+            };
+            self.expect_token(TokenType::Colon)?;
+            fields.push((field, self.parse_nested_expression()?));
+            if !self.peek_is(TokenType::Comma) {
+                break;
+            }
+            self.tokens.next();
+        }
+        self.expect_token(TokenType::CloseBrace)?;
+        Ok(AstExpression::StructureLiteral {
+            ty: AstType::Structure(name.name),
+            fields,
+            token: name.token,
+        })
+    }
+
+    /// Parses the rest of `\(x: i32) -> i32 { .. }` after the backslash.
+    fn parse_lambda(&mut self, backslash_tok: Token) -> Result<AstExpression, (String, Span)> {
+        let name = PathData {
+            name: "<closure>".to_string(),
+            token: backslash_tok.clone(),
+        };
+        let Some(parameters) = self.parse_parameters(&name) else {
+            return Err(self.errors.pop().expect("parse_parameters reports its errors"));
+        };
+        self.expect_token(TokenType::Arrow)?;
+        let returns = self.parse_type()?;
+        let in_condition = std::mem::replace(&mut self.in_condition, false);
+        let code_block = self.parse_code_block();
+        self.in_condition = in_condition;
+        let Some(code_block) = code_block else {
+            return Err(self.errors.pop().expect("parse_code_block reports its errors"));
+        };
+        Ok(AstExpression::Lambda {
+            parameters,
+            returns,
+            code_block,
+            locals: vec![],
+            captures: vec![],
+            ty: OnceCell::new(),
+            token: backslash_tok,
+        })
+    }
+
     fn parse_base_value(&mut self) -> Result<AstExpression, (String, Span)> {
         let Some(tok) = self.tokens.peek().cloned() else {
             return Err((
@@ -209,11 +435,84 @@ impl Parser {
                 ty: OnceCell::new(),
                 token: tok.clone(),
             }),
-            TokenType::Identifier { content } => Ok(AstExpression::VariableLiteral {
+            TokenType::OpenParen => {
+                let expr = self.parse_nested_expression()?;
+                let Some(close_paren_tok) = self.tokens.next().cloned() else {
+                    return Err((
+                        "expected CloseParen, found EOF".to_string(),
+                        self.tokens.vector.last().cloned().unwrap().span,
+                    ));
+                };
+                let TokenType::CloseParen = close_paren_tok.token_type else {
+                    return Err((
+                        format!("expected CloseParen, found {:?}", close_paren_tok.token_type),
+                        close_paren_tok.span,
+                    ));
+                };
+                Ok(expr)
+            }
+            TokenType::OpenBracket => {
+                let mut content = Vec::new();
+                while !self.peek_is(TokenType::CloseBracket) {
+                    content.push(self.parse_nested_expression()?);
+                    if !self.peek_is(TokenType::Comma) {
+                        break;
+                    }
+                    self.tokens.next();
+                }
+                let close_bracket_tok = self.expect_token(TokenType::CloseBracket)?;
+                Ok(AstExpression::ArrayLiteral {
+                    content,
+                    ty: OnceCell::new(),
+                    open_bracket_tok: tok,
+                    close_bracket_tok,
+                })
+            }
+            TokenType::MatchKeyword => self.parse_match(tok),
+            TokenType::Backslash => self.parse_lambda(tok),
+            TokenType::NoneKeyword => Ok(AstExpression::NoneLiteral {
+                ty: OnceCell::new(),
+                token: tok,
+            }),
+            TokenType::TrueKeyword => Ok(AstExpression::BooleanLiteral {
+                value: true,
+                ty: OnceCell::new(),
+                token: tok,
+            }),
+            TokenType::FalseKeyword => Ok(AstExpression::BooleanLiteral {
+                value: false,
+                ty: OnceCell::new(),
+                token: tok,
+            }),
+            TokenType::StringValue { content } => Ok(AstExpression::StringLiteral {
                 content,
                 ty: OnceCell::new(),
                 token: tok,
             }),
+            TokenType::Identifier { content } => {
+                if let Some(peeked) = self.tokens.peek()
+                    && peeked.token_type == TokenType::DoubleColon
+                {
+                    self.tokens.index -= 1;
+                    let path = self.parse_identifier()?;
+                    if !self.in_condition && self.peek_is(TokenType::OpenBrace) {
+                        return self.parse_structure_literal(path);
+                    }
+                    return Ok(AstExpression::PathLiteral(path));
+                }
+                if !self.in_condition && self.peek_is(TokenType::OpenBrace) {
+                    return self.parse_structure_literal(PathData {
+                        name: content,
+                        token: tok,
+                    });
+                }
+                Ok(AstExpression::VariableLiteral {
+                    content,
+                    ty: OnceCell::new(),
+                    token: tok,
+                    local: OnceCell::new(),
+                })
+            }
             _ => Err((
                 format!("expected base value, found {:?}", tok.clone().token_type),
                 self.tokens.vector.last().cloned().unwrap().span,
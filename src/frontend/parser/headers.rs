@@ -1,9 +1,8 @@
 use crate::frontend::lexer::tokens::TokenType;
 use crate::frontend::parser::ast::AstHeader::Import;
-use crate::frontend::parser::ast::{AstHeader, AstType};
+use crate::frontend::parser::ast::{AstEnumVariant, AstHeader, AstInterfaceMethod, AstType, PathData};
 use crate::frontend::parser::core::Parser;
 use crate::match_token_type;
-use std::collections::HashMap;
 
 impl Parser {
     pub fn parse_to_headers(&mut self) -> Vec<AstHeader> {
@@ -27,11 +26,18 @@ impl Parser {
                 }
             },
             TokenType::FnKeyword => self.parse_function(),
-            TokenType::StructKeyword => self.parse_struct(),
+            TokenType::StructKeyword => self.parse_struct(false),
+            // `shared struct Name { .. }`, objects that can be sent to other threads.
+            TokenType::SharedKeyword => {
+                match_token_type!(in self, let struct_tok: TokenType::StructKeyword => TokenType::StructKeyword);
+                self.parse_struct(true)
+            }
+            TokenType::EnumKeyword => self.parse_enum(),
+            TokenType::InterfaceKeyword => self.parse_interface(),
             _ => {
                 self.errors.push((
                     format!(
-                        "expected FnKeyword or StructKeyword, found {:?}",
+                        "expected FnKeyword, StructKeyword, SharedKeyword, EnumKeyword or InterfaceKeyword, found {:?}",
                         keyword_tok.token_type
                     ),
                     keyword_tok.span.clone(),
@@ -41,8 +47,35 @@ impl Parser {
         }
     }
 
-    pub fn parse_struct(&mut self) -> Option<AstHeader> {
+    pub fn parse_struct(&mut self, shared: bool) -> Option<AstHeader> {
         let name = self.parse_identifier().ok()?;
+        let generics = match self.parse_generic_parameters() {
+            Ok(generics) => generics,
+            Err(err) => {
+                self.errors.push(err);
+                return None;
+            }
+        };
+
+        // `struct Name: Interface, Other { .. }` declares the interfaces the struct implements.
+        let mut implements = Vec::new();
+        if self.peek_is(TokenType::Colon) {
+            self.tokens.next();
+            loop {
+                let start = self.tokens.index;
+                match self.parse_base_type() {
+                    Ok(interface) => implements.push((interface, self.tokens.vector[start].clone())),
+                    Err(err) => {
+                        self.errors.push(err);
+                        return None;
+                    }
+                }
+                if !self.peek_is(TokenType::Comma) {
+                    break;
+                }
+                self.tokens.next();
+            }
+        }
         match_token_type!(in self, let open_brace_tok: TokenType::OpenParen => TokenType::OpenBrace);
 
         let mut fields = Vec::new();
@@ -75,7 +108,78 @@ impl Parser {
 
         match_token_type!(in self, let close_brace_tok: TokenType::CloseParen => TokenType::CloseBrace);
 
-        Some(AstHeader::Struct { name, fields })
+        Some(AstHeader::Struct {
+            name,
+            generics,
+            fields,
+            implements,
+            shared,
+        })
+    }
+
+    pub fn parse_enum(&mut self) -> Option<AstHeader> {
+        let name = match self.parse_identifier() {
+            Ok(name) => name,
+            Err(err) => {
+                self.errors.push(err);
+                return None;
+            }
+        };
+        match_token_type!(in self, let open_brace_tok: TokenType::OpenBrace => TokenType::OpenBrace);
+
+        let mut variants = Vec::new();
+        loop {
+            let Some(next_tok) = self.tokens.peek().cloned() else {
+                break;
+            };
+            match next_tok.token_type.clone() {
+                TokenType::CloseBrace => break,
+                TokenType::Identifier { content } => {
+                    self.tokens.next();
+
+                    let mut fields = Vec::new();
+                    if self.peek_is(TokenType::OpenParen) {
+                        self.tokens.next();
+                        while !self.peek_is(TokenType::CloseParen) {
+                            match self.parse_type() {
+                                Ok(ty) => fields.push(ty),
+                                Err(err) => {
+                                    self.errors.push(err);
+                                    return None;
+                                }
+                            }
+                            if !self.peek_is(TokenType::CloseParen)
+                                && let Err(err) = self.expect_token(TokenType::Comma)
+                            {
+                                self.errors.push(err);
+                                return None;
+                            }
+                        }
+                        self.tokens.next();
+                    }
+                    variants.push(AstEnumVariant {
+                        name: content,
+                        fields,
+                        token: next_tok,
+                    });
+
+                    if self.peek_is(TokenType::Comma) {
+                        self.tokens.next();
+                    }
+                }
+                ty => {
+                    self.errors.push((
+                        format!("expected Identifier or CloseBrace, found {:?}", ty),
+                        next_tok.span,
+                    ));
+                    return None;
+                }
+            }
+        }
+
+        match_token_type!(in self, let close_brace_tok: TokenType::CloseBrace => TokenType::CloseBrace);
+
+        Some(AstHeader::Enum { name, variants })
     }
 
     pub fn parse_function(&mut self) -> Option<AstHeader> {
@@ -84,8 +188,14 @@ impl Parser {
             self.errors.push(ident.unwrap_err());
             return None;
         };
-        match_token_type!(in self, let open_paren_tok: TokenType::OpenParen => TokenType::OpenParen);
-        match_token_type!(in self, let close_paren_tok: TokenType::CloseParen => TokenType::CloseParen);
+        let generics = match self.parse_generic_parameters() {
+            Ok(generics) => generics,
+            Err(err) => {
+                self.errors.push(err);
+                return None;
+            }
+        };
+        let parameters = self.parse_parameters(&function_name)?;
         match_token_type!(in self, let arrow_tok: TokenType::Arrow => TokenType::Arrow);
 
         let return_type = match self.parse_type() {
@@ -101,10 +211,131 @@ impl Parser {
         };
         Some(AstHeader::Function {
             name: function_name,
-            parameters: vec![],
+            generics,
+            parameters,
             returns: return_type,
             code_block,
-            locals: HashMap::new(),
+            locals: vec![],
+        })
+    }
+
+    /// Parses a parenthesized parameter list.
+    ///
+    /// A leading `self` without a type takes the type the function is declared on,
+    /// `Type` in `fn Type::name(self)`.
+    pub(crate) fn parse_parameters(&mut self, function_name: &PathData) -> Option<Vec<(AstType, PathData)>> {
+        match_token_type!(in self, let open_paren_tok: TokenType::OpenParen => TokenType::OpenParen);
+
+        let mut parameters = Vec::new();
+        loop {
+            let Some(next_tok) = self.tokens.peek().cloned() else {
+                break;
+            };
+            match next_tok.token_type {
+                TokenType::CloseParen => break,
+                TokenType::Identifier { ref content } => {
+                    self.tokens.next();
+                    let parameter = PathData {
+                        name: content.clone(),
+                        token: next_tok.clone(),
+                    };
+                    if parameter.name == "self" && parameters.is_empty() && !self.peek_is(TokenType::Colon) {
+                        let Some((type_name, _)) = function_name.name.rsplit_once("::") else {
+                            self.errors.push((
+                                "only functions declared on a type can take `self`".to_string(),
+                                next_tok.span,
+                            ));
+                            return None;
+                        };
+                        parameters.push((AstType::Structure(type_name.to_string()), parameter));
+                    } else {
+                        match_token_type!(in self, let colon: TokenType::Colon => TokenType::Colon);
+                        let ty = match self.parse_type() {
+                            Ok(t) => t,
+                            Err(err) => {
+                                self.errors.push(err);
+                                return None;
+                            }
+                        };
+                        parameters.push((ty, parameter));
+                    }
+
+                    if let Some(peeked) = self.tokens.peek()
+                        && peeked.token_type == TokenType::Comma
+                    {
+                        self.tokens.next();
+                    }
+                }
+                ty => {
+                    self.errors.push((
+                        format!("expected Identifier or CloseParen, found {:?}", ty),
+                        next_tok.span,
+                    ));
+                    return None;
+                }
+            }
+        }
+
+        match_token_type!(in self, let close_paren_tok: TokenType::CloseParen => TokenType::CloseParen);
+        Some(parameters)
+    }
+
+    pub fn parse_interface(&mut self) -> Option<AstHeader> {
+        let name = match self.parse_identifier() {
+            Ok(name) => name,
+            Err(err) => {
+                self.errors.push(err);
+                return None;
+            }
+        };
+        let generics = match self.parse_generic_parameters() {
+            Ok(generics) => generics,
+            Err(err) => {
+                self.errors.push(err);
+                return None;
+            }
+        };
+        match_token_type!(in self, let open_brace_tok: TokenType::OpenBrace => TokenType::OpenBrace);
+
+        let mut methods = Vec::new();
+        while !self.peek_is(TokenType::CloseBrace) {
+            match_token_type!(in self, let fn_tok: TokenType::FnKeyword => TokenType::FnKeyword);
+            let method_name = match self.parse_identifier() {
+                Ok(method_name) => method_name,
+                Err(err) => {
+                    self.errors.push(err);
+                    return None;
+                }
+            };
+            // Methods take `self` as the interface type, like `fn Interface::method(self)` would.
+            let qualified_name = PathData {
+                name: format!("{}::{}", name.name, method_name.name),
+                token: method_name.token.clone(),
+            };
+            let parameters = self.parse_parameters(&qualified_name)?;
+            match_token_type!(in self, let arrow_tok: TokenType::Arrow => TokenType::Arrow);
+            let returns = match self.parse_type() {
+                Ok(t) => t,
+                Err(err) => {
+                    self.errors.push(err);
+                    AstType::Invalid
+                }
+            };
+            match_token_type!(in self, let semicolon: TokenType::Semicolon => TokenType::Semicolon);
+
+            methods.push(AstInterfaceMethod {
+                name: method_name,
+                parameters,
+                returns,
+            });
+        }
+
+        match_token_type!(in self, let close_brace_tok: TokenType::CloseBrace => TokenType::CloseBrace);
+
+        Some(AstHeader::Interface {
+            name,
+            generics,
+            methods,
         })
     }
 }
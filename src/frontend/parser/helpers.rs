@@ -1,9 +1,31 @@
-use crate::frontend::lexer::tokens::TokenType;
+use crate::frontend::lexer::tokens::{Token, TokenType};
 use crate::frontend::parser::ast::{AstType, PathData};
 use crate::frontend::parser::core::Parser;
 use crate::frontend::span::Span;
 
 impl Parser {
+    pub fn expect_token(&mut self, expected: TokenType) -> Result<Token, (String, Span)> {
+        let Some(tok) = self.tokens.next().cloned() else {
+            return Err((
+                format!("expected {:?}, found EOF", expected),
+                self.tokens.vector.last().cloned().unwrap().span,
+            ));
+        };
+        if tok.token_type != expected {
+            return Err((
+                format!("expected {:?}, found {:?}", expected, tok.token_type),
+                tok.span,
+            ));
+        }
+        Ok(tok)
+    }
+
+    pub fn peek_is(&mut self, expected: TokenType) -> bool {
+        self.tokens
+            .peek()
+            .is_some_and(|tok| tok.token_type == expected)
+    }
+
     pub fn parse_identifier(&mut self) -> Result<PathData, (String, Span)> {
         let mut final_identifier = String::new();
         let mut tokens = Vec::new();
@@ -59,7 +81,7 @@ impl Parser {
                 TokenType::VerticalLine => {
                     self.tokens.next();
                     let other_type = self.parse_type()?;
-                    ty = AstType::UnionOf(Box::new(ty), Box::new(other_type));
+                    ty = AstType::union(vec![ty, other_type]);
                 }
                 _ => break,
             }
@@ -69,14 +91,109 @@ impl Parser {
     }
 
     pub fn parse_base_type(&mut self) -> Result<AstType, (String, Span)> {
+        if let Some(tok) = self.tokens.peek()
+            && tok.token_type == TokenType::NoneKeyword
+        {
+            self.tokens.next();
+            return Ok(AstType::None);
+        }
+        if self.peek_is(TokenType::FnKeyword) {
+            return self.parse_function_type();
+        }
+        // `weak T | none` is a union with `none`, `weak` only applies to `T`.
+        if self.peek_is(TokenType::WeakKeyword) {
+            self.tokens.next();
+            return Ok(AstType::Weak(Box::new(self.parse_base_type()?)));
+        }
+        if self.peek_is(TokenType::OpenBracket) {
+            self.tokens.next();
+            let element = self.parse_type()?;
+            self.expect_token(TokenType::CloseBracket)?;
+            return Ok(AstType::ArrayOf(Box::new(element)));
+        }
         let identifier = self.parse_identifier()?;
-        match identifier.name.as_str() {
-            "i32" => Ok(AstType::Int32),
-            "i64" => Ok(AstType::Int64),
-            "f32" => Ok(AstType::Float32),
-            "f64" => Ok(AstType::Float64),
-            "void" => Ok(AstType::Void),
-            _ => Ok(AstType::Structure(identifier.name)),
+        if let Some(ty) = AstType::from_name(&identifier.name) {
+            return Ok(ty);
+        }
+        if !self.peek_is(TokenType::LessThan) {
+            return Ok(AstType::Structure(identifier.name));
+        }
+
+        self.tokens.next();
+        let mut arguments = Vec::new();
+        loop {
+            arguments.push(self.parse_type()?);
+            if !self.peek_is(TokenType::Comma) {
+                break;
+            }
+            self.tokens.next();
+        }
+        self.expect_closing_angle()?;
+        Ok(AstType::Instance(identifier.name, arguments))
+    }
+
+    /// Parses `fn(i32, i32) -> i32`.
+    ///
+    /// The return type extends over unions, `fn() -> i32 | none` returns `i32 | none`.
+    fn parse_function_type(&mut self) -> Result<AstType, (String, Span)> {
+        self.expect_token(TokenType::FnKeyword)?;
+        self.expect_token(TokenType::OpenParen)?;
+        let mut parameters = Vec::new();
+        while !self.peek_is(TokenType::CloseParen) {
+            parameters.push(self.parse_type()?);
+            if !self.peek_is(TokenType::Comma) {
+                break;
+            }
+            self.tokens.next();
+        }
+        self.expect_token(TokenType::CloseParen)?;
+        self.expect_token(TokenType::Arrow)?;
+        let returns = self.parse_type()?;
+        Ok(AstType::Function(parameters, Box::new(returns)))
+    }
+
+    /// Parses the names of the type parameters of a header, `<T, U>`, if there are any.
+    pub fn parse_generic_parameters(&mut self) -> Result<Vec<String>, (String, Span)> {
+        let mut generics = Vec::new();
+        if !self.peek_is(TokenType::LessThan) {
+            return Ok(generics);
+        }
+        self.tokens.next();
+        loop {
+            let tok = self.tokens.next().cloned();
+            let Some(Token {
+                token_type: TokenType::Identifier { content },
+                ..
+            }) = tok
+            else {
+                return Err((
+                    format!("expected Identifier, found {:?}", tok.as_ref().map(|x| &x.token_type)),
+                    self.tokens.vector[self.tokens.index - 1].span.clone(),
+                ));
+            };
+            generics.push(content);
+            if !self.peek_is(TokenType::Comma) {
+                break;
+            }
+            self.tokens.next();
+        }
+        self.expect_closing_angle()?;
+        Ok(generics)
+    }
+
+    /// Consumes the `>` closing a list of type arguments.
+    ///
+    /// `List<List<i32>>` lexes its closing brackets as one `>>`, which is split so the
+    /// outer list can consume its own `>`.
+    fn expect_closing_angle(&mut self) -> Result<(), (String, Span)> {
+        if let Some(tok) = self.tokens.peek()
+            && tok.token_type == TokenType::ShiftRight
+        {
+            let index = self.tokens.index;
+            self.tokens.vector[index].token_type = TokenType::GreaterThan;
+            return Ok(());
         }
+        self.expect_token(TokenType::GreaterThan)?;
+        Ok(())
     }
 }
@@ -3,6 +3,7 @@ pub mod core;
 mod exprs;
 pub mod headers;
 mod helpers;
+mod patterns;
 mod stmts;
 
 #[macro_export]
@@ -0,0 +1,222 @@
+use crate::frontend::lexer::tokens::{Token, TokenType};
+use crate::frontend::parser::ast::{
+    AstCodeBlock, AstExpression, AstMatchArm, AstPattern, AstType, PathData,
+};
+use crate::frontend::parser::core::Parser;
+use crate::frontend::span::Span;
+use std::cell::OnceCell;
+
+impl Parser {
+    pub(crate) fn parse_match(&mut self, match_tok: Token) -> Result<AstExpression, (String, Span)> {
+        let value = self.parse_condition()?;
+        self.expect_token(TokenType::OpenBrace)?;
+        let in_condition = std::mem::replace(&mut self.in_condition, false);
+        let arms = self.parse_match_arms();
+        self.in_condition = in_condition;
+
+        Ok(AstExpression::Match {
+            value: Box::new(value),
+            arms: arms?,
+            ty: OnceCell::new(),
+            token: match_tok,
+        })
+    }
+
+    fn parse_match_arms(&mut self) -> Result<Vec<AstMatchArm>, (String, Span)> {
+        let mut arms = Vec::new();
+        while !self.peek_is(TokenType::CloseBrace) {
+            let pattern = self.parse_pattern()?;
+            let arrow_tok = self.expect_token(TokenType::FatArrow)?;
+
+            let body = if self.peek_is(TokenType::OpenBrace) {
+                let Some(block) = self.parse_code_block() else {
+                    return Err(("expected code block after FatArrow".to_string(), arrow_tok.span));
+                };
+                if self.peek_is(TokenType::Comma) {
+                    self.tokens.next();
+                }
+                block
+            } else {
+                let expr = self.parse_expression()?;
+                if !self.peek_is(TokenType::CloseBrace) {
+                    self.expect_token(TokenType::Comma)?;
+                }
+                AstCodeBlock {
+                    statements: vec![],
+                    tail: Some(Box::new(expr)),
+                    locals: vec![],
+                }
+            };
+
+            arms.push(AstMatchArm {
+                pattern,
+                body,
+                locals: vec![],
+                token: arrow_tok,
+            });
+        }
+        self.expect_token(TokenType::CloseBrace)?;
+        Ok(arms)
+    }
+
+    pub fn parse_pattern(&mut self) -> Result<AstPattern, (String, Span)> {
+        let Some(tok) = self.tokens.peek().cloned() else {
+            return Err((
+                "expected pattern, found EOF".to_string(),
+                self.tokens.vector.last().cloned().unwrap().span,
+            ));
+        };
+        match &tok.token_type {
+            TokenType::NoneKeyword => {
+                self.tokens.next();
+                Ok(AstPattern::TypeTest {
+                    binding: None,
+                    ty: AstType::None,
+                    token: tok,
+                })
+            }
+            TokenType::TrueKeyword
+            | TokenType::FalseKeyword
+            | TokenType::StringValue { .. } => Ok(AstPattern::Literal(self.parse_expression()?)),
+            TokenType::Number { .. } => {
+                let start = self.parse_literal_value()?;
+                let Some(range_tok) = self.tokens.peek().cloned() else {
+                    return Ok(AstPattern::Literal(start));
+                };
+                if range_tok.token_type != TokenType::DoubleDot {
+                    return Ok(AstPattern::Literal(start));
+                }
+                self.tokens.next();
+                let inclusive = self.peek_is(TokenType::Equal);
+                if inclusive {
+                    self.tokens.next();
+                }
+                let end = self.parse_literal_value()?;
+                Ok(AstPattern::Range {
+                    start,
+                    end,
+                    inclusive,
+                    token: range_tok,
+                })
+            }
+            TokenType::Identifier { content } => {
+                let next = self.tokens.vector.get(self.tokens.index + 1).cloned();
+                match next.map(|next| next.token_type) {
+                    Some(TokenType::Colon) => {
+                        self.tokens.next();
+                        self.tokens.next();
+                        let ty = self.parse_base_type()?;
+                        let binding = (content != "_").then(|| (content.clone(), OnceCell::new()));
+                        Ok(AstPattern::TypeTest {
+                            binding,
+                            ty,
+                            token: tok,
+                        })
+                    }
+                    Some(TokenType::OpenBrace)
+                    | Some(TokenType::OpenParen)
+                    | Some(TokenType::DoubleColon) => self.parse_path_pattern(),
+                    _ if content == "_" => {
+                        self.tokens.next();
+                        Ok(AstPattern::Wildcard { token: tok })
+                    }
+                    _ => {
+                        self.tokens.next();
+                        Ok(AstPattern::Binding {
+                            name: content.clone(),
+                            token: tok,
+                            local: OnceCell::new(),
+                        })
+                    }
+                }
+            }
+            other => Err((format!("expected pattern, found {:?}", other), tok.span)),
+        }
+    }
+
+    /// Parses the patterns that start with a path, `Name { .. }`, `Enum::Variant`
+    /// and `Enum::Variant(..)`.
+    fn parse_path_pattern(&mut self) -> Result<AstPattern, (String, Span)> {
+        let name = self.parse_identifier()?;
+        if self.peek_is(TokenType::OpenBrace) {
+            return self.parse_structure_pattern(name);
+        }
+
+        let mut fields = Vec::new();
+        if self.peek_is(TokenType::OpenParen) {
+            self.tokens.next();
+            while !self.peek_is(TokenType::CloseParen) {
+                fields.push(self.parse_pattern()?);
+                if !self.peek_is(TokenType::CloseParen) {
+                    self.expect_token(TokenType::Comma)?;
+                }
+            }
+            self.expect_token(TokenType::CloseParen)?;
+        }
+        Ok(AstPattern::Variant {
+            name,
+            fields,
+            ty: OnceCell::new(),
+        })
+    }
+
+    fn parse_structure_pattern(&mut self, name: PathData) -> Result<AstPattern, (String, Span)> {
+        self.expect_token(TokenType::OpenBrace)?;
+
+        let mut fields = Vec::new();
+        while !self.peek_is(TokenType::CloseBrace) {
+            let field_tok = self.tokens.next().cloned();
+            let Some(Token {
+                token_type: TokenType::Identifier { content: field },
+                ..
+            }) = field_tok.clone()
+            else {
+                return Err((
+                    format!("expected Identifier, found {:?}", field_tok.map(|x| x.token_type)),
+                    self.tokens.vector[self.tokens.index - 1].span.clone(),
+                ));
+            };
+
+            // `field` alone is shorthand for `field: field`.
+            let pattern = if self.peek_is(TokenType::Colon) {
+                self.tokens.next();
+                self.parse_pattern()?
+            } else {
+                AstPattern::Binding {
+                    name: field.clone(),
+                    token: field_tok.unwrap(),
+                    local: OnceCell::new(),
+                }
+            };
+            fields.push((field, pattern));
+
+            if !self.peek_is(TokenType::CloseBrace) {
+                self.expect_token(TokenType::Comma)?;
+            }
+        }
+        self.expect_token(TokenType::CloseBrace)?;
+
+        Ok(AstPattern::Structure {
+            name,
+            fields,
+            ty: OnceCell::new(),
+        })
+    }
+
+    fn parse_literal_value(&mut self) -> Result<AstExpression, (String, Span)> {
+        let Some(tok) = self.tokens.next().cloned() else {
+            return Err((
+                "expected Number, found EOF".to_string(),
+                self.tokens.vector.last().cloned().unwrap().span,
+            ));
+        };
+        let TokenType::Number { content } = tok.token_type.clone() else {
+            return Err((format!("expected Number, found {:?}", tok.token_type), tok.span));
+        };
+        Ok(AstExpression::NumberLiteral {
+            content,
+            ty: OnceCell::new(),
+            token: tok,
+        })
+    }
+}
@@ -1,9 +1,9 @@
-use crate::frontend::lexer::tokens::TokenType;
-use crate::frontend::parser::ast::{AstCodeBlock, AstStatement};
+use crate::frontend::lexer::tokens::{Token, TokenType};
+use crate::frontend::parser::ast::{AstCodeBlock, AstExpression, AstIterable, AstStatement};
 use crate::frontend::parser::core::Parser;
 use crate::frontend::span::Span;
 use crate::match_token_type;
-use std::collections::HashMap;
+use std::cell::OnceCell;
 
 impl Parser {
     pub fn parse_code_block(&mut self) -> Option<AstCodeBlock> {
@@ -14,12 +14,45 @@ impl Parser {
             if let Some(peeked) = self.tokens.peek().cloned() {
                 if peeked.token_type == TokenType::CloseBrace {
                     match_token_type!(in self, let close_brace_tok: TokenType::CloseBrace => TokenType::CloseBrace);
-                    return Some(AstCodeBlock { statements: stmts });
+                    return Some(AstCodeBlock {
+                        statements: stmts,
+                        tail: None,
+                        locals: vec![],
+                    });
                 }
                 let stmt = self.parse_statement();
                 match stmt {
+                    Ok(AstStatement::Expression(expr)) => {
+                        if let Some(peeked) = self.tokens.peek()
+                            && peeked.token_type == TokenType::CloseBrace
+                        {
+                            self.tokens.next();
+                            return Some(AstCodeBlock {
+                                statements: stmts,
+                                tail: Some(Box::new(expr)),
+                                locals: vec![],
+                            });
+                        }
+                        // Like other block-like statements, a match doesn't need a semicolon.
+                        if let AstExpression::Match { .. } = expr
+                            && !self.peek_is(TokenType::Semicolon)
+                        {
+                            stmts.push(AstStatement::Expression(expr));
+                            continue;
+                        }
+                        stmts.push(AstStatement::Expression(expr));
+                    }
                     Ok(ok) => {
+                        let block_like = matches!(
+                            ok,
+                            AstStatement::IfStatement { .. }
+                                | AstStatement::WhileStatement { .. }
+                                | AstStatement::ForEach { .. }
+                        );
                         stmts.push(ok);
+                        if block_like {
+                            continue;
+                        }
                     }
                     Err(err) => {
                         while let Some(peeked) = self.tokens.peek().cloned()
@@ -45,13 +78,226 @@ impl Parser {
                 self.tokens.vector.last().cloned().unwrap().span,
             ));
         };
-        match tok.token_type {
+        match &tok.token_type {
             TokenType::LoopKeyword => Err(("loops are not implemented yet".to_string(), tok.span)),
-            TokenType::IfKeyword => Err((
-                "if statements are not implemented yet".to_string(),
-                tok.span,
-            )),
-            _ => Ok(AstStatement::Expression(self.parse_expression()?)),
+            TokenType::IfKeyword => self.parse_if_statement(),
+            TokenType::WhileKeyword => {
+                self.tokens.next();
+                let cond = self.parse_condition()?;
+                let Some(do_true) = self.parse_code_block() else {
+                    return Err(("expected code block after while condition".to_string(), tok.span));
+                };
+                Ok(AstStatement::WhileStatement { cond, do_true })
+            }
+            TokenType::ForEachKeyword => self.parse_foreach_statement(),
+            TokenType::ReturnKeyword => {
+                self.tokens.next();
+                if let Some(peeked) = self.tokens.peek()
+                    && peeked.token_type == TokenType::Semicolon
+                {
+                    return Ok(AstStatement::Return {
+                        value: None,
+                        token: tok,
+                    });
+                }
+                Ok(AstStatement::Return {
+                    value: Some(self.parse_expression()?),
+                    token: tok,
+                })
+            }
+            TokenType::LetKeyword => self.parse_variable_declaration(),
+            TokenType::Identifier { content }
+                if self
+                    .tokens
+                    .vector
+                    .get(self.tokens.index + 1)
+                    .is_some_and(|x| x.token_type == TokenType::Equal) =>
+            {
+                self.tokens.next();
+                self.tokens.next();
+                Ok(AstStatement::ModifyVariable {
+                    name: content.clone(),
+                    ty: OnceCell::new(),
+                    value: self.parse_expression()?,
+                    token: tok,
+                    local: OnceCell::new(),
+                })
+            }
+            _ => {
+                let expr = self.parse_expression()?;
+                let Some(equal_tok) = self.tokens.peek().cloned() else {
+                    return Ok(AstStatement::Expression(expr));
+                };
+                if equal_tok.token_type != TokenType::Equal {
+                    return Ok(AstStatement::Expression(expr));
+                }
+                self.tokens.next();
+                match expr {
+                    AstExpression::FieldAccess {
+                        value: object,
+                        field,
+                        token,
+                        ..
+                    } => Ok(AstStatement::AssignField {
+                        object: *object,
+                        field,
+                        ty: OnceCell::new(),
+                        value: self.parse_expression()?,
+                        token,
+                    }),
+                    AstExpression::Index {
+                        base, other, token, ..
+                    } => Ok(AstStatement::AssignIndex {
+                        base: *base,
+                        index: *other,
+                        ty: OnceCell::new(),
+                        value: self.parse_expression()?,
+                        token,
+                    }),
+                    _ => Err((
+                        "only variables, fields and array elements can be assigned to".to_string(),
+                        equal_tok.span,
+                    )),
+                }
+            }
+        }
+    }
+
+    fn parse_variable_declaration(&mut self) -> Result<AstStatement, (String, Span)> {
+        self.tokens.next();
+        let Some(name_tok) = self.tokens.next().cloned() else {
+            return Err((
+                "expected Identifier, found EOF".to_string(),
+                self.tokens.vector.last().cloned().unwrap().span,
+            ));
+        };
+        let TokenType::Identifier { content: name } = name_tok.token_type.clone() else {
+            return Err((
+                format!("expected Identifier, found {:?}", name_tok.token_type),
+                name_tok.span,
+            ));
+        };
+
+        let mut declared_ty = None;
+        if let Some(peeked) = self.tokens.peek()
+            && peeked.token_type == TokenType::Colon
+        {
+            self.tokens.next();
+            declared_ty = Some(self.parse_type()?);
+        }
+
+        let Some(equal_tok) = self.tokens.next().cloned() else {
+            return Err((
+                "expected Equal, found EOF".to_string(),
+                self.tokens.vector.last().cloned().unwrap().span,
+            ));
+        };
+        if equal_tok.token_type != TokenType::Equal {
+            return Err((
+                format!("expected Equal, found {:?}", equal_tok.token_type),
+                equal_tok.span,
+            ));
         }
+
+        Ok(AstStatement::DeclareVariable {
+            name,
+            declared_ty,
+            ty: OnceCell::new(),
+            value: self.parse_expression()?,
+            token: name_tok,
+            local: OnceCell::new(),
+        })
+    }
+
+    fn parse_foreach_statement(&mut self) -> Result<AstStatement, (String, Span)> {
+        let foreach_tok = self.expect_token(TokenType::ForEachKeyword)?;
+        let name_tok = self.tokens.next().cloned();
+        let Some(Token {
+            token_type: TokenType::Identifier { content: name },
+            ..
+        }) = name_tok
+        else {
+            return Err((
+                format!(
+                    "expected Identifier, found {:?}",
+                    name_tok.as_ref().map(|tok| &tok.token_type)
+                ),
+                self.tokens.vector[self.tokens.index - 1].span.clone(),
+            ));
+        };
+        self.expect_token(TokenType::InKeyword)?;
+
+        let value = self.parse_condition()?;
+        let iterable = if self.peek_is(TokenType::DoubleDot) {
+            self.tokens.next();
+            let inclusive = self.peek_is(TokenType::Equal);
+            if inclusive {
+                self.tokens.next();
+            }
+            AstIterable::Range {
+                start: value,
+                end: self.parse_condition()?,
+                inclusive,
+            }
+        } else {
+            AstIterable::Value { value, next: None }
+        };
+
+        let Some(do_true) = self.parse_code_block() else {
+            return Err(("expected code block after foreach".to_string(), foreach_tok.span));
+        };
+        Ok(AstStatement::ForEach {
+            name,
+            iterable,
+            do_true,
+            ty: OnceCell::new(),
+            token: foreach_tok,
+            local: OnceCell::new(),
+            source: OnceCell::new(),
+            index: OnceCell::new(),
+        })
+    }
+
+    fn parse_if_statement(&mut self) -> Result<AstStatement, (String, Span)> {
+        let Some(if_tok) = self.tokens.next().cloned() else {
+            return Err((
+                "expected IfKeyword, found EOF".to_string(),
+                self.tokens.vector.last().cloned().unwrap().span,
+            ));
+        };
+        let cond = self.parse_condition()?;
+        let Some(if_true) = self.parse_code_block() else {
+            return Err(("expected code block after if condition".to_string(), if_tok.span));
+        };
+
+        let if_false = match self.tokens.peek().cloned() {
+            Some(else_tok) if else_tok.token_type == TokenType::ElseKeyword => {
+                self.tokens.next();
+                match self.tokens.peek() {
+                    Some(peeked) if peeked.token_type == TokenType::IfKeyword => AstCodeBlock {
+                        statements: vec![self.parse_if_statement()?],
+                        tail: None,
+                        locals: vec![],
+                    },
+                    _ => {
+                        let Some(if_false) = self.parse_code_block() else {
+                            return Err(("expected code block after else".to_string(), else_tok.span));
+                        };
+                        if_false
+                    }
+                }
+            }
+            _ => AstCodeBlock {
+                statements: vec![],
+                tail: None,
+                locals: vec![],
+            },
+        };
+
+        Ok(AstStatement::IfStatement {
+            cond,
+            if_true,
+            if_false,
+        })
     }
 }
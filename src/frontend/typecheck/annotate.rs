@@ -1,44 +1,240 @@
 use std::cell::OnceCell;
-use std::collections::HashMap;
 use std::str::FromStr;
-use crate::frontend::parser::ast::{AstCodeBlock, AstExpression, AstHeader, AstStatement, AstType};
-use crate::frontend::typecheck::data::TypeInformation;
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstCodeBlock, AstExpression, AstHeader, AstStatement, AstType, PathData};
+use crate::frontend::typecheck::arrays::array_method;
+use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
+use crate::frontend::typecheck::loops::ForEachLocals;
+use crate::frontend::typecheck::memory::{builtin_function, weak_method, SPAWN};
+use crate::frontend::typecheck::scope::Scopes;
+
+impl AstHeader {
+    pub fn annotate_type_information(&mut self, type_information: &mut TypeInformation) {
+        if self.is_generic() {
+            return;
+        }
+        if let AstHeader::Function {
+            parameters,
+            returns,
+            code_block,
+            locals,
+            ..
+        } = self
+        {
+            let mut scopes = Scopes::new(returns.clone());
+            scopes.declare_parameters(parameters, &mut type_information.errors);
+            code_block.annotate_type_information(type_information, &mut scopes);
+            *locals = scopes.locals;
+        }
+    }
+}
 
 impl AstCodeBlock {
+    /// Annotates the block. Tails are only allowed where they return from the
+    /// function, so they are annotated expecting its return type.
     pub fn annotate_type_information(
         &mut self,
-        type_information: &TypeInformation,
-        locals: &mut HashMap<String, AstType>
+        type_information: &mut TypeInformation,
+        scopes: &mut Scopes
+    ) {
+        let returns = scopes.returns.clone();
+        self.annotate_expecting(Some(&returns), type_information, scopes);
+    }
+
+    /// Annotates the block, with the tail expecting `expected` if there is one.
+    pub fn annotate_expecting(
+        &mut self,
+        expected: Option<&AstType>,
+        type_information: &mut TypeInformation,
+        scopes: &mut Scopes
     ) {
+        scopes.push();
         for stmt in &mut self.statements {
-            stmt.annotate_type_information(type_information, locals);
+            stmt.annotate_type_information(type_information, scopes);
         }
+        if let Some(tail) = &mut self.tail {
+            match expected {
+                Some(expected) => tail.annotate_expecting(expected, type_information, scopes),
+                None => tail.annotate_type_information(type_information, scopes),
+            };
+        }
+        self.locals = scopes.pop();
     }
 }
 
 impl AstStatement {
     pub fn annotate_type_information(
         &mut self,
-        type_information: &TypeInformation,
-        locals: &mut HashMap<String, AstType>
+        type_information: &mut TypeInformation,
+        scopes: &mut Scopes
     ) {
         match self {
             AstStatement::Comment(content) => {}
             AstStatement::Expression(expr) => {
-                expr.annotate_type_information(type_information, locals);
+                expr.annotate_type_information(type_information, scopes);
             }
-            AstStatement::ModifyVariable { name, ty, value } => {
-                let var_ty = value.annotate_type_information(type_information, locals);
-                locals.insert(name.clone(), var_ty);
+            AstStatement::DeclareVariable {
+                name,
+                declared_ty,
+                ty,
+                value,
+                token,
+                local,
+            } => {
+                let var_ty = match declared_ty {
+                    Some(declared_ty) => {
+                        *declared_ty = type_information.resolve_type(declared_ty, &token.span);
+                        value.annotate_coerced(declared_ty, type_information, scopes);
+                        declared_ty.clone()
+                    }
+                    None => value.annotate_type_information(type_information, scopes),
+                };
+                let _ = ty.set(var_ty.clone());
+                let _ = local.set(scopes.declare(name.clone(), var_ty, token.span.clone()));
+            }
+            AstStatement::ModifyVariable {
+                name,
+                ty,
+                value,
+                token,
+                local,
+            } => {
+                let Some(index) = resolve_local(name, token, type_information, scopes) else {
+                    value.annotate_type_information(type_information, scopes);
+                    let _ = ty.set(AstType::Invalid);
+                    return;
+                };
+                if scopes.is_captured(index) {
+                    type_information.errors.push((
+                        format!("can not assign to `{}`, closures capture variables by value", name),
+                        token.span.clone(),
+                    ));
+                }
+                let var_ty = scopes.get(index).ty.clone();
+                value.annotate_coerced(&var_ty, type_information, scopes);
+                scopes.forget_narrowing(index);
+                let _ = ty.set(var_ty);
+                let _ = local.set(index);
             }
             AstStatement::IfStatement {
                 cond,
                 if_true,
                 if_false,
             } => {
-                cond.annotate_type_information(type_information, locals);
+                cond.annotate_condition(type_information, scopes);
+
+                // `if value is T` narrows `value` to `T` in the first branch and to the
+                // remaining members of its union in the second.
+                let narrowing = match cond {
+                    AstExpression::Is { value, target, .. } => match value.as_ref() {
+                        AstExpression::VariableLiteral { local, .. } => local
+                            .get()
+                            .map(|index| (*index, target.clone(), scopes.type_of(*index))),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+
+                scopes.push();
+                if let Some((index, target, _)) = &narrowing {
+                    scopes.narrow(*index, target.clone());
+                }
+                if_true.annotate_type_information(type_information, scopes);
+                scopes.pop();
+
+                scopes.push();
+                if let Some((index, target, union)) = &narrowing {
+                    let remaining = union
+                        .union_members()
+                        .into_iter()
+                        .filter(|member| member != target)
+                        .collect::<Vec<_>>();
+                    if !remaining.is_empty() {
+                        scopes.narrow(*index, AstType::union(remaining));
+                    }
+                }
+                if_false.annotate_type_information(type_information, scopes);
+                scopes.pop();
+            }
+            AstStatement::WhileStatement { cond, do_true } => {
+                cond.annotate_condition(type_information, scopes);
+                do_true.annotate_type_information(type_information, scopes);
+            }
+            AstStatement::ForEach {
+                name,
+                iterable,
+                do_true,
+                ty,
+                token,
+                local,
+                source,
+                index,
+            } => AstStatement::annotate_foreach(
+                name,
+                iterable,
+                do_true,
+                token,
+                ForEachLocals {
+                    ty,
+                    local,
+                    source,
+                    index,
+                },
+                type_information,
+                scopes,
+            ),
+            AstStatement::AssignIndex {
+                base,
+                index,
+                ty,
+                value,
+                token,
+            } => {
+                let element = AstExpression::annotate_index(base, index, token, type_information, scopes);
+                if element == AstType::Invalid {
+                    value.annotate_type_information(type_information, scopes);
+                } else {
+                    value.annotate_coerced(&element, type_information, scopes);
+                }
+                let _ = ty.set(element);
+            }
+            AstStatement::AssignField {
+                object,
+                field,
+                ty,
+                value,
+                token,
+            } => {
+                let object_ty = object.annotate_type_information(type_information, scopes);
+                let field_ty = type_information.field_type(&object_ty, field, token);
+                if let AstType::Structure(name) = &object_ty
+                    && let Some(ProgramType::Structure { shared: true, .. }) = type_information.names.get(name)
+                {
+                    type_information.errors.push((
+                        format!("fields of shared struct `{}` can not be assigned, other threads may be using it", name),
+                        token.span.clone(),
+                    ));
+                }
+                if field_ty == AstType::Invalid {
+                    value.annotate_type_information(type_information, scopes);
+                } else {
+                    value.annotate_coerced(&field_ty, type_information, scopes);
+                }
+                let _ = ty.set(field_ty);
+            }
+            AstStatement::Return { value, .. } => {
+                // Returning a value from a void function is reported by the control flow check.
+                match value {
+                    Some(value) if scopes.returns != AstType::Void => {
+                        let returns = scopes.returns.clone();
+                        value.annotate_coerced(&returns, type_information, scopes);
+                    }
+                    Some(value) => {
+                        value.annotate_type_information(type_information, scopes);
+                    }
+                    None => {}
+                }
             }
-            AstStatement::WhileStatement { cond, do_true } => {}
         }
     }
 }
@@ -50,95 +246,516 @@ impl AstExpression {
         match self {
             AstExpression::NumberLiteral { ty, .. } => ty.get().cloned().unwrap(),
             AstExpression::StringLiteral { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::BooleanLiteral { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::NoneLiteral { ty, .. } => ty.get().cloned().unwrap(),
             AstExpression::VariableLiteral { ty, .. } => ty.get().cloned().unwrap(),
             AstExpression::PathLiteral(_) => AstType::Invalid,
             AstExpression::ArrayLiteral { ty, .. } => ty.get().cloned().unwrap(),
             AstExpression::StructureLiteral { ty, .. } => ty.clone(),
             AstExpression::TypeLiteral { ty, .. } => ty.clone(),
+            AstExpression::EnumLiteral { ty, .. } => ty.clone(),
+            AstExpression::Lambda { ty, .. } => ty.get().cloned().unwrap(),
             AstExpression::Add { ty, .. } => ty.get().cloned().unwrap(),
             AstExpression::Sub { ty, .. } => ty.get().cloned().unwrap(),
             AstExpression::Mul { ty, .. } => ty.get().cloned().unwrap(),
             AstExpression::Div { ty, .. } => ty.get().cloned().unwrap(),
             AstExpression::Mod { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::ShiftLeft { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::ShiftRight { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::Equal { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::NotEqual { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::LessThan { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::GreaterThan { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::LessThanOrEqual { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::GreaterThanOrEqual { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::Cast { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::Is { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::Match { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::Propagate { ty, .. } => ty.get().cloned().unwrap(),
+            AstExpression::FieldAccess { ty, .. } => ty.get().cloned().unwrap(),
             AstExpression::Invoke { return_type, .. } => return_type.get().cloned().unwrap(),
             AstExpression::Index { ty, .. } => ty.get().cloned().unwrap(),
         }
     }
 
+    pub fn annotate_condition(
+        &mut self,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) {
+        let cond_ty = self.annotate_type_information(type_data, scopes);
+        if cond_ty != AstType::Bool && cond_ty != AstType::Invalid {
+            type_data.errors.push((
+                format!("expected Bool, found {:?}", cond_ty),
+                self.get_span(),
+            ));
+        }
+    }
+
+    /// Annotates both operands of a binary operator and brings them to a common type,
+    /// widening one side if needed. Returns `None` if either side is invalid.
+    pub fn annotate_operands(
+        lhs: &mut AstExpression, rhs: &mut AstExpression,
+        op_tok: &Token, operator: &str,
+        type_data: &mut TypeInformation, scopes: &mut Scopes) -> Option<AstType> {
+        // Annotate the side that isn't just number literals first, so the literals
+        // can take on the type of the other side.
+        let (lhs_ty, rhs_ty) = if lhs.is_untyped_number() {
+            let rhs_ty = rhs.annotate_type_information(type_data, scopes);
+            (lhs.annotate_expecting(&rhs_ty, type_data, scopes), rhs_ty)
+        } else {
+            let lhs_ty = lhs.annotate_type_information(type_data, scopes);
+            (lhs_ty.clone(), rhs.annotate_expecting(&lhs_ty, type_data, scopes))
+        };
+        if lhs_ty == AstType::Invalid || rhs_ty == AstType::Invalid {
+            return None;
+        }
+        if lhs_ty.widens_to(&rhs_ty) {
+            lhs.coerce_to(&rhs_ty, type_data);
+            return Some(rhs_ty);
+        }
+        if rhs_ty.widens_to(&lhs_ty) {
+            rhs.coerce_to(&lhs_ty, type_data);
+            return Some(lhs_ty);
+        }
+        if lhs_ty != rhs_ty {
+            type_data.errors.push((
+                format!("mismatched operand types {:?} and {:?} for `{}`", lhs_ty, rhs_ty, operator),
+                op_tok.span.clone(),
+            ));
+            return None;
+        }
+        Some(lhs_ty)
+    }
+
     pub fn annotate_binop(
         lhs: &mut AstExpression, rhs: &mut AstExpression, ty: &mut OnceCell<AstType>,
-        type_data: &TypeInformation, locals: &HashMap<String, AstType>) -> AstType {
-        lhs.annotate_type_information(type_data, locals);
-        rhs.annotate_type_information(type_data, locals);
-        if(lhs.get_type() == rhs.get_type()) {
-            ty.set(lhs.get_type()).expect("TODO: panic message");
-        } else {
-            ty.set(AstType::Invalid).expect("TODO: panic message");
+        op_tok: &Token, operator: &str,
+        type_data: &mut TypeInformation, scopes: &mut Scopes) -> AstType {
+        let Some(operand_ty) = Self::annotate_operands(lhs, rhs, op_tok, operator, type_data, scopes) else {
+            return settle(ty, AstType::Invalid);
+        };
+        if !operand_ty.is_numeric() {
+            type_data.errors.push((
+                format!("operator `{}` is not supported for {:?}", operator, operand_ty),
+                op_tok.span.clone(),
+            ));
+            return settle(ty, AstType::Invalid);
+        }
+        settle(ty, operand_ty)
+    }
+
+    pub fn annotate_shift(
+        lhs: &mut AstExpression, rhs: &mut AstExpression, ty: &mut OnceCell<AstType>,
+        op_tok: &Token, operator: &str,
+        type_data: &mut TypeInformation, scopes: &mut Scopes) -> AstType {
+        let lhs_ty = lhs.annotate_type_information(type_data, scopes);
+        let rhs_ty = rhs.annotate_expecting(&lhs_ty, type_data, scopes);
+        if lhs_ty == AstType::Invalid || rhs_ty == AstType::Invalid {
+            return settle(ty, AstType::Invalid);
+        }
+        if !lhs_ty.is_integer() || !rhs_ty.is_integer() {
+            type_data.errors.push((
+                format!("operator `{}` is not supported for {:?} and {:?}", operator, lhs_ty, rhs_ty),
+                op_tok.span.clone(),
+            ));
+            return settle(ty, AstType::Invalid);
+        }
+        settle(ty, lhs_ty)
+    }
+
+    pub fn annotate_comparison(
+        lhs: &mut AstExpression, rhs: &mut AstExpression, ty: &mut OnceCell<AstType>,
+        op_tok: &Token, operator: &str,
+        type_data: &mut TypeInformation, scopes: &mut Scopes) -> AstType {
+        let Some(operand_ty) = Self::annotate_operands(lhs, rhs, op_tok, operator, type_data, scopes) else {
+            return settle(ty, AstType::Invalid);
+        };
+        let is_equality = operator == "==" || operator == "!=";
+        if !operand_ty.is_numeric() && !(is_equality && operand_ty == AstType::Bool) {
+            type_data.errors.push((
+                format!("operator `{}` is not supported for {:?}", operator, operand_ty),
+                op_tok.span.clone(),
+            ));
+            return settle(ty, AstType::Invalid);
+        }
+        settle(ty, AstType::Bool)
+    }
+
+    pub fn annotate_invoke(
+        receiver: &mut AstExpression, arguments: &mut Vec<AstExpression>,
+        return_type: &mut OnceCell<AstType>, resolve_as_ufcs: bool,
+        type_data: &mut TypeInformation, scopes: &mut Scopes) -> AstType {
+        // The receiver of a UFCS call has to be typed before the function can be resolved.
+        let receiver_ty = match arguments.first_mut() {
+            Some(first) if resolve_as_ufcs => Some(first.annotate_type_information(type_data, scopes)),
+            _ => None,
+        };
+        let skip = receiver_ty.iter().count();
+
+        // Locals shadow functions, so `f(x)` calls the closure in `f` if there is one.
+        let (name, token) = match receiver {
+            AstExpression::VariableLiteral { content, .. }
+                if !resolve_as_ufcs && scopes.resolve(content).is_some() =>
+            {
+                return Self::annotate_closure_invoke(
+                    receiver, arguments, return_type, skip, type_data, scopes);
+            }
+            AstExpression::VariableLiteral { content, token, .. } => (content.clone(), token.clone()),
+            AstExpression::PathLiteral(path) => (path.name.clone(), path.token.clone()),
+            _ => {
+                return Self::annotate_closure_invoke(
+                    receiver, arguments, return_type, skip, type_data, scopes);
+            }
+        };
+
+        let builtin_method = match &receiver_ty {
+            Some(AstType::ArrayOf(element)) => array_method(&name, element).map(|method| ("array", method)),
+            Some(AstType::Weak(target)) => weak_method(&name, target).map(|method| ("weak", method)),
+            _ => None,
+        };
+        if let Some((kind, (mut parameters, returns))) = builtin_method
+            && let Some(receiver_ty) = receiver_ty
+        {
+            parameters.insert(0, receiver_ty);
+            if parameters.len() != arguments.len() {
+                type_data.errors.push((
+                    format!(
+                        "{} method `{}` expects {} arguments, found {}",
+                        kind,
+                        name,
+                        parameters.len() - 1,
+                        arguments.len() - 1
+                    ),
+                    token.span.clone(),
+                ));
+                for argument in arguments.iter_mut().skip(skip) {
+                    argument.annotate_type_information(type_data, scopes);
+                }
+                return settle(return_type, returns);
+            }
+            Self::annotate_arguments(&parameters, arguments, skip, type_data, scopes);
+            *receiver = AstExpression::PathLiteral(PathData {
+                name: format!("qre::{}::{}", kind, name),
+                token,
+            });
+            return settle(return_type, returns);
+        }
+
+        // `value.function()` prefers a function declared on the type of `value`, or on
+        // the generic struct it is an instance of.
+        let mut resolved_name = name.clone();
+        let mut generic_name = None;
+        if let Some(AstType::Structure(type_name)) = &receiver_ty {
+            if type_data.names.contains_key(&format!("{}::{}", type_name, name)) {
+                resolved_name = format!("{}::{}", type_name, name);
+            } else if let Some((origin, _)) = type_data.instance_origins.get(type_name)
+                && type_data.generics.contains_key(&format!("{}::{}", origin, name))
+            {
+                generic_name = Some(format!("{}::{}", origin, name));
+            }
+        }
+        if generic_name.is_none()
+            && !type_data.names.contains_key(&resolved_name)
+            && type_data.generics.contains_key(&name)
+        {
+            generic_name = Some(name.clone());
+        }
+        if let Some(generic_name) = generic_name {
+            return Self::annotate_generic_invoke(
+                receiver, arguments, return_type, skip, &generic_name, token, type_data, scopes);
+        }
+
+        let (callee, parameters, returns) = match type_data.names.get(&resolved_name).cloned() {
+            Some(ProgramType::Function { arguments: parameters, returns, .. }) => (
+                resolved_name.clone(),
+                parameters.into_iter().map(|(_, ty)| ty).collect::<Vec<_>>(),
+                returns,
+            ),
+            _ => match builtin_function(&resolved_name) {
+                Some((intrinsic, parameters, returns)) => (intrinsic.to_string(), parameters, returns),
+                None => {
+                    type_data.errors.push((
+                        format!("function `{}` is not defined", name),
+                        token.span.clone(),
+                    ));
+                    for argument in arguments.iter_mut().skip(skip) {
+                        argument.annotate_type_information(type_data, scopes);
+                    }
+                    return settle(return_type, AstType::Invalid);
+                }
+            },
+        };
+        let spawns = callee == SPAWN;
+        *receiver = AstExpression::PathLiteral(PathData {
+            name: callee,
+            token,
+        });
+
+        if parameters.len() != arguments.len() {
+            type_data.errors.push((
+                format!(
+                    "function `{}` expects {} arguments, found {}",
+                    resolved_name,
+                    parameters.len(),
+                    arguments.len()
+                ),
+                receiver.get_span(),
+            ));
+            for argument in arguments.iter_mut().skip(skip) {
+                argument.annotate_type_information(type_data, scopes);
+            }
+            return settle(return_type, returns);
+        }
+        Self::annotate_arguments(&parameters, arguments, skip, type_data, scopes);
+        if spawns {
+            Self::check_sendable(&arguments[0], type_data);
+        }
+        settle(return_type, returns)
+    }
+
+    /// Converts the arguments of a call to the types of its parameters. The first
+    /// `skip` arguments have already been annotated.
+    pub fn annotate_arguments(
+        parameters: &[AstType], arguments: &mut [AstExpression], skip: usize,
+        type_data: &mut TypeInformation, scopes: &mut Scopes) {
+        for (index, (parameter_ty, argument)) in parameters.iter().zip(arguments.iter_mut()).enumerate() {
+            if index < skip {
+                if !argument.coerce_to(parameter_ty, type_data) {
+                    type_data.errors.push((
+                        format!("expected {:?}, found {:?}", parameter_ty, argument.get_type()),
+                        argument.get_span(),
+                    ));
+                }
+            } else {
+                argument.annotate_coerced(parameter_ty, type_data, scopes);
+            }
         }
-        ty.get().unwrap().clone()
     }
 
     pub fn annotate_type_information(
         &mut self,
-        type_data: &TypeInformation,
-        locals: &HashMap<String, AstType>,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
     ) -> AstType {
+        self.resolve_enum_literal(type_data);
         match self {
             AstExpression::NumberLiteral { content, ty, .. } => {
                 if content.contains(".") {
-                    if let Ok(v) = f32::from_str(&content) {
-                        ty.set(AstType::Float32).expect("type must not be set");
+                    if let Ok(_) = f32::from_str(&content) {
+                        settle(ty, AstType::Float32)
                     } else {
-                        ty.set(AstType::Float64).expect("type must not be set");
+                        settle(ty, AstType::Float64)
                     }
                 } else {
-                    if let Ok(v) = i32::from_str(&content) {
-                        ty.set(AstType::Int32).expect("type must not be set");
+                    if let Ok(_) = i32::from_str(&content) {
+                        settle(ty, AstType::Int32)
                     } else {
-                        ty.set(AstType::Int64).expect("type must not be set");
+                        settle(ty, AstType::Int64)
                     }
                 }
-                
-                ty.get().cloned().unwrap()
             }
-            AstExpression::StringLiteral { content, ty, .. } => {
-                ty.set(AstType::Structure("std::string".to_string()))
-                    .expect("type must not be set");
-                ty.get().cloned().unwrap()
+            AstExpression::StringLiteral { ty, .. } => {
+                settle(ty, AstType::Structure("std::string".to_string()))
             }
-            AstExpression::VariableLiteral { content, ty, .. } => {
-                ty.set(locals.get(content).expect("variable must exist").clone())
-                    .expect("type must exist");
-                ty.get().unwrap().clone()
-            }
-            AstExpression::PathLiteral(_) => {
-                todo!()
+            AstExpression::BooleanLiteral { ty, .. } => settle(ty, AstType::Bool),
+            AstExpression::NoneLiteral { ty, .. } => settle(ty, AstType::None),
+            AstExpression::VariableLiteral {
+                content,
+                ty,
+                token,
+                local,
+            } => {
+                if scopes.resolve(content).is_none()
+                    && let Some(function_ty) = type_data.function_type(content)
+                {
+                    return settle(ty, function_ty);
+                }
+                let Some(index) = resolve_local(content, token, type_data, scopes) else {
+                    return settle(ty, AstType::Invalid);
+                };
+                let _ = local.set(index);
+                settle(ty, scopes.type_of(index))
             }
-            AstExpression::ArrayLiteral { .. } => {
-                todo!()
+            AstExpression::PathLiteral(path) => {
+                if let Some(function_ty) = type_data.function_type(&path.name) {
+                    *self = AstExpression::VariableLiteral {
+                        content: path.name.clone(),
+                        ty: OnceCell::from(function_ty.clone()),
+                        token: path.token.clone(),
+                        local: OnceCell::new(),
+                    };
+                    return function_ty;
+                }
+                type_data.errors.push((
+                    format!("`{}` can not be used as a value", path.name),
+                    path.token.span.clone(),
+                ));
+                AstType::Invalid
             }
-            AstExpression::StructureLiteral { .. } => {
-                todo!()
+            AstExpression::ArrayLiteral { content, ty, open_bracket_tok, .. } =>
+                Self::annotate_array_literal(content, ty, open_bracket_tok, None, type_data, scopes),
+            AstExpression::StructureLiteral { ty, fields, token } =>
+                Self::annotate_structure_literal(ty, fields, token, None, type_data, scopes),
+            AstExpression::TypeLiteral { token, .. } => {
+                type_data.errors.push((
+                    "types can not be used as values".to_string(),
+                    token.span.clone(),
+                ));
+                AstType::Invalid
             }
-            AstExpression::TypeLiteral { .. } => {
-                todo!()
+            AstExpression::Lambda {
+                parameters,
+                returns,
+                code_block,
+                locals,
+                captures,
+                ty,
+                token,
+            } => Self::annotate_lambda(
+                parameters, returns, code_block, locals, captures, ty, token, type_data, scopes),
+            AstExpression::EnumLiteral { ty, variant, arguments, token } =>
+                Self::annotate_enum_literal(ty, variant, arguments, token, type_data, scopes),
+            AstExpression::Add { lhs, rhs, ty, op_tok } =>
+                Self::annotate_binop(lhs, rhs, ty, op_tok, "+", type_data, scopes),
+            AstExpression::Sub { lhs, rhs, ty, op_tok } =>
+                Self::annotate_binop(lhs, rhs, ty, op_tok, "-", type_data, scopes),
+            AstExpression::Mul { lhs, rhs, ty, op_tok } =>
+                Self::annotate_binop(lhs, rhs, ty, op_tok, "*", type_data, scopes),
+            AstExpression::Div { lhs, rhs, ty, op_tok } =>
+                Self::annotate_binop(lhs, rhs, ty, op_tok, "/", type_data, scopes),
+            AstExpression::Mod { lhs, rhs, ty, op_tok } =>
+                Self::annotate_binop(lhs, rhs, ty, op_tok, "%", type_data, scopes),
+            AstExpression::ShiftLeft { lhs, rhs, ty, op_tok } =>
+                Self::annotate_shift(lhs, rhs, ty, op_tok, "<<", type_data, scopes),
+            AstExpression::ShiftRight { lhs, rhs, ty, op_tok } =>
+                Self::annotate_shift(lhs, rhs, ty, op_tok, ">>", type_data, scopes),
+            AstExpression::Equal { lhs, rhs, ty, op_tok } =>
+                Self::annotate_comparison(lhs, rhs, ty, op_tok, "==", type_data, scopes),
+            AstExpression::NotEqual { lhs, rhs, ty, op_tok } =>
+                Self::annotate_comparison(lhs, rhs, ty, op_tok, "!=", type_data, scopes),
+            AstExpression::LessThan { lhs, rhs, ty, op_tok } =>
+                Self::annotate_comparison(lhs, rhs, ty, op_tok, "<", type_data, scopes),
+            AstExpression::GreaterThan { lhs, rhs, ty, op_tok } =>
+                Self::annotate_comparison(lhs, rhs, ty, op_tok, ">", type_data, scopes),
+            AstExpression::LessThanOrEqual { lhs, rhs, ty, op_tok } =>
+                Self::annotate_comparison(lhs, rhs, ty, op_tok, "<=", type_data, scopes),
+            AstExpression::GreaterThanOrEqual { lhs, rhs, ty, op_tok } =>
+                Self::annotate_comparison(lhs, rhs, ty, op_tok, ">=", type_data, scopes),
+            AstExpression::Cast { value, target, ty, token } => {
+                *target = type_data.resolve_type(target, &token.span);
+                Self::annotate_cast(value, target, ty, type_data, scopes)
             }
-            AstExpression::Add { lhs, rhs, ty, .. } => 
-                Self::annotate_binop(lhs, rhs, ty, type_data, locals),
-            AstExpression::Sub { lhs, rhs, ty, .. } => 
-                Self::annotate_binop(lhs, rhs, ty, type_data, locals),
-            AstExpression::Mul { lhs, rhs, ty, .. } => 
-                Self::annotate_binop(lhs, rhs, ty, type_data, locals),
-            AstExpression::Div { lhs, rhs, ty, .. } => 
-                Self::annotate_binop(lhs, rhs, ty, type_data, locals),
-            AstExpression::Mod { lhs, rhs, ty, .. } => 
-                Self::annotate_binop(lhs, rhs, ty, type_data, locals),
-            AstExpression::Invoke { .. } => {
-                todo!()
+            AstExpression::Is { value, target, ty, token } => {
+                *target = type_data.resolve_type(target, &token.span);
+                let value_ty = value.annotate_type_information(type_data, scopes);
+                if value_ty == AstType::Invalid {
+                    return settle(ty, AstType::Bool);
+                }
+                if !matches!(value_ty, AstType::UnionOf(..)) {
+                    type_data.errors.push((
+                        format!("`is` can only test values of a union type, found {:?}", value_ty),
+                        token.span.clone(),
+                    ));
+                } else if !value_ty.union_members().contains(target) {
+                    type_data.errors.push((
+                        format!("a value of type {:?} can never be {:?}", value_ty, target),
+                        token.span.clone(),
+                    ));
+                }
+                settle(ty, AstType::Bool)
             }
-            AstExpression::Index { .. } => {
-                todo!()
+            AstExpression::FieldAccess { value, field, ty, token } =>
+                Self::annotate_field_access(value, field, ty, token, type_data, scopes),
+            AstExpression::Propagate { value, ty, token } =>
+                Self::annotate_propagate(value, ty, token, type_data, scopes),
+            AstExpression::Match { value, arms, ty, token } =>
+                Self::annotate_match(value, arms, ty, token, None, type_data, scopes),
+            AstExpression::Invoke {
+                receiver,
+                arguments,
+                return_type,
+                resolve_as_ufcs,
+                ..
+            } => Self::annotate_invoke(
+                receiver,
+                arguments,
+                return_type,
+                *resolve_as_ufcs,
+                type_data,
+                scopes,
+            ),
+            AstExpression::Index { ty, base, other, token } => {
+                let element = Self::annotate_index(base, other, token, type_data, scopes);
+                settle(ty, element)
             }
         }
     }
 }
+
+impl AstType {
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.integer_bits().is_some()
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, AstType::Float32 | AstType::Float64)
+    }
+
+    /// Signed integers use signed division, remainder and comparison, shift right
+    /// arithmetically, and overflow past their signed range. Unsigned integers use
+    /// the unsigned forms of all of these.
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            AstType::Int8 | AstType::Int16 | AstType::Int32 | AstType::Int64
+        )
+    }
+
+    /// The width of an integer type in bits. `usize` is pointer sized, which is 64 bits on
+    /// every target QRE supports.
+    pub fn integer_bits(&self) -> Option<u32> {
+        match self {
+            AstType::Int8 | AstType::UInt8 => Some(8),
+            AstType::Int16 | AstType::UInt16 => Some(16),
+            AstType::Int32 | AstType::UInt32 => Some(32),
+            AstType::Int64 | AstType::UInt64 | AstType::USize => Some(64),
+            _ => None,
+        }
+    }
+}
+
+/// Looks up a visible local by name, reporting an error if there is none.
+fn resolve_local(
+    name: &str,
+    token: &Token,
+    type_data: &mut TypeInformation,
+    scopes: &mut Scopes,
+) -> Option<usize> {
+    if let Some(index) = scopes.resolve(name) {
+        return Some(index);
+    }
+    match scopes.resolve_dropped(name) {
+        Some(dropped) => type_data.errors.push((
+            format!(
+                "variable `{}` is not in scope here, it was declared at {:?}",
+                name, dropped.span
+            ),
+            token.span.clone(),
+        )),
+        None => type_data.errors.push((
+            format!("variable `{}` is not defined", name),
+            token.span.clone(),
+        )),
+    }
+    None
+}
+
+/// Sets the type of an expression if it was not already annotated and returns it.
+pub(crate) fn settle(ty: &OnceCell<AstType>, value: AstType) -> AstType {
+    ty.get_or_init(|| value).clone()
+}
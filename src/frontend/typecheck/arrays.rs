@@ -0,0 +1,87 @@
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstExpression, AstType};
+use crate::frontend::typecheck::annotate::settle;
+use crate::frontend::typecheck::data::TypeInformation;
+use crate::frontend::typecheck::scope::Scopes;
+use std::cell::OnceCell;
+
+impl AstExpression {
+    /// Annotates `[a, b, ..]`. Without an expected element type, the elements take
+    /// the type of the first one.
+    pub fn annotate_array_literal(
+        content: &mut [AstExpression],
+        ty: &mut OnceCell<AstType>,
+        token: &Token,
+        element: Option<&AstType>,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        let element = match element {
+            Some(element) => element.clone(),
+            None => {
+                let Some(first) = content.first_mut() else {
+                    type_data.errors.push((
+                        "can not infer the element type of an empty array".to_string(),
+                        token.span.clone(),
+                    ));
+                    return settle(ty, AstType::Invalid);
+                };
+                first.annotate_type_information(type_data, scopes)
+            }
+        };
+        if element == AstType::Invalid {
+            return settle(ty, AstType::Invalid);
+        }
+        for value in content.iter_mut() {
+            value.annotate_coerced(&element, type_data, scopes);
+        }
+        settle(ty, AstType::ArrayOf(Box::new(element)))
+    }
+
+    /// Annotates `base[index]`, returning the element type of the array.
+    pub fn annotate_index(
+        base: &mut AstExpression,
+        index: &mut AstExpression,
+        token: &Token,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        let base_ty = base.annotate_type_information(type_data, scopes);
+        let index_ty = index.annotate_expecting(&AstType::USize, type_data, scopes);
+        if index_ty != AstType::Invalid && !index_ty.is_integer() {
+            type_data.errors.push((
+                format!("arrays are indexed by integers, found {:?}", index_ty),
+                index.get_span(),
+            ));
+        }
+        match base_ty {
+            AstType::ArrayOf(element) => *element,
+            AstType::Invalid => AstType::Invalid,
+            other => {
+                type_data.errors.push((
+                    format!("only arrays can be indexed, found {:?}", other),
+                    token.span.clone(),
+                ));
+                AstType::Invalid
+            }
+        }
+    }
+}
+
+/// The parameters after `self` and the return type of a builtin method of arrays
+/// holding `element`.
+///
+/// Calls to these are lowered to the intrinsic `qre::array::{name}`.
+pub fn array_method(name: &str, element: &AstType) -> Option<(Vec<AstType>, AstType)> {
+    let element = element.clone();
+    match name {
+        "len" | "capacity" => Some((vec![], AstType::USize)),
+        "push" => Some((vec![element], AstType::Void)),
+        "pop" => Some((vec![], AstType::union(vec![element, AstType::None]))),
+        "insert" => Some((vec![AstType::USize, element], AstType::Void)),
+        "remove" => Some((vec![AstType::USize], element)),
+        "reserve" => Some((vec![AstType::USize], AstType::Void)),
+        "clear" => Some((vec![], AstType::Void)),
+        _ => None,
+    }
+}
@@ -0,0 +1,104 @@
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstCapture, AstCodeBlock, AstExpression, AstLocal, AstType, PathData};
+use crate::frontend::typecheck::annotate::settle;
+use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
+use crate::frontend::typecheck::scope::Scopes;
+use std::cell::OnceCell;
+
+impl AstExpression {
+    pub fn annotate_lambda(
+        parameters: &mut [(AstType, PathData)],
+        returns: &mut AstType,
+        code_block: &mut AstCodeBlock,
+        locals: &mut Vec<AstLocal>,
+        captures: &mut Vec<AstCapture>,
+        ty: &mut OnceCell<AstType>,
+        token: &Token,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        for (parameter_ty, _) in parameters.iter_mut() {
+            *parameter_ty = type_data.resolve_type(parameter_ty, &token.span);
+        }
+        *returns = type_data.resolve_type(returns, &token.span);
+
+        scopes.enter_closure(returns.clone());
+        scopes.declare_parameters(parameters, &mut type_data.errors);
+        code_block.annotate_type_information(type_data, scopes);
+        let closure = scopes.exit_closure();
+        *locals = closure.locals;
+        *captures = closure.captures;
+
+        // Control flow checks don't descend into expressions, so the body is checked here.
+        let always_returns = code_block.check_returns(returns, true, type_data);
+        if !always_returns && *returns != AstType::Void {
+            type_data.errors.push((
+                format!(
+                    "closure does not return a value of type {:?} on every path",
+                    returns
+                ),
+                token.span.clone(),
+            ));
+        }
+
+        let parameter_types = parameters.iter().map(|(ty, _)| ty.clone()).collect();
+        settle(ty, AstType::Function(parameter_types, Box::new(returns.clone())))
+    }
+
+    /// Annotates a call of a closure value, anything invoked that isn't a named function.
+    pub fn annotate_closure_invoke(
+        receiver: &mut AstExpression,
+        arguments: &mut [AstExpression],
+        return_type: &mut OnceCell<AstType>,
+        skip: usize,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        let callee_ty = receiver.annotate_type_information(type_data, scopes);
+        let AstType::Function(parameters, returns) = callee_ty else {
+            if callee_ty != AstType::Invalid {
+                type_data.errors.push((
+                    format!("values of type {:?} can not be called", callee_ty),
+                    receiver.get_span(),
+                ));
+            }
+            for argument in arguments.iter_mut().skip(skip) {
+                argument.annotate_type_information(type_data, scopes);
+            }
+            return settle(return_type, AstType::Invalid);
+        };
+
+        if parameters.len() != arguments.len() {
+            type_data.errors.push((
+                format!(
+                    "closure expects {} arguments, found {}",
+                    parameters.len(),
+                    arguments.len()
+                ),
+                receiver.get_span(),
+            ));
+            for argument in arguments.iter_mut().skip(skip) {
+                argument.annotate_type_information(type_data, scopes);
+            }
+            return settle(return_type, *returns);
+        }
+        Self::annotate_arguments(&parameters, arguments, skip, type_data, scopes);
+        settle(return_type, *returns)
+    }
+}
+
+impl TypeInformation {
+    /// The type of a named function used as a value.
+    pub fn function_type(&self, name: &str) -> Option<AstType> {
+        let Some(ProgramType::Function {
+            arguments, returns, ..
+        }) = self.names.get(name)
+        else {
+            return None;
+        };
+        Some(AstType::Function(
+            arguments.iter().map(|(_, ty)| ty.clone()).collect(),
+            Box::new(returns.clone()),
+        ))
+    }
+}
@@ -0,0 +1,258 @@
+use crate::frontend::parser::ast::{AstExpression, AstType};
+use crate::frontend::typecheck::annotate::settle;
+use crate::frontend::typecheck::data::TypeInformation;
+use crate::frontend::typecheck::scope::Scopes;
+use std::cell::OnceCell;
+use std::str::FromStr;
+
+impl AstExpression {
+    /// Annotates the expression, letting untyped number literals take on `expected`
+    /// when their value fits in it, and array literals take its element type.
+    ///
+    /// Arithmetic on number literals, the arms of a match and the fields of a generic
+    /// structure literal get their types from `expected` too.
+    pub fn annotate_expecting(
+        &mut self,
+        expected: &AstType,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        if self.is_untyped_number() {
+            self.settle_literals(expected);
+        }
+        if let AstExpression::Match {
+            value,
+            arms,
+            ty,
+            token,
+        } = self
+        {
+            return Self::annotate_match(value, arms, ty, token, Some(expected), type_data, scopes);
+        }
+        if let AstExpression::StructureLiteral { ty, fields, token } = self {
+            return Self::annotate_structure_literal(ty, fields, token, Some(expected), type_data, scopes);
+        }
+        if let AstExpression::ArrayLiteral {
+            content,
+            ty,
+            open_bracket_tok,
+            ..
+        } = self
+            && let Some(AstType::ArrayOf(element)) = expected
+                .union_members()
+                .into_iter()
+                .find(|member| matches!(member, AstType::ArrayOf(_)))
+        {
+            return Self::annotate_array_literal(
+                content,
+                ty,
+                open_bracket_tok,
+                Some(&element),
+                type_data,
+                scopes,
+            );
+        }
+        self.annotate_type_information(type_data, scopes)
+    }
+
+    /// Whether the expression is arithmetic on number literals that don't have a type
+    /// yet, so it can take the type of its context.
+    pub fn is_untyped_number(&self) -> bool {
+        match self {
+            AstExpression::NumberLiteral { ty, .. } => ty.get().is_none(),
+            AstExpression::Add { lhs, rhs, .. }
+            | AstExpression::Sub { lhs, rhs, .. }
+            | AstExpression::Mul { lhs, rhs, .. }
+            | AstExpression::Div { lhs, rhs, .. }
+            | AstExpression::Mod { lhs, rhs, .. } => {
+                lhs.is_untyped_number() && rhs.is_untyped_number()
+            }
+            // The shift amount takes the type of the value being shifted.
+            AstExpression::ShiftLeft { lhs, .. } | AstExpression::ShiftRight { lhs, .. } => {
+                lhs.is_untyped_number()
+            }
+            _ => false,
+        }
+    }
+
+    /// Gives the literals of an untyped number expression the first member of
+    /// `expected` their value fits in.
+    fn settle_literals(&mut self, expected: &AstType) {
+        match self {
+            AstExpression::NumberLiteral { content, ty, .. } => {
+                if let Some(literal_ty) = expected
+                    .union_members()
+                    .into_iter()
+                    .find(|member| literal_fits(content, member))
+                {
+                    settle(ty, literal_ty);
+                }
+            }
+            AstExpression::Add { lhs, rhs, .. }
+            | AstExpression::Sub { lhs, rhs, .. }
+            | AstExpression::Mul { lhs, rhs, .. }
+            | AstExpression::Div { lhs, rhs, .. }
+            | AstExpression::Mod { lhs, rhs, .. } => {
+                lhs.settle_literals(expected);
+                rhs.settle_literals(expected);
+            }
+            AstExpression::ShiftLeft { lhs, .. } | AstExpression::ShiftRight { lhs, .. } => {
+                lhs.settle_literals(expected)
+            }
+            _ => {}
+        }
+    }
+
+    /// Annotates the expression and converts it to `expected`, reporting an error
+    /// if no implicit conversion exists.
+    pub fn annotate_coerced(
+        &mut self,
+        expected: &AstType,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        self.annotate_expecting(expected, type_data, scopes);
+        if !self.coerce_to(expected, type_data) {
+            type_data.errors.push((
+                format!("expected {:?}, found {:?}", expected, self.get_type()),
+                self.get_span(),
+            ));
+        }
+        self.get_type()
+    }
+
+    /// Converts an annotated expression to `expected`, wrapping it in an implicit
+    /// widening, union, interface or weak reference conversion when needed. Returns
+    /// false if the types are incompatible.
+    pub fn coerce_to(&mut self, expected: &AstType, type_data: &TypeInformation) -> bool {
+        let actual = self.get_type();
+        if actual == *expected || actual == AstType::Invalid || *expected == AstType::Invalid {
+            return true;
+        }
+        if !actual.widens_to(expected)
+            && !actual.is_subset_of(expected)
+            && !actual.implements(expected, type_data)
+            && !(actual.weakens_to(expected) && !type_data.is_shareable(&actual))
+        {
+            return false;
+        }
+        let token = self.get_token();
+        let value = std::mem::replace(
+            self,
+            AstExpression::BooleanLiteral {
+                value: false,
+                ty: OnceCell::new(),
+                token: token.clone(),
+            },
+        );
+        *self = AstExpression::Cast {
+            value: Box::new(value),
+            target: expected.clone(),
+            ty: OnceCell::from(expected.clone()),
+            token,
+        };
+        true
+    }
+
+    pub fn annotate_cast(
+        value: &mut AstExpression,
+        target: &AstType,
+        ty: &mut OnceCell<AstType>,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        let value_ty = value.annotate_expecting(target, type_data, scopes);
+        if value_ty == AstType::Invalid {
+            return settle(ty, AstType::Invalid);
+        }
+        // Weak references are only tracked by the thread that made them.
+        if value_ty.weakens_to(target) && type_data.is_shareable(&value_ty) {
+            type_data.errors.push((
+                format!("weak references can not point to {:?}, which is shared", value_ty),
+                value.get_span(),
+            ));
+            return settle(ty, AstType::Invalid);
+        }
+        // Casting an interface back to a struct that implements it checks the type ID at runtime.
+        if !value_ty.can_cast_to(target)
+            && !value_ty.implements(target, type_data)
+            && !target.implements(&value_ty, type_data)
+        {
+            type_data.errors.push((
+                format!("can not cast {:?} to {:?}", value_ty, target),
+                value.get_span(),
+            ));
+            return settle(ty, AstType::Invalid);
+        }
+        settle(ty, target.clone())
+    }
+}
+
+impl AstType {
+    /// Whether a value of this type converts to `other` implicitly without losing information.
+    pub fn widens_to(&self, other: &AstType) -> bool {
+        if *self == AstType::Float32 && *other == AstType::Float64 {
+            return true;
+        }
+        let (Some(from_bits), Some(to_bits)) = (self.integer_bits(), other.integer_bits()) else {
+            return false;
+        };
+        match (self, other) {
+            // usize is kept apart from the fixed width types so code doesn't come to
+            // depend on the pointer size.
+            (AstType::USize, _) => false,
+            (_, AstType::USize) => !self.is_signed() && from_bits < to_bits,
+            _ if self.is_signed() == other.is_signed() => from_bits < to_bits,
+            // Every unsigned value fits in a strictly wider signed type.
+            _ => !self.is_signed() && from_bits < to_bits,
+        }
+    }
+
+    /// Whether `value as other` is allowed.
+    ///
+    /// Casts that can lose information, like `i64 as i32` or `f64 as i32`, are checked at
+    /// runtime and panic if the value does not fit in the target type.
+    ///
+    /// A union can be cast to any of its members, which panics if it holds a different one.
+    pub fn can_cast_to(&self, other: &AstType) -> bool {
+        self == other
+            || (self.is_numeric() && other.is_numeric())
+            || self.is_subset_of(other)
+            || other.is_subset_of(self)
+            || self.weakens_to(other)
+    }
+
+    /// Whether `other` is a weak reference to objects of this type.
+    pub fn weakens_to(&self, other: &AstType) -> bool {
+        matches!(other, AstType::Weak(target) if **target == *self)
+    }
+
+    /// Whether every member of this type is also a member of the union `other`.
+    pub fn is_subset_of(&self, other: &AstType) -> bool {
+        if !matches!(other, AstType::UnionOf(..)) {
+            return false;
+        }
+        let members = other.union_members();
+        self.union_members()
+            .iter()
+            .all(|member| members.contains(member))
+    }
+}
+
+fn literal_fits(content: &str, expected: &AstType) -> bool {
+    if content.contains('.') {
+        return matches!(expected, AstType::Float32 | AstType::Float64);
+    }
+    match expected {
+        AstType::Int8 => i8::from_str(content).is_ok(),
+        AstType::Int16 => i16::from_str(content).is_ok(),
+        AstType::Int32 => i32::from_str(content).is_ok(),
+        AstType::Int64 => i64::from_str(content).is_ok(),
+        AstType::UInt8 => u8::from_str(content).is_ok(),
+        AstType::UInt16 => u16::from_str(content).is_ok(),
+        AstType::UInt32 => u32::from_str(content).is_ok(),
+        AstType::UInt64 | AstType::USize => u64::from_str(content).is_ok(),
+        AstType::Float32 | AstType::Float64 => true,
+        _ => false,
+    }
+}
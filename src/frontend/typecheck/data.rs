@@ -1,4 +1,5 @@
-use crate::frontend::parser::ast::{AstType, PathData};
+use crate::frontend::parser::ast::{AstHeader, AstType};
+use crate::frontend::span::Span;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
@@ -6,6 +7,20 @@ pub enum ProgramType {
     Structure {
         name: String,
         fields: Vec<(String, AstType)>,
+        implements: Vec<String>,
+        /// Declared `shared struct`.
+        shared: bool,
+    },
+    /// Values of an enum hold which variant they are and the values that variant carries.
+    Enum {
+        name: String,
+        variants: Vec<(String, Vec<AstType>)>,
+    },
+    /// Each method is also declared as a function `Interface::method`, which
+    /// dispatches to the implementation of the type of its `self` argument.
+    Interface {
+        name: String,
+        methods: Vec<String>,
     },
     Function {
         name: String,
@@ -17,4 +32,38 @@ pub enum ProgramType {
 #[derive(Debug, Clone)]
 pub struct TypeInformation {
     pub names: HashMap<String, ProgramType>,
+    pub errors: Vec<(String, Span)>,
+    /// Types that have been given a runtime type ID, indexed by ID.
+    ///
+    /// ID 0 is never handed out, so zeroed memory never looks like a live object.
+    pub type_ids: Vec<AstType>,
+    /// Generic structs and functions by name. They are only typechecked through
+    /// the copies made for each set of type arguments they are used with.
+    pub generics: HashMap<String, AstHeader>,
+    /// Copies of generic headers that still have to be typechecked.
+    pub instantiations: Vec<Instantiation>,
+    /// The generic and type arguments each instance was made from, by the instance's name.
+    pub instance_origins: HashMap<String, (String, Vec<AstType>)>,
+}
+
+/// A copy of a generic header with its type parameters replaced by type arguments.
+#[derive(Debug, Clone)]
+pub struct Instantiation {
+    pub header: AstHeader,
+    /// Where the type arguments were first used, errors in the copy are reported here.
+    pub span: Span,
+}
+
+impl TypeInformation {
+    /// The ID stored in the type ID slot of heap objects holding a value of `ty`.
+    pub fn type_id(&mut self, ty: &AstType) -> u32 {
+        let index = match self.type_ids.iter().position(|known| known == ty) {
+            Some(index) => index,
+            None => {
+                self.type_ids.push(ty.clone());
+                self.type_ids.len() - 1
+            }
+        };
+        index as u32 + 1
+    }
 }
@@ -0,0 +1,43 @@
+use crate::frontend::parser::ast::{AstHeader, AstType};
+use crate::frontend::typecheck::data::TypeInformation;
+
+impl AstHeader {
+    /// Checks that `main` can be called by the runtime: it takes nothing or the
+    /// command-line arguments as `[string]`, and returns nothing or an exit code.
+    pub fn check_entry_point(&self, info: &mut TypeInformation) {
+        let AstHeader::Function {
+            name,
+            generics,
+            parameters,
+            returns,
+            ..
+        } = self
+        else {
+            return;
+        };
+        if name.name != "main" {
+            return;
+        }
+        if !generics.is_empty() {
+            info.errors.push((
+                "`main` can not be generic".to_string(),
+                name.token.span.clone(),
+            ));
+        }
+        let arguments = AstType::ArrayOf(Box::new(AstType::Structure("std::string".to_string())));
+        match parameters.as_slice() {
+            [] => {}
+            [(ty, _)] if *ty == arguments => {}
+            _ => info.errors.push((
+                "`main` takes no parameters or the arguments of the program as `[string]`".to_string(),
+                name.token.span.clone(),
+            )),
+        }
+        if *returns != AstType::Void && !returns.is_integer() {
+            info.errors.push((
+                format!("`main` returns void or an integer exit code, found {:?}", returns),
+                name.token.span.clone(),
+            ));
+        }
+    }
+}
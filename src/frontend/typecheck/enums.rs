@@ -0,0 +1,95 @@
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstExpression, AstType};
+use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
+use crate::frontend::typecheck::scope::Scopes;
+
+impl AstExpression {
+    /// Replaces paths and calls that name an enum variant with an `EnumLiteral`.
+    pub fn resolve_enum_literal(&mut self, type_data: &mut TypeInformation) {
+        let literal = match self {
+            AstExpression::PathLiteral(path) => find_variant(&path.name, type_data).map(|_| {
+                AstExpression::EnumLiteral {
+                    ty: enum_type(&path.name),
+                    variant: path.name.clone(),
+                    arguments: vec![],
+                    token: path.token.clone(),
+                }
+            }),
+            AstExpression::Invoke {
+                receiver,
+                arguments,
+                ..
+            } => match receiver.as_ref() {
+                AstExpression::PathLiteral(path) => find_variant(&path.name, type_data).map(|fields| {
+                    if fields.is_empty() && arguments.is_empty() {
+                        type_data.errors.push((
+                            format!(
+                                "variant `{}` carries no values and is written without parentheses",
+                                path.name
+                            ),
+                            path.token.span.clone(),
+                        ));
+                    }
+                    AstExpression::EnumLiteral {
+                        ty: enum_type(&path.name),
+                        variant: path.name.clone(),
+                        arguments: std::mem::take(arguments),
+                        token: path.token.clone(),
+                    }
+                }),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(literal) = literal {
+            *self = literal;
+        }
+    }
+
+    pub fn annotate_enum_literal(
+        ty: &AstType,
+        variant: &str,
+        arguments: &mut Vec<AstExpression>,
+        token: &Token,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        let fields = find_variant(variant, type_data).unwrap_or_default();
+        if fields.len() != arguments.len() {
+            type_data.errors.push((
+                format!(
+                    "variant `{}` carries {} values, found {}",
+                    variant,
+                    fields.len(),
+                    arguments.len()
+                ),
+                token.span.clone(),
+            ));
+            for argument in arguments.iter_mut() {
+                argument.annotate_type_information(type_data, scopes);
+            }
+            return ty.clone();
+        }
+        for (field_ty, argument) in fields.iter().zip(arguments.iter_mut()) {
+            argument.annotate_coerced(field_ty, type_data, scopes);
+        }
+        ty.clone()
+    }
+}
+
+/// Looks up the types carried by the variant a path like `Enum::Variant` names.
+pub fn find_variant(path: &str, type_data: &TypeInformation) -> Option<Vec<AstType>> {
+    let (enum_name, variant) = path.rsplit_once("::")?;
+    let Some(ProgramType::Enum { variants, .. }) = type_data.names.get(enum_name) else {
+        return None;
+    };
+    variants
+        .iter()
+        .find(|(name, _)| name == variant)
+        .map(|(_, fields)| fields.clone())
+}
+
+fn enum_type(path: &str) -> AstType {
+    let (enum_name, _) = path.rsplit_once("::").expect("variant paths contain `::`");
+    AstType::Structure(enum_name.to_string())
+}
@@ -0,0 +1,329 @@
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstExpression, AstMatchArm, AstPattern, AstType};
+use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
+use crate::frontend::typecheck::patterns::integer_value;
+use std::collections::HashMap;
+
+/// A pattern reduced to what matters for exhaustiveness checking.
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+    /// A string or float literal, whose type has too many values to enumerate.
+    Opaque(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    /// Selects one member of a union, the only field is the member's value.
+    Member(AstType),
+    Bool(bool),
+    /// An inclusive range of integer values.
+    Range(i128, i128),
+    Structure(String),
+    /// One variant of an enum, named by its enum and variant name.
+    Variant(String, String),
+}
+
+impl AstExpression {
+    /// Reports arms that can never be reached and values the arms don't cover.
+    ///
+    /// This is the usefulness check from Maranget's "Warnings for pattern matching":
+    /// an arm is unreachable if it is not useful after the arms before it, and the
+    /// match is exhaustive if a wildcard is not useful after all of its arms.
+    pub fn check_exhaustiveness(
+        value_ty: &AstType,
+        arms: &[AstMatchArm],
+        token: &Token,
+        type_data: &mut TypeInformation,
+    ) {
+        let checker = Checker { names: &type_data.names };
+        let mut rows: Vec<Vec<Pat>> = Vec::new();
+        for arm in arms {
+            let row = vec![checker.lower(&arm.pattern, value_ty)];
+            if !checker.useful(&rows, &row, std::slice::from_ref(value_ty)) {
+                type_data.errors.push((
+                    "unreachable match arm".to_string(),
+                    arm.pattern.get_token().span,
+                ));
+            }
+            rows.push(row);
+        }
+
+        if !checker.useful(&rows, &[Pat::Wild], std::slice::from_ref(value_ty)) {
+            return;
+        }
+        let message = if matches!(value_ty, AstType::UnionOf(..)) {
+            let missing = value_ty
+                .union_members()
+                .into_iter()
+                .filter(|member| {
+                    let probe = Pat::Ctor(Ctor::Member(member.clone()), vec![Pat::Wild]);
+                    checker.useful(&rows, &[probe], std::slice::from_ref(value_ty))
+                })
+                .map(|member| format!("{:?}", member))
+                .collect::<Vec<_>>();
+            format!("match is not exhaustive, {} not covered", missing.join(", "))
+        } else if let AstType::Structure(name) = value_ty
+            && let variants = checker.variants_of(name)
+            && !variants.is_empty()
+        {
+            let missing = variants
+                .into_iter()
+                .filter(|(variant, fields)| {
+                    let ctor = Ctor::Variant(name.clone(), variant.clone());
+                    let probe = Pat::Ctor(ctor, vec![Pat::Wild; fields.len()]);
+                    checker.useful(&rows, &[probe], std::slice::from_ref(value_ty))
+                })
+                .map(|(variant, _)| format!("{}::{}", name, variant))
+                .collect::<Vec<_>>();
+            format!("match is not exhaustive, {} not covered", missing.join(", "))
+        } else {
+            format!("match is not exhaustive for {:?}", value_ty)
+        };
+        type_data.errors.push((message, token.span.clone()));
+    }
+}
+
+struct Checker<'a> {
+    names: &'a HashMap<String, ProgramType>,
+}
+
+impl Checker<'_> {
+    fn lower(&self, pattern: &AstPattern, ty: &AstType) -> Pat {
+        let is_union = matches!(ty, AstType::UnionOf(..));
+        let (pattern_ty, pat) = match pattern {
+            AstPattern::Wildcard { .. } | AstPattern::Binding { .. } => return Pat::Wild,
+            AstPattern::TypeTest { ty: tested, .. } => (tested.clone(), Pat::Wild),
+            AstPattern::Literal(expr) => {
+                let literal_ty = expr.get_type();
+                let pat = match expr {
+                    AstExpression::BooleanLiteral { value, .. } => {
+                        Pat::Ctor(Ctor::Bool(*value), vec![])
+                    }
+                    AstExpression::NumberLiteral { content, .. } => match integer_value(expr) {
+                        Some(value) if literal_ty.is_integer() => {
+                            Pat::Ctor(Ctor::Range(value, value), vec![])
+                        }
+                        _ => Pat::Opaque(content.clone()),
+                    },
+                    AstExpression::StringLiteral { content, .. } => Pat::Opaque(content.clone()),
+                    _ => Pat::Wild,
+                };
+                (literal_ty, pat)
+            }
+            AstPattern::Range {
+                start,
+                end,
+                inclusive,
+                ..
+            } => {
+                let (Some(low), Some(high)) = (integer_value(start), integer_value(end)) else {
+                    return Pat::Wild;
+                };
+                let high = if *inclusive { high } else { high - 1 };
+                (start.get_type(), Pat::Ctor(Ctor::Range(low, high), vec![]))
+            }
+            AstPattern::Structure { name, fields, .. } => {
+                let struct_ty = AstType::Structure(name.name.clone());
+                let subpatterns = self
+                    .fields_of(&name.name)
+                    .iter()
+                    .map(|(field, field_ty)| {
+                        fields
+                            .iter()
+                            .find(|(matched, _)| matched == field)
+                            .map(|(_, pattern)| self.lower(pattern, field_ty))
+                            .unwrap_or(Pat::Wild)
+                    })
+                    .collect();
+                (struct_ty, Pat::Ctor(Ctor::Structure(name.name.clone()), subpatterns))
+            }
+            AstPattern::Variant { name, fields, .. } => {
+                let Some((enum_name, variant)) = name.name.rsplit_once("::") else {
+                    return Pat::Wild;
+                };
+                let ctor = Ctor::Variant(enum_name.to_string(), variant.to_string());
+                // A pattern with the wrong number of fields has already been reported,
+                // the fields it leaves out match anything.
+                let subpatterns = self
+                    .field_types(&ctor)
+                    .iter()
+                    .enumerate()
+                    .map(|(index, field_ty)| match fields.get(index) {
+                        Some(pattern) => self.lower(pattern, field_ty),
+                        None => Pat::Wild,
+                    })
+                    .collect();
+                (AstType::Structure(enum_name.to_string()), Pat::Ctor(ctor, subpatterns))
+            }
+        };
+        if is_union {
+            Pat::Ctor(Ctor::Member(pattern_ty), vec![pat])
+        } else {
+            pat
+        }
+    }
+
+    fn fields_of(&self, name: &str) -> Vec<(String, AstType)> {
+        match self.names.get(name) {
+            Some(ProgramType::Structure { fields, .. }) => fields.clone(),
+            _ => vec![],
+        }
+    }
+
+    fn variants_of(&self, name: &str) -> Vec<(String, Vec<AstType>)> {
+        match self.names.get(name) {
+            Some(ProgramType::Enum { variants, .. }) => variants.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// The types of the values a constructor holds.
+    fn field_types(&self, ctor: &Ctor) -> Vec<AstType> {
+        match ctor {
+            Ctor::Member(member) => vec![member.clone()],
+            Ctor::Bool(_) | Ctor::Range(..) => vec![],
+            Ctor::Structure(name) => self
+                .fields_of(name)
+                .into_iter()
+                .map(|(_, ty)| ty)
+                .collect(),
+            Ctor::Variant(enum_name, variant) => self
+                .variants_of(enum_name)
+                .into_iter()
+                .find(|(name, _)| name == variant)
+                .map(|(_, fields)| fields)
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Whether some value matched by `row` is not matched by any of `rows`.
+    fn useful(&self, rows: &[Vec<Pat>], row: &[Pat], tys: &[AstType]) -> bool {
+        let Some((head, rest)) = row.split_first() else {
+            return rows.is_empty();
+        };
+        let column = rows.iter().map(|row| &row[0]).collect::<Vec<_>>();
+        match head {
+            Pat::Ctor(ctor, fields) => self.split(ctor, &column).into_iter().any(|ctor| {
+                let mut row = fields.clone();
+                row.extend_from_slice(rest);
+                self.useful_specialized(rows, &ctor, row, tys)
+            }),
+            Pat::Opaque(key) => {
+                let specialized = rows
+                    .iter()
+                    .filter(|row| match &row[0] {
+                        Pat::Wild => true,
+                        Pat::Opaque(other) => other == key,
+                        Pat::Ctor(..) => false,
+                    })
+                    .map(|row| row[1..].to_vec())
+                    .collect::<Vec<_>>();
+                self.useful(&specialized, rest, &tys[1..])
+            }
+            // Listing the constructors no arm names only turns wildcards into more
+            // wildcards, which never ends for types that contain themselves.
+            Pat::Wild => match self.all_ctors(&tys[0], &column).filter(|ctors| {
+                ctors.is_empty() || column.iter().any(|pat| matches!(pat, Pat::Ctor(..)))
+            }) {
+                Some(ctors) => ctors.into_iter().any(|ctor| {
+                    let mut row = vec![Pat::Wild; self.field_types(&ctor).len()];
+                    row.extend_from_slice(rest);
+                    self.useful_specialized(rows, &ctor, row, tys)
+                }),
+                None => {
+                    let default = rows
+                        .iter()
+                        .filter(|row| matches!(row[0], Pat::Wild))
+                        .map(|row| row[1..].to_vec())
+                        .collect::<Vec<_>>();
+                    self.useful(&default, rest, &tys[1..])
+                }
+            },
+        }
+    }
+
+    /// Checks usefulness among the values built with `ctor`, after `row` has
+    /// already been specialized.
+    fn useful_specialized(&self, rows: &[Vec<Pat>], ctor: &Ctor, row: Vec<Pat>, tys: &[AstType]) -> bool {
+        let field_tys = self.field_types(ctor);
+        let specialized = rows
+            .iter()
+            .filter_map(|other| {
+                let mut specialized = match &other[0] {
+                    Pat::Wild => vec![Pat::Wild; field_tys.len()],
+                    Pat::Ctor(other_ctor, fields) if covers(other_ctor, ctor) => fields.clone(),
+                    _ => return None,
+                };
+                specialized.extend_from_slice(&other[1..]);
+                Some(specialized)
+            })
+            .collect::<Vec<_>>();
+        let mut tys_after = field_tys;
+        tys_after.extend_from_slice(&tys[1..]);
+        self.useful(&specialized, &row, &tys_after)
+    }
+
+    /// Every constructor of a type, or `None` if there are too many to list.
+    ///
+    /// Integer ranges are split at the boundaries of the ranges in `column`, so
+    /// each piece is either entirely inside or entirely outside every one of them.
+    fn all_ctors(&self, ty: &AstType, column: &[&Pat]) -> Option<Vec<Ctor>> {
+        match ty {
+            AstType::UnionOf(..) => Some(ty.union_members().into_iter().map(Ctor::Member).collect()),
+            AstType::Bool => Some(vec![Ctor::Bool(false), Ctor::Bool(true)]),
+            AstType::Structure(name) => match self.names.get(name) {
+                Some(ProgramType::Structure { .. }) => Some(vec![Ctor::Structure(name.clone())]),
+                Some(ProgramType::Enum { variants, .. }) => Some(
+                    variants
+                        .iter()
+                        .map(|(variant, _)| Ctor::Variant(name.clone(), variant.clone()))
+                        .collect(),
+                ),
+                _ => None,
+            },
+            _ => {
+                let (low, high) = integer_domain(ty)?;
+                Some(self.split(&Ctor::Range(low, high), column))
+            }
+        }
+    }
+
+    fn split(&self, ctor: &Ctor, column: &[&Pat]) -> Vec<Ctor> {
+        let Ctor::Range(low, high) = ctor else {
+            return vec![ctor.clone()];
+        };
+        let mut boundaries = vec![*low, *high + 1];
+        for pat in column {
+            if let Pat::Ctor(Ctor::Range(start, end), _) = pat {
+                boundaries.extend([*start, *end + 1].into_iter().filter(|at| low < at && at <= high));
+            }
+        }
+        boundaries.sort();
+        boundaries.dedup();
+        boundaries
+            .windows(2)
+            .map(|piece| Ctor::Range(piece[0], piece[1] - 1))
+            .collect()
+    }
+}
+
+/// Whether every value built with `inner` is also built with `outer`.
+fn covers(outer: &Ctor, inner: &Ctor) -> bool {
+    match (outer, inner) {
+        (Ctor::Range(outer_low, outer_high), Ctor::Range(low, high)) => {
+            outer_low <= low && high <= outer_high
+        }
+        _ => outer == inner,
+    }
+}
+
+fn integer_domain(ty: &AstType) -> Option<(i128, i128)> {
+    let bits = ty.integer_bits()?;
+    if ty.is_signed() {
+        Some((-(1 << (bits - 1)), (1 << (bits - 1)) - 1))
+    } else {
+        Some((0, (1 << bits) - 1))
+    }
+}
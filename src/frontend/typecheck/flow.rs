@@ -0,0 +1,199 @@
+use crate::frontend::parser::ast::{AstCodeBlock, AstExpression, AstHeader, AstStatement, AstType};
+use crate::frontend::typecheck::data::TypeInformation;
+
+impl AstHeader {
+    /// Checks that every path through a function returns a value of its declared type.
+    pub fn check_control_flow(&mut self, info: &mut TypeInformation) {
+        if self.is_generic() {
+            return;
+        }
+        if let AstHeader::Function {
+            name,
+            returns,
+            code_block,
+            ..
+        } = self
+        {
+            let always_returns = code_block.check_returns(returns, true, info);
+            if !always_returns && *returns != AstType::Void {
+                info.errors.push((
+                    format!(
+                        "function `{}` does not return a value of type {:?} on every path",
+                        name.name, returns
+                    ),
+                    name.token.span.clone(),
+                ));
+            }
+        }
+    }
+}
+
+impl AstCodeBlock {
+    /// Returns true when every path through this block returns from the function.
+    ///
+    /// `tail_position` is set when falling off the end of this block also
+    /// falls off the end of the function, which is the only place a tail
+    /// expression may produce the function's return value.
+    pub fn check_returns(
+        &mut self,
+        returns: &AstType,
+        tail_position: bool,
+        info: &mut TypeInformation,
+    ) -> bool {
+        let mut always_returns = false;
+        let statement_count = self.statements.len();
+        for (index, stmt) in self.statements.iter_mut().enumerate() {
+            let is_last = index + 1 == statement_count && self.tail.is_none();
+            if stmt.check_returns(returns, tail_position && is_last, info) {
+                always_returns = true;
+            }
+        }
+
+        let Some(tail) = &mut self.tail else {
+            return always_returns;
+        };
+        let tail_returns = tail.check_returns(returns, info);
+        if !tail_position {
+            // Calls to void functions at the end of a block are just statements.
+            if !matches!(tail.get_type(), AstType::Void | AstType::Invalid) {
+                info.errors.push((
+                    "tail expressions are only allowed at the end of a function".to_string(),
+                    tail.get_span(),
+                ));
+            }
+            return always_returns || tail_returns;
+        }
+        if !tail.coerce_to(returns, info) {
+            info.errors.push((
+                format!("expected {:?}, found {:?}", returns, tail.get_type()),
+                tail.get_span(),
+            ));
+        }
+        true
+    }
+
+    /// Like `check_returns`, for blocks whose tail is the value of a match arm.
+    pub fn check_arm_returns(&mut self, returns: &AstType, info: &mut TypeInformation) -> bool {
+        let mut always_returns = false;
+        for stmt in &mut self.statements {
+            if stmt.check_returns(returns, false, info) {
+                always_returns = true;
+            }
+        }
+        if let Some(tail) = &mut self.tail
+            && tail.check_returns(returns, info)
+        {
+            always_returns = true;
+        }
+        always_returns
+    }
+
+    /// Whether every path through this block returns from the function,
+    /// without reporting any errors.
+    pub fn always_returns(&self) -> bool {
+        self.statements.iter().any(|stmt| stmt.always_returns())
+            || self.tail.as_ref().is_some_and(|tail| tail.always_returns())
+    }
+}
+
+impl AstStatement {
+    pub fn check_returns(
+        &mut self,
+        returns: &AstType,
+        tail_position: bool,
+        info: &mut TypeInformation,
+    ) -> bool {
+        match self {
+            AstStatement::Comment(_) => false,
+            AstStatement::Expression(expr) => expr.check_returns(returns, info),
+            AstStatement::DeclareVariable { value, .. } => value.check_returns(returns, info),
+            AstStatement::ModifyVariable { value, .. } => value.check_returns(returns, info),
+            AstStatement::AssignField { object, value, .. } => {
+                let object_returns = object.check_returns(returns, info);
+                value.check_returns(returns, info) || object_returns
+            }
+            AstStatement::AssignIndex {
+                base, index, value, ..
+            } => {
+                let base_returns = base.check_returns(returns, info);
+                let index_returns = index.check_returns(returns, info);
+                value.check_returns(returns, info) || base_returns || index_returns
+            }
+            AstStatement::IfStatement {
+                if_true, if_false, ..
+            } => {
+                let true_returns = if_true.check_returns(returns, tail_position, info);
+                let false_returns = if_false.check_returns(returns, tail_position, info);
+                true_returns && false_returns
+            }
+            AstStatement::WhileStatement { do_true, .. } | AstStatement::ForEach { do_true, .. } => {
+                do_true.check_returns(returns, false, info);
+                false
+            }
+            AstStatement::Return { value, token } => {
+                match value {
+                    None if *returns != AstType::Void => info.errors.push((
+                        format!("expected a return value of type {:?}", returns),
+                        token.span.clone(),
+                    )),
+                    Some(value) if *returns == AstType::Void => info.errors.push((
+                        "void functions can not return a value".to_string(),
+                        value.get_span(),
+                    )),
+                    _ => {}
+                }
+                if let Some(value) = value {
+                    value.check_returns(returns, info);
+                }
+                true
+            }
+        }
+    }
+
+    pub fn always_returns(&self) -> bool {
+        match self {
+            AstStatement::Comment(_) => false,
+            AstStatement::Expression(expr) => expr.always_returns(),
+            AstStatement::DeclareVariable { value, .. } => value.always_returns(),
+            AstStatement::ModifyVariable { value, .. } => value.always_returns(),
+            AstStatement::AssignField { object, value, .. } => {
+                object.always_returns() || value.always_returns()
+            }
+            AstStatement::AssignIndex {
+                base, index, value, ..
+            } => base.always_returns() || index.always_returns() || value.always_returns(),
+            AstStatement::IfStatement {
+                if_true, if_false, ..
+            } => if_true.always_returns() && if_false.always_returns(),
+            AstStatement::WhileStatement { .. } | AstStatement::ForEach { .. } => false,
+            AstStatement::Return { .. } => true,
+        }
+    }
+}
+
+impl AstExpression {
+    /// Checks the returns inside a match expression, returning true when every arm returns.
+    pub fn check_returns(&mut self, returns: &AstType, info: &mut TypeInformation) -> bool {
+        let AstExpression::Match { value, arms, .. } = self else {
+            return false;
+        };
+        let value_returns = value.check_returns(returns, info);
+        let mut arms_return = !arms.is_empty();
+        for arm in arms.iter_mut() {
+            if !arm.body.check_arm_returns(returns, info) {
+                arms_return = false;
+            }
+        }
+        value_returns || arms_return
+    }
+
+    pub fn always_returns(&self) -> bool {
+        match self {
+            AstExpression::Match { value, arms, .. } => {
+                value.always_returns()
+                    || (!arms.is_empty() && arms.iter().all(|arm| arm.body.always_returns()))
+            }
+            _ => false,
+        }
+    }
+}
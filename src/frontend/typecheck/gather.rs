@@ -1,35 +1,128 @@
-use crate::frontend::parser::ast::AstHeader;
+use crate::frontend::parser::ast::{AstHeader, AstType};
 use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
 
 impl AstHeader {
-    pub fn gather_type_information(&self, info: &mut TypeInformation) {
+    /// Records the types and functions a header declares.
+    ///
+    /// Types written with type arguments are replaced by the instances they name.
+    pub fn gather_type_information(&mut self, info: &mut TypeInformation) {
+        if self.is_generic() {
+            return;
+        }
         match self {
             AstHeader::Import(_) => {}
             AstHeader::Function {
                 name,
                 parameters,
                 returns,
-                code_block,
-                locals,
+                ..
             } => {
+                for (ty, _) in parameters.iter_mut() {
+                    *ty = info.resolve_type(ty, &name.token.span);
+                }
+                *returns = info.resolve_type(returns, &name.token.span);
                 info.names.insert(
                     name.name.clone(),
                     ProgramType::Function {
                         name: name.name.clone(),
                         arguments: parameters
                             .iter()
-                            .map(|x| (x.1.clone(), x.0.clone()))
+                            .map(|x| (x.1.name.clone(), x.0.clone()))
                             .collect(),
                         returns: returns.clone(),
                     },
                 );
             }
-            AstHeader::Struct { name, fields } => {
+            AstHeader::Struct {
+                name,
+                fields,
+                implements,
+                shared,
+                ..
+            } => {
+                for (_, ty) in fields.iter_mut() {
+                    *ty = info.resolve_type(ty, &name.token.span);
+                }
+                for (interface, interface_tok) in implements.iter_mut() {
+                    *interface = info.resolve_type(interface, &interface_tok.span);
+                }
                 info.names.insert(
                     name.name.clone(),
                     ProgramType::Structure {
                         name: name.name.clone(),
                         fields: fields.clone(),
+                        implements: implements
+                            .iter()
+                            .filter_map(|(interface, _)| match interface {
+                                AstType::Structure(interface) => Some(interface.clone()),
+                                _ => None,
+                            })
+                            .collect(),
+                        shared: *shared,
+                    },
+                );
+            }
+            AstHeader::Enum { name, variants } => {
+                for variant in variants.iter_mut() {
+                    for ty in variant.fields.iter_mut() {
+                        *ty = info.resolve_type(ty, &variant.token.span);
+                    }
+                }
+                for (index, variant) in variants.iter().enumerate() {
+                    if variants[..index].iter().any(|other| other.name == variant.name) {
+                        info.errors.push((
+                            format!("variant `{}` is declared more than once", variant.name),
+                            variant.token.span.clone(),
+                        ));
+                    }
+                }
+                info.names.insert(
+                    name.name.clone(),
+                    ProgramType::Enum {
+                        name: name.name.clone(),
+                        variants: variants
+                            .iter()
+                            .map(|variant| (variant.name.clone(), variant.fields.clone()))
+                            .collect(),
+                    },
+                );
+            }
+            AstHeader::Interface { name, methods, .. } => {
+                let self_ty = AstType::Structure(name.name.clone());
+                for method in methods.iter_mut() {
+                    for (ty, _) in method.parameters.iter_mut() {
+                        *ty = info.resolve_type(ty, &method.name.token.span);
+                    }
+                    method.returns = info.resolve_type(&method.returns, &method.name.token.span);
+                    let takes_self = method
+                        .parameters
+                        .first()
+                        .is_some_and(|(ty, parameter)| parameter.name == "self" && *ty == self_ty);
+                    if !takes_self {
+                        info.errors.push((
+                            format!("interface method `{}` must take `self` first", method.name.name),
+                            method.name.token.span.clone(),
+                        ));
+                    }
+                    let qualified_name = format!("{}::{}", name.name, method.name.name);
+                    info.names.insert(
+                        qualified_name.clone(),
+                        ProgramType::Function {
+                            name: qualified_name,
+                            arguments: method
+                                .parameters
+                                .iter()
+                                .map(|x| (x.1.name.clone(), x.0.clone()))
+                                .collect(),
+                            returns: method.returns.clone(),
+                        },
+                    );
+                }
+                info.names.insert(
+                    name.name.clone(),
+                    ProgramType::Interface {
+                        name: name.name.clone(),
+                        methods: methods.iter().map(|x| x.name.name.clone()).collect(),
                     },
                 );
             }
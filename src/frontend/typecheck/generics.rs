@@ -0,0 +1,538 @@
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstExpression, AstHeader, AstType, PathData};
+use crate::frontend::span::Span;
+use crate::frontend::typecheck::annotate::settle;
+use crate::frontend::typecheck::data::{Instantiation, ProgramType, TypeInformation};
+use crate::frontend::typecheck::scope::Scopes;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+
+/// How many copies of generic headers are made before giving up, which stops
+/// generics that instantiate themselves with ever larger type arguments.
+const INSTANTIATION_LIMIT: usize = 10_000;
+
+impl AstHeader {
+    pub fn is_generic(&self) -> bool {
+        match self {
+            AstHeader::Function { generics, .. }
+            | AstHeader::Struct { generics, .. }
+            | AstHeader::Interface { generics, .. } => !generics.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// Records generic structs, interfaces and functions, which have to be known before any
+    /// type written with type arguments can be resolved.
+    pub fn gather_generics(&self, info: &mut TypeInformation) {
+        match self {
+            AstHeader::Struct {
+                name, implements, ..
+            } if self.is_generic() => {
+                if let Some((_, interface_tok)) = implements.first() {
+                    info.errors.push((
+                        "generic structs can not implement interfaces".to_string(),
+                        interface_tok.span.clone(),
+                    ));
+                }
+                info.generics.insert(name.name.clone(), self.clone());
+            }
+            AstHeader::Function { name, .. } | AstHeader::Interface { name, .. } if self.is_generic() => {
+                info.generics.insert(name.name.clone(), self.clone());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl TypeInformation {
+    /// Replaces generic structs and interfaces applied to type arguments with their
+    /// instances, making the instances that don't exist yet.
+    pub fn resolve_type(&mut self, ty: &AstType, span: &Span) -> AstType {
+        match ty {
+            AstType::Instance(name, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.resolve_type(argument, span))
+                    .collect::<Vec<_>>();
+                if arguments.contains(&AstType::Invalid) {
+                    return AstType::Invalid;
+                }
+                if let Some(AstHeader::Interface { .. }) = self.generics.get(name) {
+                    return self.instantiate_interface(name, arguments, span);
+                }
+                let Some(AstHeader::Struct {
+                    name: generic_name,
+                    generics,
+                    fields,
+                    shared,
+                    ..
+                }) = self.generics.get(name).cloned()
+                else {
+                    self.errors.push((format!("`{}` is not a generic struct", name), span.clone()));
+                    return AstType::Invalid;
+                };
+                if !self.check_type_arguments(name, &generics, &arguments, span) {
+                    return AstType::Invalid;
+                }
+
+                let instance_name = AstType::Instance(name.clone(), arguments.clone()).source_name();
+                if !self.names.contains_key(&instance_name) {
+                    // Registered before resolving the fields, so they can refer to the struct itself.
+                    self.names.insert(
+                        instance_name.clone(),
+                        ProgramType::Structure {
+                            name: instance_name.clone(),
+                            fields: vec![],
+                            implements: vec![],
+                            shared,
+                        },
+                    );
+                    self.instance_origins
+                        .insert(instance_name.clone(), (name.clone(), arguments.clone()));
+
+                    let bindings = generics.into_iter().zip(arguments).collect::<HashMap<_, _>>();
+                    let fields = fields
+                        .iter()
+                        .map(|(field, ty)| (field.clone(), self.resolve_type(&ty.substitute(&bindings), span)))
+                        .collect::<Vec<_>>();
+                    self.names.insert(
+                        instance_name.clone(),
+                        ProgramType::Structure {
+                            name: instance_name.clone(),
+                            fields: fields.clone(),
+                            implements: vec![],
+                            shared,
+                        },
+                    );
+                    self.instantiations.push(Instantiation {
+                        header: AstHeader::Struct {
+                            name: PathData {
+                                name: instance_name.clone(),
+                                token: generic_name.token,
+                            },
+                            generics: vec![],
+                            fields,
+                            implements: vec![],
+                            shared,
+                        },
+                        span: span.clone(),
+                    });
+                }
+                AstType::Structure(instance_name)
+            }
+            AstType::Structure(name)
+                if matches!(
+                    self.generics.get(name),
+                    Some(AstHeader::Struct { .. } | AstHeader::Interface { .. })
+                ) =>
+            {
+                self.errors.push((format!("`{}` needs type arguments", name), span.clone()));
+                AstType::Invalid
+            }
+            AstType::ArrayOf(inner) => AstType::ArrayOf(Box::new(self.resolve_type(inner, span))),
+            AstType::Function(parameters, returns) => AstType::Function(
+                parameters
+                    .iter()
+                    .map(|parameter| self.resolve_type(parameter, span))
+                    .collect(),
+                Box::new(self.resolve_type(returns, span)),
+            ),
+            AstType::Weak(target) => match self.resolve_type(target, span) {
+                AstType::Invalid => AstType::Invalid,
+                target @ (AstType::ArrayOf(_) | AstType::Structure(_) | AstType::Function(..)) => {
+                    AstType::Weak(Box::new(target))
+                }
+                other => {
+                    self.errors.push((
+                        format!("weak references point to objects, found {:?}", other),
+                        span.clone(),
+                    ));
+                    AstType::Invalid
+                }
+            },
+            AstType::UnionOf(..) => {
+                let members = ty
+                    .union_members()
+                    .iter()
+                    .map(|member| self.resolve_type(member, span))
+                    .collect();
+                AstType::union(members)
+            }
+            other => other.clone(),
+        }
+    }
+
+    fn check_type_arguments(&mut self, name: &str, generics: &[String], arguments: &[AstType], span: &Span) -> bool {
+        if generics.len() != arguments.len() {
+            self.errors.push((
+                format!(
+                    "`{}` expects {} type arguments, found {}",
+                    name,
+                    generics.len(),
+                    arguments.len()
+                ),
+                span.clone(),
+            ));
+            return false;
+        }
+        true
+    }
+
+    /// Makes the instance of a generic interface for the given type arguments, an
+    /// interface of its own whose methods have the type arguments substituted.
+    fn instantiate_interface(&mut self, name: &str, arguments: Vec<AstType>, span: &Span) -> AstType {
+        let Some(AstHeader::Interface {
+            name: generic_name,
+            generics,
+            mut methods,
+        }) = self.generics.get(name).cloned()
+        else {
+            unreachable!("only generic interfaces are instantiated as interfaces");
+        };
+        if !self.check_type_arguments(name, &generics, &arguments, span) {
+            return AstType::Invalid;
+        }
+
+        let instance_name = AstType::Instance(name.to_string(), arguments.clone()).source_name();
+        if !self.names.contains_key(&instance_name) {
+            // Registered before resolving the methods, which take the instance as `self`.
+            self.names.insert(
+                instance_name.clone(),
+                ProgramType::Interface {
+                    name: instance_name.clone(),
+                    methods: vec![],
+                },
+            );
+            self.instance_origins
+                .insert(instance_name.clone(), (name.to_string(), arguments.clone()));
+
+            let bindings = generics.into_iter().zip(arguments).collect::<HashMap<_, _>>();
+            for method in methods.iter_mut() {
+                for (ty, parameter) in method.parameters.iter_mut() {
+                    // A bare `self` is the interface itself, as it is for generic structs.
+                    *ty = match &*ty {
+                        AstType::Structure(self_ty) if parameter.name == "self" && self_ty == name => {
+                            AstType::Structure(instance_name.clone())
+                        }
+                        other => other.substitute(&bindings),
+                    };
+                }
+                method.returns = method.returns.substitute(&bindings);
+            }
+            let mut header = AstHeader::Interface {
+                name: PathData {
+                    name: instance_name.clone(),
+                    token: generic_name.token,
+                },
+                generics: vec![],
+                methods,
+            };
+            header.gather_type_information(self);
+            self.instantiations.push(Instantiation {
+                header,
+                span: span.clone(),
+            });
+        }
+        AstType::Structure(instance_name)
+    }
+
+    /// The name of the instance of the generic struct `generic` among the members of `ty`.
+    pub fn instance_named(&self, generic: &str, ty: &AstType) -> Option<String> {
+        ty.union_members().into_iter().find_map(|member| match member {
+            AstType::Structure(name)
+                if self
+                    .instance_origins
+                    .get(&name)
+                    .is_some_and(|(origin, _)| origin == generic) =>
+            {
+                Some(name)
+            }
+            _ => None,
+        })
+    }
+
+    /// Typechecks the copies of generic headers made so far, including the ones
+    /// they cause to be made in turn, and returns them.
+    pub fn monomorphize(&mut self) -> Vec<AstHeader> {
+        let mut instances = Vec::new();
+        while !self.instantiations.is_empty() {
+            let Instantiation { mut header, span } = self.instantiations.remove(0);
+            if instances.len() == INSTANTIATION_LIMIT {
+                self.errors.push((
+                    "generics are instantiated too many times, does a generic instantiate itself with larger and larger types?".to_string(),
+                    span,
+                ));
+                break;
+            }
+
+            let errors_before = self.errors.len();
+            header.annotate_type_information(self);
+            header.check_control_flow(self);
+            header.check_sharing(self);
+
+            // Errors in a copy are reported where its type arguments came from.
+            if let AstHeader::Function { name, .. } = &header {
+                for (message, error_span) in &mut self.errors[errors_before..] {
+                    *message = format!("{} (in `{}` at {:?})", message, name.name, error_span);
+                    *error_span = span.clone();
+                }
+            }
+            instances.push(header);
+        }
+        instances
+    }
+
+    /// Makes a copy of a generic function for the given type arguments, returning
+    /// the types of its parameters and its return type.
+    fn instantiate_function(
+        &mut self,
+        mut header: AstHeader,
+        instance_name: &str,
+        bindings: &HashMap<String, AstType>,
+        span: &Span,
+    ) -> (Vec<AstType>, AstType) {
+        let AstHeader::Function {
+            name,
+            generics,
+            parameters,
+            returns,
+            code_block,
+            ..
+        } = &mut header
+        else {
+            unreachable!("only functions are instantiated at call sites");
+        };
+        name.name = instance_name.to_string();
+        generics.clear();
+        for (ty, _) in parameters.iter_mut() {
+            *ty = self.resolve_type(&ty.substitute(bindings), span);
+        }
+        *returns = self.resolve_type(&returns.substitute(bindings), span);
+        code_block.substitute_types(bindings);
+
+        let parameter_tys = parameters.iter().map(|(ty, _)| ty.clone()).collect::<Vec<_>>();
+        let returns = returns.clone();
+        self.names.insert(
+            instance_name.to_string(),
+            ProgramType::Function {
+                name: instance_name.to_string(),
+                arguments: parameters.iter().map(|x| (x.1.name.clone(), x.0.clone())).collect(),
+                returns: returns.clone(),
+            },
+        );
+        self.instantiations.push(Instantiation {
+            header,
+            span: span.clone(),
+        });
+        (parameter_tys, returns)
+    }
+
+    /// A generic function, with a bare `self` in `fn List::get<T>(self)` typed as the
+    /// generic struct applied to the function's first type parameters, `List<T>`.
+    fn generic_function(&self, name: &str) -> Option<AstHeader> {
+        let mut header = self.generics.get(name).cloned()?;
+        let AstHeader::Function {
+            name,
+            generics,
+            parameters,
+            ..
+        } = &mut header
+        else {
+            return None;
+        };
+        if let Some((type_name, _)) = name.name.rsplit_once("::")
+            && let Some(AstHeader::Struct {
+                generics: struct_generics,
+                ..
+            }) = self.generics.get(type_name)
+            && let Some((self_ty, parameter)) = parameters.first_mut()
+            && parameter.name == "self"
+            && *self_ty == AstType::Structure(type_name.to_string())
+            && struct_generics.len() <= generics.len()
+        {
+            *self_ty = AstType::Instance(
+                type_name.to_string(),
+                generics[..struct_generics.len()]
+                    .iter()
+                    .map(|generic| AstType::Structure(generic.clone()))
+                    .collect(),
+            );
+        }
+        Some(header)
+    }
+
+    /// Infers type arguments by matching the type of a parameter against the type of its argument.
+    pub fn infer(
+        &self,
+        parameter: &AstType,
+        argument: &AstType,
+        generics: &[String],
+        bindings: &mut HashMap<String, AstType>,
+    ) -> Result<(), String> {
+        match parameter {
+            AstType::Structure(name) if generics.contains(name) => bind(name, argument, bindings),
+            AstType::Instance(name, parameters) => {
+                let AstType::Structure(instance) = argument else {
+                    return Ok(());
+                };
+                match self.instance_origins.get(instance) {
+                    Some((origin, arguments)) if origin == name && arguments.len() == parameters.len() => {
+                        for (parameter, argument) in parameters.iter().zip(arguments) {
+                            self.infer(parameter, argument, generics, bindings)?;
+                        }
+                        Ok(())
+                    }
+                    _ => Ok(()),
+                }
+            }
+            AstType::ArrayOf(parameter) => match argument {
+                AstType::ArrayOf(argument) => self.infer(parameter, argument, generics, bindings),
+                _ => Ok(()),
+            },
+            AstType::Weak(parameter) => match argument {
+                AstType::Weak(argument) => self.infer(parameter, argument, generics, bindings),
+                _ => Ok(()),
+            },
+            AstType::Function(parameters, returns) => match argument {
+                AstType::Function(arguments, argument_returns) if arguments.len() == parameters.len() => {
+                    for (parameter, argument) in parameters.iter().zip(arguments) {
+                        self.infer(parameter, argument, generics, bindings)?;
+                    }
+                    self.infer(returns, argument_returns, generics, bindings)
+                }
+                _ => Ok(()),
+            },
+            // `T | none` takes `T` from whatever the argument holds besides `none`.
+            AstType::UnionOf(..) => {
+                let (variables, known): (Vec<_>, Vec<_>) = parameter
+                    .union_members()
+                    .into_iter()
+                    .partition(|member| matches!(member, AstType::Structure(name) if generics.contains(name)));
+                let [AstType::Structure(variable)] = variables.as_slice() else {
+                    return Ok(());
+                };
+                let remaining = argument
+                    .union_members()
+                    .into_iter()
+                    .filter(|member| !known.contains(member))
+                    .collect::<Vec<_>>();
+                if remaining.is_empty() {
+                    return Ok(());
+                }
+                bind(variable, &AstType::union(remaining), bindings)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn bind(name: &str, ty: &AstType, bindings: &mut HashMap<String, AstType>) -> Result<(), String> {
+    match bindings.get(name) {
+        None => {
+            bindings.insert(name.to_string(), ty.clone());
+            Ok(())
+        }
+        Some(existing) if existing == ty || ty.widens_to(existing) => Ok(()),
+        Some(existing) if existing.widens_to(ty) => {
+            bindings.insert(name.to_string(), ty.clone());
+            Ok(())
+        }
+        Some(existing) => Err(format!(
+            "type argument `{}` can not be both {:?} and {:?}",
+            name, existing, ty
+        )),
+    }
+}
+
+impl AstExpression {
+    /// Calls a generic function, inferring its type arguments from the arguments
+    /// and instantiating it for them.
+    ///
+    /// The first `skip` arguments have already been annotated.
+    pub fn annotate_generic_invoke(
+        receiver: &mut AstExpression, arguments: &mut Vec<AstExpression>,
+        return_type: &mut OnceCell<AstType>, skip: usize, generic_name: &str, token: Token,
+        type_data: &mut TypeInformation, scopes: &mut Scopes) -> AstType {
+        for argument in arguments.iter_mut().skip(skip) {
+            argument.annotate_type_information(type_data, scopes);
+        }
+        let Some(AstHeader::Function { generics, parameters, .. }) =
+            type_data.generic_function(generic_name)
+        else {
+            return settle(return_type, AstType::Invalid);
+        };
+        if parameters.len() != arguments.len() {
+            type_data.errors.push((
+                format!(
+                    "function `{}` expects {} arguments, found {}",
+                    generic_name,
+                    parameters.len(),
+                    arguments.len()
+                ),
+                token.span.clone(),
+            ));
+            return settle(return_type, AstType::Invalid);
+        }
+        if arguments.iter().any(|argument| argument.get_type() == AstType::Invalid) {
+            return settle(return_type, AstType::Invalid);
+        }
+
+        let mut bindings = HashMap::new();
+        for ((parameter_ty, _), argument) in parameters.iter().zip(arguments.iter()) {
+            if let Err(message) =
+                type_data.infer(parameter_ty, &argument.get_type(), &generics, &mut bindings)
+            {
+                type_data.errors.push((message, argument.get_span()));
+                return settle(return_type, AstType::Invalid);
+            }
+        }
+        let Some(type_arguments) = generics
+            .iter()
+            .map(|generic| bindings.get(generic).cloned())
+            .collect::<Option<Vec<_>>>()
+        else {
+            let missing = generics
+                .iter()
+                .filter(|generic| !bindings.contains_key(*generic))
+                .map(|generic| format!("`{}`", generic))
+                .collect::<Vec<_>>();
+            type_data.errors.push((
+                format!(
+                    "can not infer type argument {} of `{}`",
+                    missing.join(", "),
+                    generic_name
+                ),
+                token.span.clone(),
+            ));
+            return settle(return_type, AstType::Invalid);
+        };
+
+        let instance_name = AstType::Instance(generic_name.to_string(), type_arguments).source_name();
+        let (parameter_tys, returns) = match type_data.names.get(&instance_name) {
+            Some(ProgramType::Function {
+                arguments, returns, ..
+            }) => (
+                arguments.iter().map(|(_, ty)| ty.clone()).collect(),
+                returns.clone(),
+            ),
+            _ => {
+                let header = type_data.generic_function(generic_name).unwrap();
+                type_data.instantiate_function(header, &instance_name, &bindings, &token.span)
+            }
+        };
+        *receiver = AstExpression::PathLiteral(PathData {
+            name: instance_name,
+            token,
+        });
+
+        for (parameter_ty, argument) in parameter_tys.iter().zip(arguments.iter_mut()) {
+            if !argument.coerce_to(parameter_ty, type_data) {
+                type_data.errors.push((
+                    format!("expected {:?}, found {:?}", parameter_ty, argument.get_type()),
+                    argument.get_span(),
+                ));
+            }
+        }
+        settle(return_type, returns)
+    }
+}
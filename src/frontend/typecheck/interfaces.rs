@@ -0,0 +1,83 @@
+use crate::frontend::parser::ast::{AstHeader, AstType};
+use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
+
+impl AstHeader {
+    /// Checks that a struct provides every method of the interfaces it declares.
+    pub fn check_conformance(&self, info: &mut TypeInformation) {
+        if self.is_generic() {
+            return;
+        }
+        let AstHeader::Struct {
+            name, implements, ..
+        } = self
+        else {
+            return;
+        };
+        let self_ty = AstType::Structure(name.name.clone());
+        for (interface, interface_tok) in implements {
+            // Interfaces with type arguments that don't resolve were already reported.
+            if *interface == AstType::Invalid {
+                continue;
+            }
+            let interface = interface.source_name();
+            let Some(ProgramType::Interface { methods, .. }) = info.names.get(&interface).cloned()
+            else {
+                info.errors.push((
+                    format!("`{}` is not an interface", interface),
+                    interface_tok.span.clone(),
+                ));
+                continue;
+            };
+            for method in methods {
+                let required = format!("{}::{}", interface, method);
+                let provided = format!("{}::{}", name.name, method);
+                let Some(ProgramType::Function {
+                    arguments: required_arguments,
+                    returns: required_returns,
+                    ..
+                }) = info.names.get(&required).cloned()
+                else {
+                    continue;
+                };
+                let Some(ProgramType::Function {
+                    arguments, returns, ..
+                }) = info.names.get(&provided).cloned()
+                else {
+                    info.errors.push((
+                        format!("struct `{}` does not implement `{}`", name.name, required),
+                        interface_tok.span.clone(),
+                    ));
+                    continue;
+                };
+                // `self` is the implementing struct, every other parameter has to match exactly.
+                let matches = arguments.len() == required_arguments.len()
+                    && arguments.first().is_some_and(|(_, ty)| *ty == self_ty)
+                    && arguments
+                        .iter()
+                        .zip(&required_arguments)
+                        .skip(1)
+                        .all(|((_, ty), (_, required_ty))| ty == required_ty)
+                    && returns == required_returns;
+                if !matches {
+                    info.errors.push((
+                        format!("`{}` does not match the signature of `{}`", provided, required),
+                        interface_tok.span.clone(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl AstType {
+    /// Whether this is a struct that declares it implements the interface `other`.
+    pub fn implements(&self, other: &AstType, type_data: &TypeInformation) -> bool {
+        let (AstType::Structure(name), AstType::Structure(interface)) = (self, other) else {
+            return false;
+        };
+        match type_data.names.get(name) {
+            Some(ProgramType::Structure { implements, .. }) => implements.contains(interface),
+            _ => false,
+        }
+    }
+}
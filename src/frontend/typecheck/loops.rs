@@ -0,0 +1,154 @@
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{
+    AstCodeBlock, AstExpression, AstIterable, AstStatement, AstType,
+};
+use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
+use crate::frontend::typecheck::scope::Scopes;
+use std::cell::OnceCell;
+
+/// Name of the hidden local holding what a `foreach` iterates over, which can't clash
+/// with a variable in the source.
+const SOURCE_LOCAL: &str = "<foreach source>";
+const INDEX_LOCAL: &str = "<foreach index>";
+
+/// The parts of a `foreach` loop that typechecking fills in.
+pub struct ForEachLocals<'a> {
+    /// The type of the loop variable.
+    pub ty: &'a mut OnceCell<AstType>,
+    pub local: &'a mut OnceCell<usize>,
+    pub source: &'a mut OnceCell<usize>,
+    pub index: &'a mut OnceCell<usize>,
+}
+
+impl AstStatement {
+    pub fn annotate_foreach(
+        name: &str,
+        iterable: &mut AstIterable,
+        do_true: &mut AstCodeBlock,
+        token: &Token,
+        locals: ForEachLocals,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) {
+        let ForEachLocals {
+            ty,
+            local,
+            source,
+            index,
+        } = locals;
+        scopes.push();
+        let element = match iterable {
+            AstIterable::Range { start, end, .. } => {
+                let element = match AstExpression::annotate_operands(
+                    start, end, token, "..", type_data, scopes,
+                ) {
+                    Some(element) if element.is_integer() => element,
+                    Some(element) => {
+                        type_data.errors.push((
+                            format!("ranges are over integers, found {:?}", element),
+                            token.span.clone(),
+                        ));
+                        AstType::Invalid
+                    }
+                    None => AstType::Invalid,
+                };
+                let _ = source.set(scopes.declare(
+                    SOURCE_LOCAL.to_string(),
+                    element.clone(),
+                    token.span.clone(),
+                ));
+                let _ = index.set(scopes.declare(
+                    INDEX_LOCAL.to_string(),
+                    element.clone(),
+                    token.span.clone(),
+                ));
+                element
+            }
+            AstIterable::Value { value, next } => {
+                let value_ty = value.annotate_type_information(type_data, scopes);
+                let _ = source.set(scopes.declare(
+                    SOURCE_LOCAL.to_string(),
+                    value_ty.clone(),
+                    token.span.clone(),
+                ));
+                match value_ty {
+                    AstType::ArrayOf(element) => {
+                        let _ = index.set(scopes.declare(
+                            INDEX_LOCAL.to_string(),
+                            AstType::USize,
+                            token.span.clone(),
+                        ));
+                        *element
+                    }
+                    AstType::Invalid => AstType::Invalid,
+                    other => match type_data.iterator_element(&other) {
+                        Some(element) => {
+                            *next = Some(Box::new(annotate_next(token, type_data, scopes)));
+                            element
+                        }
+                        None => {
+                            type_data.errors.push((
+                                format!(
+                                    "can not iterate over {:?}, expected an array, a range or an `Iterator`",
+                                    other
+                                ),
+                                value.get_span(),
+                            ));
+                            AstType::Invalid
+                        }
+                    },
+                }
+            }
+        };
+        let _ = ty.set(element.clone());
+
+        let _ = local.set(scopes.declare(name.to_string(), element, token.span.clone()));
+        do_true.annotate_type_information(type_data, scopes);
+        scopes.pop();
+    }
+}
+
+/// Builds and annotates `source.next()` on the hidden local holding an iterator.
+fn annotate_next(
+    token: &Token,
+    type_data: &mut TypeInformation,
+    scopes: &mut Scopes,
+) -> AstExpression {
+    let variable = |content: &str| AstExpression::VariableLiteral {
+        content: content.to_string(),
+        ty: OnceCell::new(),
+        token: token.clone(),
+        local: OnceCell::new(),
+    };
+    let mut call = AstExpression::Invoke {
+        receiver: Box::new(variable("next")),
+        arguments: vec![variable(SOURCE_LOCAL)],
+        return_type: OnceCell::new(),
+        open_paren_span: token.clone(),
+        close_paren_tok: token.clone(),
+        resolve_as_ufcs: true,
+    };
+    call.annotate_type_information(type_data, scopes);
+    call
+}
+
+impl TypeInformation {
+    /// The elements of an `Iterator` value, or of a struct that implements one. The
+    /// conformance check makes sure its `next` returns them or `none`.
+    pub fn iterator_element(&self, ty: &AstType) -> Option<AstType> {
+        let AstType::Structure(name) = ty else {
+            return None;
+        };
+        let interfaces = match self.names.get(name) {
+            Some(ProgramType::Structure { implements, .. }) => implements.clone(),
+            Some(ProgramType::Interface { .. }) => vec![name.clone()],
+            _ => return None,
+        };
+        interfaces
+            .iter()
+            .find_map(|interface| match self.instance_origins.get(interface) {
+                Some((origin, arguments)) if origin == "Iterator" => arguments.first().cloned(),
+                _ => None,
+            })
+    }
+}
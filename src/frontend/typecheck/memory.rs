@@ -0,0 +1,32 @@
+use crate::frontend::parser::ast::AstType;
+
+/// The intrinsic `spawn` calls, whose argument is checked to only capture shared values.
+pub const SPAWN: &str = "qre::thread::spawn";
+
+/// The parameters after `self` and the return type of a builtin method of weak
+/// references to `target`.
+///
+/// Calls to these are lowered to the intrinsic `qre::weak::{name}`.
+pub fn weak_method(name: &str, target: &AstType) -> Option<(Vec<AstType>, AstType)> {
+    match name {
+        "get" => Some((vec![], AstType::union(vec![target.clone(), AstType::None]))),
+        _ => None,
+    }
+}
+
+/// The intrinsic, parameters and return type of a builtin function, which functions
+/// the program declares shadow.
+pub fn builtin_function(name: &str) -> Option<(&'static str, Vec<AstType>, AstType)> {
+    match name {
+        // Frees the objects that are only kept alive by reference cycles, returning how many.
+        "collect_cycles" => Some(("qre::gc::collect", vec![], AstType::USize)),
+        // Runs the task on a new thread, returning the thread to wait for with `join`.
+        "spawn" => Some((
+            SPAWN,
+            vec![AstType::Function(vec![], Box::new(AstType::Void))],
+            AstType::UInt64,
+        )),
+        "join" => Some(("qre::thread::join", vec![AstType::UInt64], AstType::Void)),
+        _ => None,
+    }
+}
@@ -1,3 +1,20 @@
 mod annotate;
+mod arrays;
+mod closures;
+mod coerce;
 pub(crate) mod data;
+mod entry;
+mod enums;
+mod exhaustive;
+mod flow;
 mod gather;
+mod generics;
+mod interfaces;
+mod loops;
+mod memory;
+mod patterns;
+mod propagate;
+pub(crate) mod scope;
+mod sharing;
+mod structures;
+mod substitute;
@@ -0,0 +1,279 @@
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstExpression, AstMatchArm, AstPattern, AstType};
+use crate::frontend::typecheck::annotate::settle;
+use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
+use crate::frontend::typecheck::enums::find_variant;
+use crate::frontend::typecheck::scope::Scopes;
+use std::cell::OnceCell;
+
+impl AstExpression {
+    pub fn annotate_match(
+        value: &mut AstExpression,
+        arms: &mut Vec<AstMatchArm>,
+        ty: &mut OnceCell<AstType>,
+        token: &Token,
+        expected: Option<&AstType>,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        let value_ty = value.annotate_type_information(type_data, scopes);
+        let value_local = match value {
+            AstExpression::VariableLiteral { local, .. } => local.get().copied(),
+            _ => None,
+        };
+
+        for arm in arms.iter_mut() {
+            scopes.push();
+            arm.pattern.annotate_pattern(&value_ty, type_data, scopes);
+            // Matching a variable against one member of its union narrows it inside the arm.
+            if let Some(index) = value_local
+                && let Some(member) = arm.pattern.tested_type()
+                && member != value_ty
+            {
+                scopes.narrow(index, member);
+            }
+            arm.body.annotate_expecting(expected, type_data, scopes);
+            arm.locals = scopes.pop();
+        }
+
+        // Arms that always return don't produce a value, so they don't take part in
+        // deciding the type of the match.
+        let mut result_ty: Option<AstType> = None;
+        for arm in arms.iter().filter(|arm| !arm.body.always_returns()) {
+            let arm_ty = arm
+                .body
+                .tail
+                .as_ref()
+                .map(|tail| tail.get_type())
+                .unwrap_or(AstType::Void);
+            result_ty = match result_ty {
+                None => Some(arm_ty),
+                Some(current) if current.widens_to(&arm_ty) => Some(arm_ty),
+                Some(current) => Some(current),
+            };
+        }
+        let result_ty = result_ty.unwrap_or(AstType::Void);
+
+        for arm in arms.iter_mut().filter(|arm| !arm.body.always_returns()) {
+            match &mut arm.body.tail {
+                Some(tail) => {
+                    if !tail.coerce_to(&result_ty, type_data) {
+                        type_data.errors.push((
+                            format!("expected {:?}, found {:?}", result_ty, tail.get_type()),
+                            tail.get_span(),
+                        ));
+                    }
+                }
+                None if result_ty != AstType::Void && result_ty != AstType::Invalid => {
+                    type_data.errors.push((
+                        format!("expected {:?}, found Void", result_ty),
+                        arm.token.span.clone(),
+                    ));
+                }
+                None => {}
+            }
+        }
+
+        if value_ty != AstType::Invalid {
+            Self::check_exhaustiveness(&value_ty, arms, token, type_data);
+        }
+        settle(ty, result_ty)
+    }
+}
+
+impl AstPattern {
+    /// The union member this pattern selects, if it only matches one.
+    pub fn tested_type(&self) -> Option<AstType> {
+        match self {
+            AstPattern::Wildcard { .. } | AstPattern::Binding { .. } => None,
+            AstPattern::TypeTest { ty, .. } => Some(ty.clone()),
+            AstPattern::Literal(expr) => expr_type(expr),
+            AstPattern::Range { start, .. } => expr_type(start),
+            AstPattern::Structure { ty, .. } | AstPattern::Variant { ty, .. } => ty.get().cloned(),
+        }
+    }
+
+    pub fn annotate_pattern(
+        &mut self,
+        expected: &AstType,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) {
+        match self {
+            AstPattern::Wildcard { .. } => {}
+            AstPattern::Binding { name, token, local } => {
+                // A bare type name tests for that type instead of binding a variable.
+                let named_type = AstType::from_name(name).or_else(|| {
+                    match type_data.names.get(name.as_str()) {
+                        Some(ProgramType::Structure { .. } | ProgramType::Enum { .. }) => {
+                            Some(AstType::Structure(name.clone()))
+                        }
+                        _ => None,
+                    }
+                });
+                if let Some(ty) = named_type {
+                    *self = AstPattern::TypeTest {
+                        binding: None,
+                        ty,
+                        token: token.clone(),
+                    };
+                    return self.annotate_pattern(expected, type_data, scopes);
+                }
+                let _ = local.set(scopes.declare(name.clone(), expected.clone(), token.span.clone()));
+            }
+            AstPattern::TypeTest { binding, ty, token } => {
+                *ty = type_data.resolve_type(ty, &token.span);
+                if !is_member(ty, expected) {
+                    type_data.errors.push((
+                        format!("a value of type {:?} can never be {:?}", expected, ty),
+                        token.span.clone(),
+                    ));
+                }
+                if let Some((name, local)) = binding {
+                    let _ = local.set(scopes.declare(name.clone(), ty.clone(), token.span.clone()));
+                }
+            }
+            AstPattern::Literal(expr) => {
+                let literal_ty = expr.annotate_expecting(expected, type_data, scopes);
+                if literal_ty != AstType::Invalid && !is_member(&literal_ty, expected) {
+                    type_data.errors.push((
+                        format!("a value of type {:?} can never be {:?}", expected, literal_ty),
+                        expr.get_span(),
+                    ));
+                }
+            }
+            AstPattern::Range {
+                start,
+                end,
+                inclusive,
+                token,
+            } => {
+                let start_ty = start.annotate_expecting(expected, type_data, scopes);
+                let end_ty = end.annotate_expecting(&start_ty, type_data, scopes);
+                if start_ty == AstType::Invalid || end_ty == AstType::Invalid {
+                    return;
+                }
+                if !start_ty.is_integer() || start_ty != end_ty {
+                    type_data.errors.push((
+                        format!("ranges must be between integers of the same type, found {:?} and {:?}", start_ty, end_ty),
+                        token.span.clone(),
+                    ));
+                    return;
+                }
+                if !is_member(&start_ty, expected) {
+                    type_data.errors.push((
+                        format!("a value of type {:?} can never be {:?}", expected, start_ty),
+                        token.span.clone(),
+                    ));
+                }
+                if let (Some(low), Some(high)) = (integer_value(start), integer_value(end))
+                    && (low > high || (low == high && !*inclusive))
+                {
+                    type_data.errors.push(("range pattern is empty".to_string(), token.span.clone()));
+                }
+            }
+            AstPattern::Structure { name, fields, ty } => {
+                // `List { .. }` matches the instance of a generic struct the value holds.
+                if let Some(instance) = type_data.instance_named(&name.name, expected) {
+                    name.name = instance;
+                }
+                let Some(ProgramType::Structure {
+                    fields: declared_fields,
+                    ..
+                }) = type_data.names.get(&name.name).cloned()
+                else {
+                    type_data.errors.push((
+                        format!("structure `{}` is not defined", name.name),
+                        name.token.span.clone(),
+                    ));
+                    return;
+                };
+                let struct_ty = AstType::Structure(name.name.clone());
+                if !is_member(&struct_ty, expected) {
+                    type_data.errors.push((
+                        format!("a value of type {:?} can never be {:?}", expected, struct_ty),
+                        name.token.span.clone(),
+                    ));
+                }
+                let _ = ty.set(struct_ty);
+
+                let mut seen: Vec<String> = Vec::new();
+                for (field, pattern) in fields.iter_mut() {
+                    let token = pattern.get_token();
+                    if seen.contains(field) {
+                        type_data.errors.push((
+                            format!("field `{}` is matched more than once", field),
+                            token.span.clone(),
+                        ));
+                        continue;
+                    }
+                    seen.push(field.clone());
+                    let Some((_, field_ty)) = declared_fields.iter().find(|(declared, _)| declared == field) else {
+                        type_data.errors.push((
+                            format!("structure `{}` has no field `{}`", name.name, field),
+                            token.span.clone(),
+                        ));
+                        continue;
+                    };
+                    pattern.annotate_pattern(field_ty, type_data, scopes);
+                }
+            }
+            AstPattern::Variant { name, fields, ty } => {
+                let Some(field_tys) = find_variant(&name.name, type_data) else {
+                    type_data.errors.push((
+                        format!("`{}` is not an enum variant", name.name),
+                        name.token.span.clone(),
+                    ));
+                    return;
+                };
+                let (enum_name, _) = name.name.rsplit_once("::").unwrap();
+                let enum_ty = AstType::Structure(enum_name.to_string());
+                if !is_member(&enum_ty, expected) {
+                    type_data.errors.push((
+                        format!("a value of type {:?} can never be {:?}", expected, enum_ty),
+                        name.token.span.clone(),
+                    ));
+                }
+                let _ = ty.set(enum_ty);
+
+                if field_tys.len() != fields.len() {
+                    type_data.errors.push((
+                        format!(
+                            "variant `{}` carries {} values, found {}",
+                            name.name,
+                            field_tys.len(),
+                            fields.len()
+                        ),
+                        name.token.span.clone(),
+                    ));
+                    return;
+                }
+                for (field_ty, pattern) in field_tys.iter().zip(fields.iter_mut()) {
+                    pattern.annotate_pattern(field_ty, type_data, scopes);
+                }
+            }
+        }
+    }
+}
+
+/// Whether a pattern of type `ty` can match a value of type `expected`.
+fn is_member(ty: &AstType, expected: &AstType) -> bool {
+    ty == expected || expected.union_members().contains(ty)
+}
+
+fn expr_type(expr: &AstExpression) -> Option<AstType> {
+    match expr {
+        AstExpression::NumberLiteral { ty, .. }
+        | AstExpression::StringLiteral { ty, .. }
+        | AstExpression::BooleanLiteral { ty, .. } => ty.get().cloned(),
+        _ => None,
+    }
+}
+
+/// The value of an integer literal pattern.
+pub fn integer_value(expr: &AstExpression) -> Option<i128> {
+    match expr {
+        AstExpression::NumberLiteral { content, .. } => content.parse().ok(),
+        _ => None,
+    }
+}
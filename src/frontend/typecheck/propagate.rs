@@ -0,0 +1,51 @@
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstExpression, AstType};
+use crate::frontend::typecheck::annotate::settle;
+use crate::frontend::typecheck::data::TypeInformation;
+use crate::frontend::typecheck::scope::Scopes;
+use std::cell::OnceCell;
+
+impl AstExpression {
+    pub fn annotate_propagate(
+        value: &mut AstExpression,
+        ty: &mut OnceCell<AstType>,
+        token: &Token,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        let value_ty = value.annotate_type_information(type_data, scopes);
+        if value_ty == AstType::Invalid {
+            return settle(ty, AstType::Invalid);
+        }
+
+        let (errors, values): (Vec<_>, Vec<_>) = value_ty
+            .union_members()
+            .into_iter()
+            .partition(|member| type_data.instance_named("Err", member).is_some());
+        if errors.is_empty() || values.is_empty() {
+            type_data.errors.push((
+                format!(
+                    "`?` can only be used on unions of values and `Err`s, found {:?}",
+                    value_ty
+                ),
+                token.span.clone(),
+            ));
+            return settle(ty, AstType::Invalid);
+        }
+
+        let returns = scopes.returns.clone();
+        let returns_members = returns.union_members();
+        for error in &errors {
+            if !returns_members.contains(error) {
+                type_data.errors.push((
+                    format!(
+                        "`?` can not return {:?} from a function returning {:?}",
+                        error, returns
+                    ),
+                    token.span.clone(),
+                ));
+            }
+        }
+        settle(ty, AstType::union(values))
+    }
+}
@@ -0,0 +1,169 @@
+use crate::frontend::parser::ast::{AstCapture, AstLocal, AstType, PathData};
+use crate::frontend::span::Span;
+use std::collections::HashMap;
+
+/// Lexical scopes of the function currently being typechecked.
+///
+/// Every declaration gets a fresh entry in `locals`, even when it shadows an
+/// earlier one, so each local keeps a unique index the backend can give its
+/// own stack slot.
+#[derive(Debug, Clone)]
+pub struct Scopes {
+    pub locals: Vec<AstLocal>,
+    /// The declared return type of the function.
+    pub returns: AstType,
+    /// The locals of the enclosing function that a closure body reads.
+    pub captures: Vec<AstCapture>,
+    /// The scopes of the enclosing function while a closure body is typechecked.
+    enclosing: Option<Box<Scopes>>,
+    stack: Vec<Scope>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    names: HashMap<String, usize>,
+    declared: Vec<usize>,
+    narrowed: HashMap<usize, AstType>,
+}
+
+impl Scopes {
+    pub fn new(returns: AstType) -> Scopes {
+        Scopes {
+            locals: vec![],
+            returns,
+            captures: vec![],
+            enclosing: None,
+            stack: vec![Scope::default()],
+        }
+    }
+
+    /// Starts typechecking the body of a closure, nested in the current function.
+    pub fn enter_closure(&mut self, returns: AstType) {
+        let enclosing = std::mem::replace(self, Scopes::new(returns));
+        self.enclosing = Some(Box::new(enclosing));
+    }
+
+    /// Returns to the enclosing function, handing back the scopes of the closure body.
+    pub fn exit_closure(&mut self) -> Scopes {
+        let enclosing = self
+            .enclosing
+            .take()
+            .expect("exit_closure must follow enter_closure");
+        std::mem::replace(self, *enclosing)
+    }
+
+    pub fn push(&mut self) {
+        self.stack.push(Scope::default());
+    }
+
+    /// Drops the innermost scope, returning the locals that were declared in it.
+    pub fn pop(&mut self) -> Vec<usize> {
+        self.stack
+            .pop()
+            .expect("scope stack must not be empty")
+            .declared
+    }
+
+    pub fn declare(&mut self, name: String, ty: AstType, span: Span) -> usize {
+        let index = self.locals.len();
+        self.locals.push(AstLocal {
+            name: name.clone(),
+            ty,
+            span,
+        });
+        let scope = self.stack.last_mut().expect("scope stack must not be empty");
+        scope.names.insert(name, index);
+        scope.declared.push(index);
+        index
+    }
+
+    /// Declares the parameters of a function, each at its own name. Repeated names are
+    /// reported, but still get a local so the rest line up with the arguments.
+    pub fn declare_parameters(
+        &mut self,
+        parameters: &[(AstType, PathData)],
+        errors: &mut Vec<(String, Span)>,
+    ) {
+        for (index, (ty, parameter)) in parameters.iter().enumerate() {
+            if parameters[..index].iter().any(|(_, earlier)| earlier.name == parameter.name) {
+                errors.push((
+                    format!("parameter `{}` is declared more than once", parameter.name),
+                    parameter.token.span.clone(),
+                ));
+            }
+            self.declare(parameter.name.clone(), ty.clone(), parameter.token.span.clone());
+        }
+    }
+
+    /// Finds the innermost visible local with the given name.
+    ///
+    /// Inside a closure, locals of the enclosing functions are captured on first use
+    /// and declared as locals of the closure body.
+    pub fn resolve(&mut self, name: &str) -> Option<usize> {
+        if let Some(index) = self
+            .stack
+            .iter()
+            .rev()
+            .find_map(|scope| scope.names.get(name).copied())
+        {
+            return Some(index);
+        }
+        let enclosing = self.enclosing.as_mut()?;
+        let outer = enclosing.resolve(name)?;
+        let ty = enclosing.type_of(outer);
+        let span = enclosing.get(outer).span.clone();
+
+        let inner = self.locals.len();
+        self.locals.push(AstLocal {
+            name: name.to_string(),
+            ty,
+            span,
+        });
+        let scope = self.stack.first_mut().expect("scope stack must not be empty");
+        scope.names.insert(name.to_string(), inner);
+        scope.declared.push(inner);
+        self.captures.push(AstCapture { outer, inner });
+        Some(inner)
+    }
+
+    pub fn is_captured(&self, index: usize) -> bool {
+        self.captures.iter().any(|capture| capture.inner == index)
+    }
+
+    /// Finds a local with the given name that has already gone out of scope.
+    pub fn resolve_dropped(&self, name: &str) -> Option<&AstLocal> {
+        self.locals.iter().rev().find(|local| local.name == name)
+    }
+
+    pub fn get(&self, index: usize) -> &AstLocal {
+        &self.locals[index]
+    }
+
+    /// The type a local has at this point, taking narrowing into account.
+    pub fn type_of(&self, index: usize) -> AstType {
+        self.stack
+            .iter()
+            .rev()
+            .find_map(|scope| scope.narrowed.get(&index).cloned())
+            .unwrap_or_else(|| self.locals[index].ty.clone())
+    }
+
+    /// Refines the type of a union-typed local for the rest of the innermost scope.
+    ///
+    /// The local keeps its declared union representation, so reads of it whose
+    /// type differs from the declared one are unboxed by the backend without a check.
+    pub fn narrow(&mut self, index: usize, ty: AstType) {
+        self.stack
+            .last_mut()
+            .expect("scope stack must not be empty")
+            .narrowed
+            .insert(index, ty);
+    }
+
+    /// Forgets every narrowing of a local, e.g. after it has been reassigned.
+    pub fn forget_narrowing(&mut self, index: usize) {
+        for scope in &mut self.stack {
+            scope.narrowed.remove(&index);
+        }
+    }
+}
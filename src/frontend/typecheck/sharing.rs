@@ -0,0 +1,89 @@
+use crate::frontend::parser::ast::{AstExpression, AstHeader, AstType};
+use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
+
+impl TypeInformation {
+    /// Whether values of `ty` can be used by several threads at once.
+    ///
+    /// The objects of these types are refcounted atomically and never change after
+    /// they are created: strings, shared structs, and the boxes of numbers and `none`
+    /// in unions. Arrays, closures, enums, interfaces and weak references stay on
+    /// the thread that made them.
+    pub fn is_shareable(&self, ty: &AstType) -> bool {
+        match ty {
+            AstType::Structure(name) if name == "std::string" => true,
+            AstType::Structure(name) => matches!(
+                self.names.get(name),
+                Some(ProgramType::Structure { shared: true, .. })
+            ),
+            AstType::UnionOf(..) => ty
+                .union_members()
+                .iter()
+                .all(|member| self.is_shareable(member)),
+            AstType::ArrayOf(_) | AstType::Function(..) | AstType::Weak(_) | AstType::Instance(..) => false,
+            // Invalid types were already reported.
+            _ => true,
+        }
+    }
+}
+
+impl AstHeader {
+    /// Checks that the fields of a shared struct only hold values other threads can use.
+    pub fn check_sharing(&self, info: &mut TypeInformation) {
+        let AstHeader::Struct {
+            name,
+            fields,
+            shared: true,
+            ..
+        } = self
+        else {
+            return;
+        };
+        if self.is_generic() {
+            return;
+        }
+        for (field, ty) in fields {
+            if !info.is_shareable(ty) {
+                info.errors.push((
+                    format!(
+                        "field `{}` of shared struct `{}` holds {:?}, which can not be shared between threads",
+                        field, name.name, ty
+                    ),
+                    name.token.span.clone(),
+                ));
+            }
+        }
+    }
+}
+
+impl AstExpression {
+    /// Checks the task given to `spawn`, whose captures are sent to the new thread.
+    pub fn check_sendable(task: &AstExpression, type_data: &mut TypeInformation) {
+        match task {
+            AstExpression::Lambda {
+                locals,
+                captures,
+                token,
+                ..
+            } => {
+                for capture in captures {
+                    let local = &locals[capture.inner];
+                    if !type_data.is_shareable(&local.ty) {
+                        type_data.errors.push((
+                            format!(
+                                "`{}` can not be sent to another thread, {:?} is not shared",
+                                local.name, local.ty
+                            ),
+                            token.span.clone(),
+                        ));
+                    }
+                }
+            }
+            // A function, which captures nothing.
+            AstExpression::VariableLiteral { local, .. } if local.get().is_none() => {}
+            _ => type_data.errors.push((
+                "`spawn` takes a closure written at the call or a function, so what it captures can be checked".to_string(),
+                task.get_span(),
+            )),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::frontend::lexer::tokens::Token;
+use crate::frontend::parser::ast::{AstExpression, AstHeader, AstType};
+use crate::frontend::typecheck::annotate::settle;
+use crate::frontend::typecheck::data::{ProgramType, TypeInformation};
+use crate::frontend::typecheck::scope::Scopes;
+use std::cell::OnceCell;
+use std::collections::HashMap;
+
+impl AstExpression {
+    /// Annotates a structure literal. A literal of a generic struct takes its type
+    /// arguments from `expected` when that holds an instance of the struct.
+    pub fn annotate_structure_literal(
+        ty: &mut AstType,
+        fields: &mut [(String, AstExpression)],
+        token: &Token,
+        expected: Option<&AstType>,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        let AstType::Structure(name) = ty.clone() else {
+            unreachable!("structure literals are parsed with a named type");
+        };
+        let expected_instance = expected.and_then(|expected| {
+            expected.union_members().into_iter().find(|member| {
+                matches!(member, AstType::Structure(instance)
+                    if type_data.instance_origins.get(instance).is_some_and(|(origin, _)| *origin == name))
+            })
+        });
+        if let Some(instance) = expected_instance {
+            *ty = instance;
+        } else if matches!(type_data.generics.get(&name), Some(AstHeader::Struct { .. })) {
+            *ty = Self::infer_structure_instance(&name, fields, token, type_data, scopes);
+            if *ty == AstType::Invalid {
+                return AstType::Invalid;
+            }
+        }
+        let AstType::Structure(name) = ty.clone() else {
+            unreachable!("instances of generic structs are structures");
+        };
+        let Some(ProgramType::Structure {
+            fields: declared_fields,
+            ..
+        }) = type_data.names.get(&name).cloned()
+        else {
+            type_data.errors.push((
+                format!("structure `{}` is not defined", name),
+                token.span.clone(),
+            ));
+            for (_, value) in fields.iter_mut() {
+                value.annotate_type_information(type_data, scopes);
+            }
+            *ty = AstType::Invalid;
+            return AstType::Invalid;
+        };
+
+        let mut seen: Vec<String> = Vec::new();
+        for (field, value) in fields.iter_mut() {
+            let Some((_, field_ty)) = declared_fields.iter().find(|(declared, _)| declared == field)
+            else {
+                type_data.errors.push((
+                    format!("structure `{}` has no field `{}`", name, field),
+                    value.get_span(),
+                ));
+                value.annotate_type_information(type_data, scopes);
+                continue;
+            };
+            if seen.contains(field) {
+                type_data.errors.push((
+                    format!("field `{}` is given more than once", field),
+                    value.get_span(),
+                ));
+            }
+            seen.push(field.clone());
+            value.annotate_coerced(field_ty, type_data, scopes);
+        }
+
+        let missing = declared_fields
+            .iter()
+            .filter(|(field, _)| !seen.contains(field))
+            .map(|(field, _)| format!("`{}`", field))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            type_data.errors.push((
+                format!(
+                    "missing {} {} in literal of structure `{}`",
+                    if missing.len() == 1 { "field" } else { "fields" },
+                    missing.join(", "),
+                    name
+                ),
+                token.span.clone(),
+            ));
+        }
+        ty.clone()
+    }
+
+    /// The instance of a generic struct a literal creates, with the type arguments
+    /// inferred from the values given to its fields.
+    fn infer_structure_instance(
+        name: &str,
+        fields: &mut [(String, AstExpression)],
+        token: &Token,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        let Some(AstHeader::Struct {
+            generics,
+            fields: declared_fields,
+            ..
+        }) = type_data.generics.get(name).cloned()
+        else {
+            return AstType::Invalid;
+        };
+
+        let mut bindings = HashMap::new();
+        for (field, value) in fields.iter_mut() {
+            let value_ty = value.annotate_type_information(type_data, scopes);
+            let Some((_, field_ty)) = declared_fields.iter().find(|(declared, _)| declared == field)
+            else {
+                continue;
+            };
+            if value_ty == AstType::Invalid {
+                return AstType::Invalid;
+            }
+            if let Err(message) = type_data.infer(field_ty, &value_ty, &generics, &mut bindings) {
+                type_data.errors.push((message, value.get_span()));
+                return AstType::Invalid;
+            }
+        }
+        let Some(type_arguments) = generics
+            .iter()
+            .map(|generic| bindings.get(generic).cloned())
+            .collect::<Option<Vec<_>>>()
+        else {
+            let missing = generics
+                .iter()
+                .filter(|generic| !bindings.contains_key(*generic))
+                .map(|generic| format!("`{}`", generic))
+                .collect::<Vec<_>>();
+            type_data.errors.push((
+                format!(
+                    "can not infer type argument {} of `{}`",
+                    missing.join(", "),
+                    name
+                ),
+                token.span.clone(),
+            ));
+            return AstType::Invalid;
+        };
+        type_data.resolve_type(&AstType::Instance(name.to_string(), type_arguments), &token.span)
+    }
+
+    pub fn annotate_field_access(
+        value: &mut AstExpression,
+        field: &str,
+        ty: &mut OnceCell<AstType>,
+        token: &Token,
+        type_data: &mut TypeInformation,
+        scopes: &mut Scopes,
+    ) -> AstType {
+        let value_ty = value.annotate_type_information(type_data, scopes);
+        let field_ty = type_data.field_type(&value_ty, field, token);
+        settle(ty, field_ty)
+    }
+}
+
+impl TypeInformation {
+    /// The type of a field of a struct, reporting an error if `ty` is not a struct
+    /// with that field.
+    pub fn field_type(&mut self, ty: &AstType, field: &str, token: &Token) -> AstType {
+        if *ty == AstType::Invalid {
+            return AstType::Invalid;
+        }
+        let fields = match ty {
+            AstType::Structure(name) => match self.names.get(name) {
+                Some(ProgramType::Structure { fields, .. }) => Some(fields),
+                _ => None,
+            },
+            _ => None,
+        };
+        let Some(fields) = fields else {
+            self.errors.push((
+                format!("only structures have fields, found {:?}", ty),
+                token.span.clone(),
+            ));
+            return AstType::Invalid;
+        };
+        match fields.iter().find(|(declared, _)| declared == field) {
+            Some((_, field_ty)) => field_ty.clone(),
+            None => {
+                self.errors.push((
+                    format!("structure `{}` has no field `{}`", ty.source_name(), field),
+                    token.span.clone(),
+                ));
+                AstType::Invalid
+            }
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::frontend::parser::ast::{
+    AstCodeBlock, AstExpression, AstIterable, AstPattern, AstStatement, AstType,
+};
+use std::collections::HashMap;
+
+impl AstType {
+    /// Replaces the type parameters named in `bindings` with their type arguments.
+    pub fn substitute(&self, bindings: &HashMap<String, AstType>) -> AstType {
+        match self {
+            AstType::Structure(name) => bindings.get(name).cloned().unwrap_or_else(|| self.clone()),
+            AstType::Instance(name, arguments) => AstType::Instance(
+                name.clone(),
+                arguments
+                    .iter()
+                    .map(|argument| argument.substitute(bindings))
+                    .collect(),
+            ),
+            AstType::ArrayOf(inner) => AstType::ArrayOf(Box::new(inner.substitute(bindings))),
+            AstType::Function(parameters, returns) => AstType::Function(
+                parameters
+                    .iter()
+                    .map(|parameter| parameter.substitute(bindings))
+                    .collect(),
+                Box::new(returns.substitute(bindings)),
+            ),
+            AstType::Weak(target) => AstType::Weak(Box::new(target.substitute(bindings))),
+            AstType::UnionOf(..) => AstType::union(
+                self.union_members()
+                    .iter()
+                    .map(|member| member.substitute(bindings))
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+}
+
+// The bodies of generic functions are never typechecked, so only the types written
+// in the source need to be replaced.
+
+impl AstCodeBlock {
+    pub fn substitute_types(&mut self, bindings: &HashMap<String, AstType>) {
+        for stmt in &mut self.statements {
+            stmt.substitute_types(bindings);
+        }
+        if let Some(tail) = &mut self.tail {
+            tail.substitute_types(bindings);
+        }
+    }
+}
+
+impl AstStatement {
+    pub fn substitute_types(&mut self, bindings: &HashMap<String, AstType>) {
+        match self {
+            AstStatement::Comment(_) => {}
+            AstStatement::Expression(expr) => expr.substitute_types(bindings),
+            AstStatement::DeclareVariable {
+                declared_ty, value, ..
+            } => {
+                if let Some(declared_ty) = declared_ty {
+                    *declared_ty = declared_ty.substitute(bindings);
+                }
+                value.substitute_types(bindings);
+            }
+            AstStatement::ModifyVariable { value, .. } => value.substitute_types(bindings),
+            AstStatement::AssignField { object, value, .. } => {
+                object.substitute_types(bindings);
+                value.substitute_types(bindings);
+            }
+            AstStatement::ForEach {
+                iterable, do_true, ..
+            } => {
+                match iterable {
+                    AstIterable::Range { start, end, .. } => {
+                        start.substitute_types(bindings);
+                        end.substitute_types(bindings);
+                    }
+                    AstIterable::Value { value, .. } => value.substitute_types(bindings),
+                }
+                do_true.substitute_types(bindings);
+            }
+            AstStatement::AssignIndex {
+                base, index, value, ..
+            } => {
+                base.substitute_types(bindings);
+                index.substitute_types(bindings);
+                value.substitute_types(bindings);
+            }
+            AstStatement::IfStatement {
+                cond,
+                if_true,
+                if_false,
+            } => {
+                cond.substitute_types(bindings);
+                if_true.substitute_types(bindings);
+                if_false.substitute_types(bindings);
+            }
+            AstStatement::WhileStatement { cond, do_true } => {
+                cond.substitute_types(bindings);
+                do_true.substitute_types(bindings);
+            }
+            AstStatement::Return { value, .. } => {
+                if let Some(value) = value {
+                    value.substitute_types(bindings);
+                }
+            }
+        }
+    }
+}
+
+impl AstExpression {
+    pub fn substitute_types(&mut self, bindings: &HashMap<String, AstType>) {
+        match self {
+            AstExpression::NumberLiteral { .. }
+            | AstExpression::StringLiteral { .. }
+            | AstExpression::BooleanLiteral { .. }
+            | AstExpression::NoneLiteral { .. }
+            | AstExpression::VariableLiteral { .. }
+            | AstExpression::PathLiteral(_) => {}
+            AstExpression::ArrayLiteral { content, .. } => {
+                for value in content {
+                    value.substitute_types(bindings);
+                }
+            }
+            AstExpression::StructureLiteral { ty, fields, .. } => {
+                *ty = ty.substitute(bindings);
+                for (_, value) in fields {
+                    value.substitute_types(bindings);
+                }
+            }
+            AstExpression::TypeLiteral { ty, .. } => *ty = ty.substitute(bindings),
+            AstExpression::Lambda {
+                parameters,
+                returns,
+                code_block,
+                ..
+            } => {
+                for (ty, _) in parameters {
+                    *ty = ty.substitute(bindings);
+                }
+                *returns = returns.substitute(bindings);
+                code_block.substitute_types(bindings);
+            }
+            AstExpression::EnumLiteral { arguments, .. } => {
+                for argument in arguments {
+                    argument.substitute_types(bindings);
+                }
+            }
+            AstExpression::Add { lhs, rhs, .. }
+            | AstExpression::Sub { lhs, rhs, .. }
+            | AstExpression::Mul { lhs, rhs, .. }
+            | AstExpression::Div { lhs, rhs, .. }
+            | AstExpression::Mod { lhs, rhs, .. }
+            | AstExpression::ShiftLeft { lhs, rhs, .. }
+            | AstExpression::ShiftRight { lhs, rhs, .. }
+            | AstExpression::Equal { lhs, rhs, .. }
+            | AstExpression::NotEqual { lhs, rhs, .. }
+            | AstExpression::LessThan { lhs, rhs, .. }
+            | AstExpression::GreaterThan { lhs, rhs, .. }
+            | AstExpression::LessThanOrEqual { lhs, rhs, .. }
+            | AstExpression::GreaterThanOrEqual { lhs, rhs, .. } => {
+                lhs.substitute_types(bindings);
+                rhs.substitute_types(bindings);
+            }
+            AstExpression::Cast { value, target, .. } | AstExpression::Is { value, target, .. } => {
+                value.substitute_types(bindings);
+                *target = target.substitute(bindings);
+            }
+            AstExpression::Propagate { value, .. } | AstExpression::FieldAccess { value, .. } => {
+                value.substitute_types(bindings)
+            }
+            AstExpression::Match { value, arms, .. } => {
+                value.substitute_types(bindings);
+                for arm in arms {
+                    arm.pattern.substitute_types(bindings);
+                    arm.body.substitute_types(bindings);
+                }
+            }
+            AstExpression::Invoke {
+                receiver,
+                arguments,
+                ..
+            } => {
+                receiver.substitute_types(bindings);
+                for argument in arguments {
+                    argument.substitute_types(bindings);
+                }
+            }
+            AstExpression::Index { base, other, .. } => {
+                base.substitute_types(bindings);
+                other.substitute_types(bindings);
+            }
+        }
+    }
+}
+
+impl AstPattern {
+    pub fn substitute_types(&mut self, bindings: &HashMap<String, AstType>) {
+        match self {
+            AstPattern::Wildcard { .. } | AstPattern::Binding { .. } | AstPattern::Range { .. } => {}
+            AstPattern::TypeTest { ty, .. } => *ty = ty.substitute(bindings),
+            AstPattern::Literal(expr) => expr.substitute_types(bindings),
+            AstPattern::Structure { fields, .. } => {
+                for (_, pattern) in fields {
+                    pattern.substitute_types(bindings);
+                }
+            }
+            AstPattern::Variant { fields, .. } => {
+                for pattern in fields {
+                    pattern.substitute_types(bindings);
+                }
+            }
+        }
+    }
+}
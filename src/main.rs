@@ -1,20 +1,41 @@
 #![feature(let_chains)]
 #![allow(dead_code)]
 
+use crate::backend::link::link;
+use crate::backend::llvm::codegen::CodeGenerator;
+use crate::backend::llvm::target::write_object;
+use crate::backend::llvm::Emit;
+use crate::backend::qir::lower::lower_program;
+use crate::backend::qir::print::print_program;
 use crate::frontend::lexer::iter::TokenIterator;
 use crate::frontend::lexer::structs::Lexer;
 use crate::frontend::typecheck::data::TypeInformation;
+use crate::options::{Command, Options};
 use frontend::parser::core::Parser;
+use inkwell::context::Context;
+use inkwell::module::Module;
 use std::alloc::System;
 use std::collections::HashMap;
 use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
 use std::process::exit;
-use crate::frontend::parser::ast::AstHeader;
 
 mod backend;
 mod frontend;
+mod options;
 
 fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            println!("Errs: {}", error);
+            exit(1);
+        }
+    };
+    // Only checking prints the output of every stage, so it doesn't get in the way of
+    // a program being run.
+    let verbose = matches!(options.command, Command::Check);
+
     let entries = std::fs::read_dir("./src/").unwrap();
 
     let mut handles = vec![];
@@ -28,13 +49,22 @@ fn main() {
         }));
     }
 
-    let tokens = handles
-        .into_iter()
-        .map(|thread| thread.join().unwrap())
+    // The prelude declares `Err`, the error member of result unions like `i32 | Err<string>`,
+    // and the `Iterator` interface `foreach` loops use.
+    let mut prelude = Lexer::new(
+        "<prelude>".to_string(),
+        include_str!("frontend/prelude.qre").to_string(),
+    );
+    let _ = prelude.lex();
+
+    let tokens = std::iter::once(prelude.tokens)
+        .chain(handles.into_iter().map(|thread| thread.join().unwrap()))
         .flatten()
         .collect::<Vec<_>>();
 
-    println!("Results: {:#?}", &tokens);
+    if verbose {
+        println!("Results: {:#?}", &tokens);
+    }
 
     let mut parser = Parser {
         tokens: TokenIterator {
@@ -42,10 +72,13 @@ fn main() {
             index: 0usize,
         },
         errors: vec![],
+        in_condition: false,
     };
     let ast = parser.parse();
 
-    println!("Parsing: {:#?}", ast);
+    if verbose {
+        println!("Parsing: {:#?}", ast);
+    }
 
     let Ok(mut headers) = ast else {
         println!("Errs: {:#?}", ast.unwrap_err());
@@ -54,20 +87,112 @@ fn main() {
 
     let mut type_info = TypeInformation {
         names: HashMap::new(),
+        errors: vec![],
+        type_ids: vec![],
+        generics: HashMap::new(),
+        instantiations: vec![],
+        instance_origins: HashMap::new(),
     };
     headers
         .iter()
+        .for_each(|x| x.gather_generics(&mut type_info));
+    headers
+        .iter_mut()
         .for_each(|x| x.gather_type_information(&mut type_info));
+    headers
+        .iter()
+        .for_each(|x| x.check_conformance(&mut type_info));
+    headers
+        .iter()
+        .for_each(|x| x.check_sharing(&mut type_info));
+    headers
+        .iter()
+        .for_each(|x| x.check_entry_point(&mut type_info));
 
     for header in &mut headers {
-        if let AstHeader::Function { ref mut code_block, ref mut locals, .. } = header {
-            code_block.annotate_type_information(
-                &mut type_info,
-                locals
-            );
+        header.annotate_type_information(&mut type_info);
+    }
+
+    headers
+        .iter_mut()
+        .for_each(|x| x.check_control_flow(&mut type_info));
+
+    // Generic headers are replaced by the copies made for their type arguments.
+    headers.retain(|x| !x.is_generic());
+    headers.extend(type_info.monomorphize());
+    if !type_info.errors.is_empty() {
+        println!("Errs: {:#?}", type_info.errors);
+        exit(1);
+    }
+
+    if verbose {
+        println!("Types: {:#?}", type_info);
+        println!("Annotated: {:#?}", headers);
+    }
+
+    let program = lower_program(&headers, &mut type_info, options.optimize_refcounts);
+
+    if let Some(Emit::Qir) = options.emit
+        && let Err(error) = std::fs::write("main.qir", print_program(&program))
+    {
+        println!("Errs: can not write main.qir: {}", error);
+        exit(1);
+    }
+
+    if matches!(options.command, Command::Check) && matches!(options.emit, None | Some(Emit::Qir)) {
+        return;
+    }
+    let context = Context::create();
+    let module = match CodeGenerator::new(&context, "main").generate(&program) {
+        Ok(module) => module,
+        Err(error) => {
+            println!("Errs: {}", error);
+            exit(1);
+        }
+    };
+    if let Some(emit) = options.emit {
+        let path = PathBuf::from(format!("main.{}", emit.extension()));
+        if let Err(error) = emit.write(&module, &path) {
+            println!("Errs: {}", error);
+            exit(1);
         }
     }
 
-    println!("Types: {:#?}", type_info);
-    println!("Annotated: {:#?}", headers);
+    let build_dir = std::env::temp_dir().join(format!("qre-build-{}", std::process::id()));
+    let result = match options.command {
+        Command::Check => Ok(0),
+        Command::Build { output } => build(&module, &build_dir, &output).map(|_| 0),
+        Command::Run { arguments } => {
+            let executable = build_dir.join("main");
+            build(&module, &build_dir, &executable).and_then(|_| {
+                std::process::Command::new(&executable)
+                    .args(arguments)
+                    .status()
+                    // Killed by a signal, the program has no exit code.
+                    .map(|status| status.code().unwrap_or(1))
+                    .map_err(|error| format!("can not run {}: {}", executable.display(), error))
+            })
+        }
+    };
+    let _ = std::fs::remove_dir_all(&build_dir);
+    match result {
+        Ok(code) => exit(code),
+        Err(error) => {
+            println!("Errs: {}", error);
+            exit(1);
+        }
+    }
+}
+
+/// Compiles `module` to an executable at `output`, through an object file in `build_dir`.
+fn build(module: &Module, build_dir: &Path, output: &Path) -> Result<(), String> {
+    // The runtime starts the program in `main`, which only programs declaring one have.
+    if module.get_function("main").is_none() {
+        return Err("the program has no `main` function".to_string());
+    }
+    std::fs::create_dir_all(build_dir)
+        .map_err(|error| format!("can not create {}: {}", build_dir.display(), error))?;
+    let object = build_dir.join("main.o");
+    write_object(module, &object)?;
+    link(&object, output)
 }
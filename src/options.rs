@@ -0,0 +1,69 @@
+use crate::backend::llvm::Emit;
+use std::path::PathBuf;
+
+/// What the compiler was asked to do with the program in `./src/`.
+pub enum Command {
+    /// Typechecks the program, printing what every stage produced.
+    Check,
+    /// `build [-o output]`, compiles the program to an executable.
+    Build { output: PathBuf },
+    /// `run [-- arguments..]`, builds the program and runs it with `arguments`.
+    Run { arguments: Vec<String> },
+}
+
+pub struct Options {
+    pub command: Command,
+    /// The form `--emit` asked for the generated module to be written in, next to the program.
+    pub emit: Option<Emit>,
+    /// Whether redundant retains and releases are removed, off with `--no-rc-elision`.
+    pub optimize_refcounts: bool,
+}
+
+impl Options {
+    /// Parses the command-line arguments, without the name of the compiler.
+    pub fn parse(arguments: impl IntoIterator<Item = String>) -> Result<Options, String> {
+        let mut arguments = arguments.into_iter().peekable();
+        let mut command = match arguments.peek().map(String::as_str) {
+            Some("build") => Command::Build {
+                output: PathBuf::from("main"),
+            },
+            Some("run") => Command::Run { arguments: vec![] },
+            _ => Command::Check,
+        };
+        if !matches!(command, Command::Check) {
+            arguments.next();
+        }
+
+        let mut emit = None;
+        let mut optimize_refcounts = true;
+        while let Some(argument) = arguments.next() {
+            match (&mut command, argument.as_str()) {
+                (Command::Build { output }, "-o") => {
+                    let Some(path) = arguments.next() else {
+                        return Err("`-o` expects the path of the executable".to_string());
+                    };
+                    *output = PathBuf::from(path);
+                }
+                (Command::Run { arguments: passed }, "--") => passed.extend(arguments.by_ref()),
+                (_, "--no-rc-elision") => optimize_refcounts = false,
+                (_, argument) => {
+                    let Some(kind) = argument.strip_prefix("--emit=") else {
+                        return Err(format!("unknown argument `{}`", argument));
+                    };
+                    let Some(kind) = Emit::from_flag(kind) else {
+                        return Err(format!(
+                            "unknown output `{}`, expected `llvm-ir`, `bitcode`, `obj` or `qir`",
+                            kind
+                        ));
+                    };
+                    emit = Some(kind);
+                }
+            }
+        }
+        Ok(Options {
+            command,
+            emit,
+            optimize_refcounts,
+        })
+    }
+}
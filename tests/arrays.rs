@@ -0,0 +1,60 @@
+mod common;
+
+/// Runs `program` with `qre run`, and returns its exit code and what it printed.
+fn run(name: &str, program: &str) -> (i32, String) {
+    let (code, printed) = common::qre(&format!("arrays-{}", name), program, &["run"], &[]);
+    let code = code.unwrap_or_else(|| panic!("{} was killed: {}", name, printed));
+    (code, printed)
+}
+
+#[test]
+fn insert_and_remove_within_bounds() {
+    let program = "
+fn main() -> i32 {
+    let values: [i32] = [];
+    values.push(1);
+    values.push(3);
+    values.insert(1, 2);
+    values.insert(3, 4);
+    let removed = values.remove(0);
+    let length: usize = values.len();
+    removed * 100 + values[0] * 10 + values[2] + (length as i32)
+}";
+    let (code, printed) = run("within-bounds", program);
+    assert_eq!(code, 100 + 20 + 4 + 3, "{}", printed);
+}
+
+#[test]
+fn insert_past_the_end_panics_with_its_location() {
+    let program = "
+fn main() -> i32 {
+    let values: [i32] = [];
+    values.push(1);
+    values.insert(2, 5);
+    0
+}";
+    let (code, printed) = run("insert-past-end", program);
+    assert_eq!(code, 101);
+    assert!(
+        printed.contains("panic: index out of bounds at ./src/main.qre:4:18"),
+        "{}",
+        printed
+    );
+}
+
+#[test]
+fn remove_at_the_length_panics_with_its_location() {
+    let program = "
+fn main() -> i32 {
+    let values: [i32] = [];
+    values.push(1);
+    values.remove(1)
+}";
+    let (code, printed) = run("remove-at-length", program);
+    assert_eq!(code, 101);
+    assert!(
+        printed.contains("panic: index out of bounds at ./src/main.qre:4:18"),
+        "{}",
+        printed
+    );
+}
@@ -0,0 +1,83 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("closures-{}", name), program, &[], &[])
+}
+
+/// Runs `program` with `qre run`, and returns its exit code.
+fn run(name: &str, program: &str) -> i32 {
+    let (code, printed) = common::qre(&format!("closures-{}", name), program, &["run"], &[]);
+    code.unwrap_or_else(|| panic!("{} was killed: {}", name, printed))
+}
+
+#[test]
+fn closures_and_functions_are_values() {
+    let program = "
+fn apply(f: fn(i32) -> i32, value: i32) -> i32 {
+    f(value)
+}
+
+fn double(value: i32) -> i32 {
+    value * 2
+}
+
+fn adder(amount: i32) -> fn(i32) -> i32 {
+    \\(value: i32) -> i32 { value + amount }
+}
+
+fn main() -> i32 {
+    let offset = 7;
+    let add = \\(value: i32) -> i32 { value + offset };
+    offset = 100;
+    let five = adder(5);
+    let first = apply(add, 3);
+    let second = apply(double, 4);
+    first + second + five(1)
+}";
+    let (code, printed) = check("values", program);
+    assert_eq!(code, Some(0), "{}", printed);
+    // `add` keeps the 7 it captured, assigning to `offset` afterwards doesn't change it.
+    assert_eq!(run("values", program), 10 + 8 + 6);
+}
+
+#[test]
+fn closure_misuse_is_reported() {
+    let program = "
+fn main() -> i32 {
+    let count = 0;
+    let bump = \\(v: i32) -> i32 {
+        count = count + 1;
+        v
+    };
+    let missing = \\(v: i32) -> i32 {
+        if v > 0 {
+            return v;
+        }
+    };
+    let number = 3;
+    let called = number(1);
+    let wrong = bump(1, 2);
+    let typed: fn(i32) -> i32 = \\(v: i64) -> i32 { 0 };
+    0
+}";
+    let (code, printed) = check("misuse", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(
+        printed.contains("can not assign to `count`, closures capture variables by value"),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains("closure does not return a value of type Int32 on every path"),
+        "{}",
+        printed
+    );
+    assert!(printed.contains("values of type Int32 can not be called"), "{}", printed);
+    assert!(printed.contains("closure expects 1 arguments, found 2"), "{}", printed);
+    assert!(
+        printed.contains("expected Function([Int32], Int32), found Function([Int64], Int32)"),
+        "{}",
+        printed
+    );
+}
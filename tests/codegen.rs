@@ -0,0 +1,54 @@
+mod common;
+
+const PROGRAM: &str = "
+fn sum(first: i32, second: i32) -> i32 {
+    let total = first + second;
+    total
+}
+
+fn main() -> i32 {
+    sum(1, 2)
+}";
+
+/// Checks `PROGRAM` with `qre --emit=<kind>`, and returns its exit code, what it printed
+/// and the contents of `output`.
+fn emit(kind: &str, output: &str) -> (Option<i32>, String, Option<Vec<u8>>) {
+    common::qre_writing(
+        &format!("codegen-{}", kind),
+        PROGRAM,
+        &[&format!("--emit={}", kind)],
+        &[],
+        Some(output),
+    )
+}
+
+#[test]
+fn llvm_ir_is_written_next_to_the_program() {
+    let (code, printed, ir) = emit("llvm-ir", "main.ll");
+    assert_eq!(code, Some(0), "{}", printed);
+    let ir = String::from_utf8(ir.expect("main.ll was not written")).unwrap();
+    // Functions of the program are prefixed, the entry point is the C `main`.
+    assert!(ir.contains("define i32 @qre.sum(i32 "), "{}", ir);
+    assert!(ir.contains("define i32 @main("), "{}", ir);
+    assert!(ir.contains("alloca i32"), "{}", ir);
+    assert!(ir.contains("@llvm.sadd.with.overflow.i32"), "{}", ir);
+}
+
+#[test]
+fn bitcode_is_written_next_to_the_program() {
+    let (code, printed, bitcode) = emit("bitcode", "main.bc");
+    assert_eq!(code, Some(0), "{}", printed);
+    let bitcode = bitcode.expect("main.bc was not written");
+    assert!(bitcode.starts_with(b"BC\xc0\xde"), "{:?}", &bitcode[..bitcode.len().min(4)]);
+}
+
+#[test]
+fn unknown_outputs_are_rejected() {
+    let (code, printed) = common::qre("codegen-unknown", PROGRAM, &["--emit=asm"], &[]);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(
+        printed.contains("Errs: unknown output `asm`, expected `llvm-ir`, `bitcode`, `obj` or `qir`"),
+        "{}",
+        printed
+    );
+}
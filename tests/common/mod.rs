@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Runs `qre` with `arguments` in a project holding `program` as src/main.qre, and
+/// returns its exit code and what it printed.
+pub fn qre(
+    name: &str,
+    program: &str,
+    arguments: &[&str],
+    env: &[(&str, &str)],
+) -> (Option<i32>, String) {
+    let (code, printed, _) = qre_writing(name, program, arguments, env, None);
+    (code, printed)
+}
+
+/// Like `qre`, and also returns the contents of the file `output` in the project, if
+/// `qre` wrote it.
+pub fn qre_writing(
+    name: &str,
+    program: &str,
+    arguments: &[&str],
+    env: &[(&str, &str)],
+    output: Option<&str>,
+) -> (Option<i32>, String, Option<Vec<u8>>) {
+    let dir = std::env::temp_dir().join(format!("qre-test-{}-{}", name, std::process::id()));
+    let src = dir.join("src");
+    std::fs::create_dir_all(&src).unwrap();
+    std::fs::write(src.join("main.qre"), program).unwrap();
+
+    let result = Command::new(PathBuf::from(env!("CARGO_BIN_EXE_qre-lang")))
+        .args(arguments)
+        .envs(env.iter().copied())
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    let written = output.and_then(|output| std::fs::read(dir.join(output)).ok());
+    let _ = std::fs::remove_dir_all(&dir);
+    let printed = String::from_utf8_lossy(&result.stdout).to_string()
+        + &String::from_utf8_lossy(&result.stderr);
+    (result.status.code(), printed, written)
+}
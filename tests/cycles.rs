@@ -0,0 +1,102 @@
+mod common;
+
+const PAIR: &str = "
+struct Pair {
+    other: Pair | none;
+}
+
+fn make_cycle() -> i32 {
+    let a = Pair { other: none };
+    let b = Pair { other: a };
+    a.other = b;
+    0
+}
+";
+
+/// Runs `program` with `qre run`, and returns its exit code. `period` is passed as
+/// QRE_CYCLE_PERIOD, 0 leaves collecting to the program.
+fn run(name: &str, program: &str, period: usize) -> i32 {
+    let period = period.to_string();
+    let (code, printed) = common::qre(
+        &format!("cycles-{}", name),
+        program,
+        &["run"],
+        &[("QRE_CYCLE_PERIOD", &period)],
+    );
+    code.unwrap_or_else(|| panic!("{} was killed: {}", name, printed))
+}
+
+#[test]
+fn unreachable_cycle_is_collected() {
+    let program = format!(
+        "{}
+fn main() -> i32 {{
+    make_cycle();
+    collect_cycles() as i32
+}}",
+        PAIR
+    );
+    assert_eq!(run("unreachable", &program, 0), 2);
+}
+
+#[test]
+fn reachable_cycle_is_kept() {
+    let program = format!(
+        "{}
+fn main() -> i32 {{
+    let a = Pair {{ other: none }};
+    let b = Pair {{ other: a }};
+    a.other = b;
+    let collected = collect_cycles() as i32;
+    match a.other {{
+        none => 100,
+        _ => collected,
+    }}
+}}",
+        PAIR
+    );
+    assert_eq!(run("reachable", &program, 0), 0);
+}
+
+#[test]
+fn cycles_are_collected_periodically() {
+    let program = format!(
+        "{}
+fn main() -> i32 {{
+    foreach i in 0..100 {{
+        make_cycle();
+    }}
+    collect_cycles() as i32
+}}",
+        PAIR
+    );
+    assert_eq!(run("manual", &program, 0), 200);
+    // Every 10 possible roots are collected as they come, leaving fewer for the end.
+    assert!(run("periodic", &program, 10) < 10);
+}
+
+#[test]
+fn weak_parent_does_not_keep_tree_alive() {
+    let program = "
+struct Node {
+    children: [Node];
+    parent: weak Node | none;
+}
+
+fn orphan() -> weak Node {
+    let parent = Node { children: [], parent: none };
+    let child = Node { children: [], parent: parent as weak Node };
+    parent.children.push(child);
+    parent
+}
+
+fn main() -> i32 {
+    let reclaimed = match orphan().get() {
+        none => 1,
+        _ => 0,
+    };
+    reclaimed * 10 + (collect_cycles() as i32)
+}";
+    // Freed by its count alone, with nothing left for the collector.
+    assert_eq!(run("weak", program, 0), 10);
+}
@@ -0,0 +1,72 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("executables-{}", name), program, &[], &[])
+}
+
+const COUNT_ARGUMENTS: &str = "
+fn main(arguments: [string]) -> u8 {
+    let count: usize = arguments.len();
+    count as u8
+}";
+
+#[test]
+fn run_passes_arguments_to_main() {
+    let (code, printed) = common::qre(
+        "executables-arguments",
+        COUNT_ARGUMENTS,
+        &["run", "--", "first", "--second"],
+        &[],
+    );
+    // The first argument is the path of the executable.
+    assert_eq!(code, Some(3), "{}", printed);
+}
+
+#[test]
+fn build_writes_an_executable() {
+    let (code, printed, executable) = common::qre_writing(
+        "executables-build",
+        COUNT_ARGUMENTS,
+        &["build", "-o", "server"],
+        &[],
+        Some("server"),
+    );
+    assert_eq!(code, Some(0), "{}", printed);
+    let executable = executable.expect("server was not written");
+    assert!(!executable.is_empty());
+    if cfg!(target_os = "linux") {
+        assert!(executable.starts_with(b"\x7fELF"), "{:?}", &executable[..4]);
+    }
+}
+
+#[test]
+fn main_must_be_callable_by_the_runtime() {
+    let program = "
+fn main(count: i32) -> bool {
+    true
+}";
+    let (code, printed) = check("main", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(
+        printed.contains("`main` takes no parameters or the arguments of the program as `[string]`"),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains("`main` returns void or an integer exit code, found Bool"),
+        "{}",
+        printed
+    );
+}
+
+#[test]
+fn malformed_command_lines_are_rejected() {
+    let (code, printed) = common::qre("executables-output", COUNT_ARGUMENTS, &["build", "-o"], &[]);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(printed.contains("Errs: `-o` expects the path of the executable"), "{}", printed);
+
+    let (code, printed) = common::qre("executables-unknown", COUNT_ARGUMENTS, &["run", "--fast"], &[]);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(printed.contains("Errs: unknown argument `--fast`"), "{}", printed);
+}
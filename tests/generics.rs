@@ -0,0 +1,43 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("generics-{}", name), program, &[], &[])
+}
+
+#[test]
+fn literal_takes_type_arguments_from_expected_type() {
+    let program = "
+struct Box<T> {
+    value: T;
+}
+
+fn small() -> Box<u8> {
+    Box { value: 200 }
+}
+
+fn main() -> i32 {
+    let wide: Box<i64> = Box { value: 4 };
+    let maybe: Box<i64> | none = Box { value: 5 };
+    let inferred = Box { value: 6 };
+    (wide.value as i32) + inferred.value + (small().value as i32)
+}";
+    let (code, printed) = check("expected-arguments", program);
+    assert_eq!(code, Some(0), "{}", printed);
+}
+
+#[test]
+fn fields_must_match_expected_type_arguments() {
+    let program = "
+struct Box<T> {
+    value: T;
+}
+
+fn main() -> i32 {
+    let wide: Box<i64> = Box { value: true };
+    0
+}";
+    let (code, printed) = check("mismatched-arguments", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(printed.contains("expected Int64, found Bool"), "{}", printed);
+}
@@ -0,0 +1,106 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("interfaces-{}", name), program, &[], &[])
+}
+
+/// Runs `program` with `qre run`, and returns its exit code.
+fn run(name: &str, program: &str) -> i32 {
+    let (code, printed) = common::qre(&format!("interfaces-{}", name), program, &["run"], &[]);
+    code.unwrap_or_else(|| panic!("{} was killed: {}", name, printed))
+}
+
+#[test]
+fn methods_dispatch_on_the_struct_behind_an_interface() {
+    let program = "
+interface Handler {
+    fn handle(self, request: i32) -> i32;
+}
+
+struct Doubler: Handler {
+    offset: i32;
+}
+
+fn Doubler::handle(self: Doubler, request: i32) -> i32 {
+    request * 2 + self.offset
+}
+
+struct Echo: Handler {
+    calls: i32;
+}
+
+fn Echo::handle(self: Echo, request: i32) -> i32 {
+    self.calls = self.calls + 1;
+    request
+}
+
+fn serve(handler: Handler, request: i32) -> i32 {
+    handler.handle(request)
+}
+
+fn main() -> i32 {
+    let handlers: [Handler] = [];
+    handlers.push(Doubler { offset: 1 });
+    handlers.push(Echo { calls: 0 });
+    let total = 0;
+    foreach handler in handlers {
+        total = total + serve(handler, 10);
+    }
+    let direct = Doubler { offset: 0 };
+    let doubled = direct.handle(5);
+    let back = handlers[1] as Echo;
+    total + doubled + back.calls
+}";
+    let (code, printed) = check("dispatch", program);
+    assert_eq!(code, Some(0), "{}", printed);
+    // 21 and 10 through the interface, 10 called directly, and the one call `Echo` counted.
+    assert_eq!(run("dispatch", program), 21 + 10 + 10 + 1);
+}
+
+#[test]
+fn structs_must_conform_to_the_interfaces_they_declare() {
+    let program = "
+interface Handler {
+    fn handle(self, request: i32) -> i32;
+}
+
+struct Point {
+    x: i32;
+}
+
+struct Missing: Handler {
+    x: i32;
+}
+
+struct Mismatched: Handler {
+    x: i32;
+}
+
+fn Mismatched::handle(self: Mismatched, request: i64) -> i32 {
+    self.x
+}
+
+struct NotAnInterface: Point {
+    x: i32;
+}
+
+fn main() -> i32 {
+    let handler: Handler = Point { x: 1 };
+    0
+}";
+    let (code, printed) = check("conformance", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(printed.contains("struct `Missing` does not implement `Handler::handle`"), "{}", printed);
+    assert!(
+        printed.contains("`Mismatched::handle` does not match the signature of `Handler::handle`"),
+        "{}",
+        printed
+    );
+    assert!(printed.contains("`Point` is not an interface"), "{}", printed);
+    assert!(
+        printed.contains("expected Structure(\\\"Handler\\\"), found Structure(\\\"Point\\\")"),
+        "{}",
+        printed
+    );
+}
@@ -0,0 +1,25 @@
+mod common;
+
+/// Runs `program` with `qre run`, and returns its exit code.
+fn run(name: &str, program: &str) -> i32 {
+    let (code, printed) = common::qre(&format!("loops-{}", name), program, &["run"], &[]);
+    code.unwrap_or_else(|| panic!("{} was killed: {}", name, printed))
+}
+
+#[test]
+fn inclusive_range_can_end_at_the_largest_value() {
+    let program = "
+fn main() -> i32 {
+    let last: u8 = 255;
+    let total = 0;
+    foreach i in 250..=last {
+        total = total + (i as i32) - 249;
+    }
+    foreach i in 0..3 {
+        total = total + 1;
+    }
+    total
+}";
+    // 1 + 2 + .. + 6 from the inclusive range, and 3 from the exclusive one.
+    assert_eq!(run("inclusive-end", program), 24);
+}
@@ -0,0 +1,69 @@
+mod common;
+
+/// Checks `program` with `qre --emit=qir`, and returns its exit code, what it printed
+/// and the QIR it wrote.
+fn emit_qir(name: &str, program: &str) -> (Option<i32>, String, Option<String>) {
+    let (code, printed, qir) = common::qre_writing(
+        &format!("lowering-{}", name),
+        program,
+        &["--emit=qir"],
+        &[],
+        Some("main.qir"),
+    );
+    (code, printed, qir.map(|qir| String::from_utf8(qir).unwrap()))
+}
+
+#[test]
+fn operators_become_intrinsics_of_their_type() {
+    let program = "
+fn scale(value: u8, factor: f64) -> f64 {
+    (value as f64) * factor
+}
+
+fn main() -> i32 {
+    let total: i64 = 40;
+    let scaled = scale(2, 1.5);
+    (total + 2) as i32
+}";
+    let (code, printed, qir) = emit_qir("intrinsics", program);
+    assert_eq!(code, Some(0), "{}", printed);
+    let qir = qir.expect("main.qir was not written");
+    assert!(
+        qir.contains(
+            "fn scale(%0: u8, %1: f64) -> f64 locals [u8, f64] {
+bb0:
+    ret (call f64 qre::f64::mul(call f64 qre::u8::to_f64(load u8 (%0)), load f64 (%1)))
+}"
+        ),
+        "{}",
+        qir
+    );
+    assert!(
+        qir.contains(
+            "fn main() -> i32 locals [i64, f64] {
+bb0:
+    store i64 (%0, i64 40)
+    store f64 (%1, call f64 scale(u8 2, f64 1.5))
+    ret (call i32 qre::i64::to_i32(call i64 qre::i64::add(load i64 (%0), i64 2)))
+}"
+        ),
+        "{}",
+        qir
+    );
+}
+
+#[test]
+fn programs_with_errors_are_not_lowered() {
+    let program = "
+fn main() -> i32 {
+    let flag = true;
+    flag + 1
+}";
+    let (code, printed) = common::qre("lowering-errors", program, &[], &[]);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(printed.contains("mismatched operand types Bool and Int32 for `+`"), "{}", printed);
+
+    let (code, printed, qir) = emit_qir("errors", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert_eq!(qir, None, "main.qir was written for a program with errors");
+}
@@ -0,0 +1,150 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("numbers-{}", name), program, &[], &[])
+}
+
+/// Runs `program` with `qre run`, and returns its exit code.
+fn run(name: &str, program: &str) -> i32 {
+    let (code, printed) = common::qre(&format!("numbers-{}", name), program, &["run"], &[]);
+    code.unwrap_or_else(|| panic!("{} was killed: {}", name, printed))
+}
+
+#[test]
+fn literal_arithmetic_takes_the_declared_type() {
+    let program = "
+fn main() -> i32 {
+    let a: i64 = 2000000000 + 2000000000;
+    (a / 1000000000) as i32
+}";
+    // Added as i32, the sum would overflow to a negative number.
+    assert_eq!(run("wide-sum", program), 4);
+
+    let program = "
+fn main() -> i32 {
+    let a: u8 = 1 + 2;
+    let b: u8 = 200 + 50 * 1;
+    (a + b) as i32
+}";
+    let (code, printed) = check("narrow-sum", program);
+    assert_eq!(code, Some(0), "{}", printed);
+}
+
+#[test]
+fn tails_take_the_return_type() {
+    let program = "
+enum Size {
+    Small,
+    Large,
+}
+
+fn one() -> u8 {
+    1
+}
+
+fn pick(first: bool) -> u8 {
+    if first {
+        1
+    } else {
+        2 + 3
+    }
+}
+
+fn limit(size: Size) -> u8 {
+    match size {
+        Size::Small => 10,
+        Size::Large => 200 + 55,
+    }
+}
+
+fn main() -> i32 {
+    (one() + pick(false) + limit(Size::Small)) as i32
+}";
+    let (code, printed) = check("tails", program);
+    assert_eq!(code, Some(0), "{}", printed);
+}
+
+#[test]
+fn literals_that_do_not_fit_are_reported() {
+    let program = "
+fn small() -> u8 {
+    256
+}
+
+fn main() -> i32 {
+    let a: u8 = 1 + 256;
+    (a + small()) as i32
+}";
+    let (code, printed) = check("too-large", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert_eq!(printed.matches("expected UInt8, found Int32").count(), 2, "{}", printed);
+}
+
+#[test]
+fn narrower_integers_widen_without_a_cast() {
+    let program = "
+fn main() -> i32 {
+    let small: u8 = 200;
+    let wide: u16 = small;
+    let signed: i32 = wide + 1000;
+    let size: usize = 3;
+    let number = size as u64;
+    let count: u32 = 4;
+    let total: u64 = number + count;
+    signed + (total as i32) + (small >> 1) as i32
+}";
+    let (code, printed) = check("widening", program);
+    assert_eq!(code, Some(0), "{}", printed);
+}
+
+#[test]
+fn conversions_that_can_lose_values_are_reported() {
+    let program = "
+fn main() -> i32 {
+    let small: u8 = 200;
+    let negative: i8 = 0 - 1;
+    let mixed = small + negative;
+    let flag = true as u8;
+    let size: usize = 3;
+    let number: u64 = size;
+    0
+}";
+    let (code, printed) = check("lossy", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(printed.contains("mismatched operand types UInt8 and Int8 for `+`"), "{}", printed);
+    assert!(printed.contains("can not cast Bool to UInt8"), "{}", printed);
+    assert!(printed.contains("expected UInt64, found USize"), "{}", printed);
+}
+
+#[test]
+fn signedness_decides_division_comparison_and_shifts() {
+    let program = "
+fn main() -> i32 {
+    let big: u8 = 200;
+    let seven: u8 = 7;
+    let negative: i8 = 0 - 100;
+    let total = 0;
+    if big > seven {
+        total = total + (big / seven) as i32;
+    }
+    total = total + (negative / 7) as i32 + 14;
+    total = total + (big >> 1) as i32;
+    total + (negative >> 1) as i32 + 50
+}";
+    // 200 / 7 and 200 >> 1 as unsigned, -100 / 7 and -100 >> 1 as signed.
+    assert_eq!(run("signedness", program), 28 + 100);
+}
+
+#[test]
+fn overflowing_arithmetic_panics() {
+    let program = "
+fn main() -> i32 {
+    let almost: u8 = 250;
+    let over = almost + 10;
+    over as i32
+}";
+    let (code, printed) = common::qre("numbers-overflow", program, &["run"], &[]);
+    assert_eq!(code, Some(101), "{}", printed);
+    assert!(printed.contains("panic: integer overflow at ./src/main.qre:3:23"), "{}", printed);
+}
@@ -0,0 +1,54 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("patterns-{}", name), program, &[], &[])
+}
+
+#[test]
+fn wrong_number_of_variant_fields_is_reported() {
+    let program = "
+enum Shape {
+    Circle(i32),
+    Square(i32, i32),
+}
+
+fn main() -> i32 {
+    match Shape::Circle(1) {
+        Shape::Circle => 0,
+        Shape::Square(width) => width,
+    }
+}";
+    let (code, printed) = check("arity", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(printed.contains("variant `Shape::Circle` carries 1 values, found 0"), "{}", printed);
+    assert!(printed.contains("variant `Shape::Square` carries 2 values, found 1"), "{}", printed);
+}
+
+#[test]
+fn unit_variant_is_written_without_parentheses() {
+    let program = "
+enum Light {
+    Off,
+    On(i32),
+}
+
+fn brightness(light: Light) -> i32 {
+    match light {
+        Light::Off => 0,
+        Light::On(level) => level,
+    }
+}
+
+fn main() -> i32 {
+    brightness(Light::Off) + brightness(Light::Off())
+}";
+    let (code, printed) = check("unit-parentheses", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(
+        printed.contains("variant `Light::Off` carries no values and is written without parentheses"),
+        "{}",
+        printed
+    );
+    assert_eq!(printed.matches("without parentheses").count(), 1, "{}", printed);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("propagation-{}", name), program, &[], &[])
+}
+
+/// Runs `program` with `qre run`, and returns its exit code.
+fn run(name: &str, program: &str) -> i32 {
+    let (code, printed) = common::qre(&format!("propagation-{}", name), program, &["run"], &[]);
+    code.unwrap_or_else(|| panic!("{} was killed: {}", name, printed))
+}
+
+const PARSE: &str = "
+fn parse(value: i32) -> i32 | Err<i32> {
+    if value < 0 {
+        return Err { error: value };
+    }
+    value
+}
+";
+
+#[test]
+fn question_mark_returns_the_error_early() {
+    let program = format!(
+        "{}
+fn twice(value: i32) -> i32 | Err<i32> {{
+    let parsed = parse(value)?;
+    parsed * 2
+}}
+
+fn main() -> i32 {{
+    let good = match twice(5) {{
+        value: i32 => value,
+        failed: Err<i32> => 0,
+    }};
+    let bad = match twice(0 - 3) {{
+        value: i32 => value,
+        failed: Err<i32> => failed.error,
+    }};
+    good + bad + 10
+}}",
+        PARSE
+    );
+    let (code, printed) = check("early-return", &program);
+    assert_eq!(code, Some(0), "{}", printed);
+    // `twice(5)` doubles, `twice(-3)` returns the error before doubling.
+    assert_eq!(run("early-return", &program), 10 - 3 + 10);
+}
+
+#[test]
+fn question_mark_needs_an_error_the_function_can_return() {
+    let program = format!(
+        "{}
+fn plain(value: i32) -> i32 {{
+    value
+}}
+
+fn strict(value: i32) -> i32 {{
+    parse(value)?
+}}
+
+fn other(value: i32) -> i32 | Err<string> {{
+    parse(value)?
+}}
+
+fn main() -> i32 {{
+    let bad = plain(1)?;
+    0
+}}",
+        PARSE
+    );
+    let (code, printed) = check("unreturnable", &program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(
+        printed.contains("`?` can not return Structure(\\\"Err<i32>\\\") from a function returning Int32"),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains(
+            "`?` can not return Structure(\\\"Err<i32>\\\") from a function returning \
+             UnionOf(Int32, Structure(\\\"Err<std::string>\\\"))"
+        ),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains("`?` can only be used on unions of values and `Err`s, found Int32"),
+        "{}",
+        printed
+    );
+}
@@ -0,0 +1,86 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("returns-{}", name), program, &[], &[])
+}
+
+#[test]
+fn every_path_returns() {
+    let program = "
+fn sign(a: i32) -> i32 {
+    if a < 0 {
+        return 0 - 1;
+    } else if a == 0 {
+        return 0;
+    }
+    1
+}
+
+fn main() -> i32 {
+    sign(4)
+}";
+    let (code, printed) = check("accepted", program);
+    assert_eq!(code, Some(0), "{}", printed);
+}
+
+#[test]
+fn missing_return_is_reported() {
+    let program = "
+fn sign(a: i32) -> i32 {
+    if a < 0 {
+        return 0 - 1;
+    }
+}
+
+fn main() -> i32 {
+    sign(4)
+}";
+    let (code, printed) = check("missing", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(
+        printed.contains("function `sign` does not return a value of type Int32 on every path"),
+        "{}",
+        printed
+    );
+}
+
+#[test]
+fn wrong_return_type_is_reported() {
+    let program = "
+fn early() -> i32 {
+    return true;
+}
+
+fn tail() -> i32 {
+    false
+}
+
+fn main() -> i32 {
+    early() + tail()
+}";
+    let (code, printed) = check("wrong-type", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert_eq!(printed.matches("expected Int32, found Bool").count(), 2, "{}", printed);
+}
+
+#[test]
+fn return_value_must_match_void() {
+    let program = "
+fn nothing() -> void {
+    return 1;
+}
+
+fn something() -> i32 {
+    return;
+}
+
+fn main() -> i32 {
+    nothing();
+    something()
+}";
+    let (code, printed) = check("void", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(printed.contains("void functions can not return a value"), "{}", printed);
+    assert!(printed.contains("expected a return value of type Int32"), "{}", printed);
+}
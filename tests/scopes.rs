@@ -0,0 +1,48 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("scopes-{}", name), program, &[], &[])
+}
+
+#[test]
+fn parameters_are_declared_at_their_names() {
+    let program = "fn add(a: i32,
+       b: i32) -> i32 {
+    a + b
+}
+
+fn main() -> i32 {
+    add(1, 2)
+}";
+    let (code, printed) = check("parameter-spans", program);
+    assert_eq!(code, Some(0), "{}", printed);
+    // The checked program is printed with the locals of each function.
+    let printed = printed.split_whitespace().collect::<String>();
+    assert!(
+        printed.contains(r#"AstLocal{name:"a",ty:Int32,span:[row0,8:8in./src/main.qre],}"#),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains(r#"AstLocal{name:"b",ty:Int32,span:[row1,8:8in./src/main.qre],}"#),
+        "{}",
+        printed
+    );
+}
+
+#[test]
+fn duplicate_parameters_are_reported() {
+    let program = "fn f(a: i32, a: i32) -> i32 {
+    a
+}
+
+fn main() -> i32 {
+    let g = \\(x: i32, x: i64) -> i32 { 0 };
+    f(1, 2) + g(3, 4)
+}";
+    let (code, printed) = check("duplicate-parameters", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(printed.contains("parameter `a` is declared more than once"), "{}", printed);
+    assert!(printed.contains("parameter `x` is declared more than once"), "{}", printed);
+}
@@ -0,0 +1,99 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("sharing-{}", name), program, &[], &[])
+}
+
+/// Runs `program` with `qre run`, and returns its exit code.
+fn run(name: &str, program: &str) -> i32 {
+    let (code, printed) = common::qre(&format!("sharing-{}", name), program, &["run"], &[]);
+    code.unwrap_or_else(|| panic!("{} was killed: {}", name, printed))
+}
+
+#[test]
+fn shared_objects_survive_being_retained_on_many_threads() {
+    let program = "
+shared struct Config {
+    port: i32;
+    name: string;
+}
+
+fn main() -> i32 {
+    let config = Config { port: 80, name: \"server\" };
+    let threads: [u64] = [];
+    foreach i in 0..8 {
+        threads.push(spawn(\\() -> void {
+            foreach j in 0..10000 {
+                let copy = config;
+            }
+        }));
+    }
+    foreach thread in threads {
+        join(thread);
+    }
+    config.port
+}";
+    let (code, printed) = check("retained", program);
+    assert_eq!(code, Some(0), "{}", printed);
+    // A count that lost an update would free `config` while it is still in use.
+    assert_eq!(run("retained", program), 80);
+}
+
+#[test]
+fn only_shareable_values_cross_threads() {
+    let program = "
+shared struct Config {
+    port: i32;
+}
+
+shared struct Bad {
+    items: [i32];
+}
+
+struct Point {
+    x: i32;
+}
+
+fn main() -> i32 {
+    let config = Config { port: 80 };
+    config.port = 81;
+    let point = Point { x: 1 };
+    let first = spawn(\\() -> void {
+        let x = point.x;
+    });
+    let task = \\() -> void {
+        let port = config.port;
+    };
+    let second = spawn(task);
+    join(first);
+    join(second);
+    0
+}";
+    let (code, printed) = check("unshareable", program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(
+        printed.contains(
+            "field `items` of shared struct `Bad` holds ArrayOf(Int32), which can not be shared between threads"
+        ),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains("fields of shared struct `Config` can not be assigned, other threads may be using it"),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains("`point` can not be sent to another thread, Structure(\\\"Point\\\") is not shared"),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains(
+            "`spawn` takes a closure written at the call or a function, so what it captures can be checked"
+        ),
+        "{}",
+        printed
+    );
+}
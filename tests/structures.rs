@@ -0,0 +1,78 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("structures-{}", name), program, &[], &[])
+}
+
+/// Runs `program` with `qre run`, and returns its exit code.
+fn run(name: &str, program: &str) -> i32 {
+    let (code, printed) = common::qre(&format!("structures-{}", name), program, &["run"], &[]);
+    code.unwrap_or_else(|| panic!("{} was killed: {}", name, printed))
+}
+
+const USER: &str = "
+struct User {
+    id: i32;
+    age: i32;
+    admin: bool;
+}
+";
+
+#[test]
+fn fields_are_assigned_through_every_reference() {
+    let program = format!(
+        "{}
+fn birthday(user: User) -> void {{
+    user.age = user.age + 1;
+}}
+
+fn main() -> i32 {{
+    let user = User {{ admin: false, age: 30, id: 1 }};
+    let same = user;
+    birthday(same);
+    user.id = 5;
+    if user.admin {{
+        return 0;
+    }}
+    user.age + user.id
+}}",
+        USER
+    );
+    let (code, printed) = check("assignment", &program);
+    assert_eq!(code, Some(0), "{}", printed);
+    assert_eq!(run("assignment", &program), 31 + 5);
+}
+
+#[test]
+fn literals_and_assignments_are_checked_against_the_fields() {
+    let program = format!(
+        "{}
+fn main() -> i32 {{
+    let first = User {{ id: 1, age: 2, admin: false, id: 3 }};
+    let second = User {{ id: 1, name: 2, age: 3, admin: true }};
+    let third = User {{ id: 1 }};
+    let fourth = User {{ id: 1, age: 2 }};
+    let ghost = Ghost {{ id: 1 }};
+    let number = 4;
+    number.id = 2;
+    first.name = 3;
+    first.admin = 1;
+    0
+}}",
+        USER
+    );
+    let (code, printed) = check("fields", &program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(printed.contains("field `id` is given more than once"), "{}", printed);
+    assert_eq!(printed.matches("structure `User` has no field `name`").count(), 2, "{}", printed);
+    assert!(
+        printed.contains("missing fields `age`, `admin` in literal of structure `User`"),
+        "{}",
+        printed
+    );
+    assert!(printed.contains("missing field `admin` in literal of structure `User`"), "{}", printed);
+    assert!(printed.contains("structure `Ghost` is not defined"), "{}", printed);
+    assert!(printed.contains("only structures have fields, found Int32"), "{}", printed);
+    assert!(printed.contains("expected Bool, found Int32"), "{}", printed);
+}
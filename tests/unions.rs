@@ -0,0 +1,89 @@
+mod common;
+
+/// Checks `program` with `qre`, and returns its exit code and what it printed.
+fn check(name: &str, program: &str) -> (Option<i32>, String) {
+    common::qre(&format!("unions-{}", name), program, &[], &[])
+}
+
+/// Runs `program` with `qre run`, and returns its exit code.
+fn run(name: &str, program: &str) -> i32 {
+    let (code, printed) = common::qre(&format!("unions-{}", name), program, &["run"], &[]);
+    code.unwrap_or_else(|| panic!("{} was killed: {}", name, printed))
+}
+
+const FIND: &str = "
+struct NotFound {
+    code: i32;
+}
+
+fn find(key: i32) -> i32 | NotFound {
+    if key > 0 {
+        return key;
+    }
+    NotFound { code: 40 }
+}
+";
+
+#[test]
+fn is_and_match_narrow_a_union() {
+    let program = format!(
+        "{}
+fn main() -> i32 {{
+    let found = find(3);
+    let total = 0;
+    if found is i32 {{
+        total = total + found;
+    }} else {{
+        total = total + found.code;
+    }}
+    let maybe: i32 | none = none;
+    if maybe is none {{
+        total = total + 1;
+    }}
+    total + match find(0) {{
+        value: i32 => value,
+        error: NotFound => error.code,
+    }}
+}}",
+        FIND
+    );
+    let (code, printed) = check("narrowing", &program);
+    assert_eq!(code, Some(0), "{}", printed);
+    assert_eq!(run("narrowing", &program), 3 + 1 + 40);
+}
+
+#[test]
+fn unions_must_be_narrowed_before_use() {
+    let program = format!(
+        "{}
+fn main() -> i32 {{
+    let found = find(3);
+    let number = 1;
+    let flag = number is i32;
+    let never = found is bool;
+    let plain: i32 = found;
+    match found {{
+        value: i32 => value,
+    }}
+}}",
+        FIND
+    );
+    let (code, printed) = check("not-narrowed", &program);
+    assert_eq!(code, Some(1), "{}", printed);
+    assert!(printed.contains("`is` can only test values of a union type, found Int32"), "{}", printed);
+    assert!(
+        printed.contains("a value of type UnionOf(Int32, Structure(\\\"NotFound\\\")) can never be Bool"),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains("expected Int32, found UnionOf(Int32, Structure(\\\"NotFound\\\"))"),
+        "{}",
+        printed
+    );
+    assert!(
+        printed.contains("match is not exhaustive, Structure(\\\"NotFound\\\") not covered"),
+        "{}",
+        printed
+    );
+}